@@ -0,0 +1,322 @@
+use clap::Parser;
+use peer::viewsync::{SyncAction, ViewSync};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::process;
+
+// Explores every interleaving of clock ticks, message deliveries and byzantine
+// messages of the view synchroniser for a small cluster, checking in each state
+// that honest views stay within the protocol bound. Byzantine nodes sign with
+// exactly `faults` identities, as peers running with a fixed validator set
+// ensure.
+#[derive(Parser, Debug)]
+#[command(name = "model", about = "Exhaustive check of Fever view synchronisation for small clusters")]
+struct Args {
+    /// Number of nodes, byzantine ones included
+    #[arg(long, default_value_t = 4)]
+    nodes: usize,
+
+    /// Byzantine nodes. They announce any view to anyone, and certify any view
+    /// an honest node announced
+    #[arg(long, default_value_t = 1)]
+    faults: usize,
+
+    /// Clock ticks per view
+    #[arg(long, default_value_t = 2)]
+    view_length: u64,
+
+    /// Time stops once the clock of a node without offset reaches this view
+    #[arg(long, default_value_t = 3)]
+    max_view: u64,
+
+    /// Largest initial offset of an honest clock, in ticks
+    #[arg(long, default_value_t = 1)]
+    skew: u64,
+
+    /// Stop exploring after this many states
+    #[arg(long, default_value_t = 5_000_000)]
+    max_states: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Kind {
+    View,
+    Certificate,
+}
+
+// A message between honest nodes that has been sent but not delivered yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Packet {
+    from: u8,
+    to: u8,
+    kind: Kind,
+    view: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct State {
+    time: u64,
+    // The largest initial offset, so no honest clock may ever pass
+    // `time + fastest`.
+    fastest: u64,
+    nodes: Vec<ViewSync<u8>>,
+    in_flight: BTreeSet<Packet>,
+    // Views some honest node announced. With their own f announcements the
+    // byzantine nodes can certify any of them.
+    announced: BTreeSet<u64>,
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Start(Vec<u64>),
+    Tick,
+    Deliver(Packet),
+    ByzantineView { from: u8, to: u8, view: u64 },
+    ByzantineCertificate { to: u8, view: u64 },
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::Start(offsets) => write!(f, "start with clock offsets {:?}", offsets),
+            Step::Tick => write!(f, "tick"),
+            Step::Deliver(packet) => write!(
+                f,
+                "node {} receives {:?} {} from node {}",
+                packet.to, packet.kind, packet.view, packet.from
+            ),
+            Step::ByzantineView { from, to, view } => {
+                write!(f, "byzantine node {} announces view {} to node {}", from, view, to)
+            }
+            Step::ByzantineCertificate { to, view } => {
+                write!(f, "byzantine nodes send node {} a certificate for view {}", to, view)
+            }
+        }
+    }
+}
+
+struct Model {
+    honest: usize,
+    nodes: usize,
+    threshold: usize,
+    view_length: u64,
+    max_view: u64,
+    skew: u64,
+    // Honest views may differ by at most this many.
+    bound: u64,
+}
+
+impl Model {
+    fn initial_states(&self) -> Vec<(Step, State)> {
+        let mut states = Vec::new();
+        let mut offsets = vec![0; self.honest];
+        loop {
+            let mut state = State {
+                time: 0,
+                fastest: offsets.iter().copied().max().unwrap_or(0),
+                nodes: offsets
+                    .iter()
+                    .map(|offset| ViewSync::new(self.view_length, self.threshold).with_offset(*offset))
+                    .collect(),
+                in_flight: BTreeSet::new(),
+                announced: BTreeSet::new(),
+            };
+            for node in 0..self.honest {
+                let actions = state.nodes[node].on_time(0);
+                self.apply(&mut state, node, actions);
+            }
+            states.push((Step::Start(offsets.clone()), state));
+
+            // Next combination of offsets, like counting in base skew + 1.
+            let Some(position) = offsets.iter().position(|offset| *offset < self.skew) else {
+                return states;
+            };
+            offsets[position] += 1;
+            offsets[..position].fill(0);
+        }
+    }
+
+    fn successors(&self, state: &State) -> Vec<(Step, State)> {
+        let mut next = Vec::new();
+
+        if state.time < self.max_view * self.view_length {
+            let mut after = state.clone();
+            after.time += 1;
+            for node in 0..self.honest {
+                let actions = after.nodes[node].on_time(after.time);
+                self.apply(&mut after, node, actions);
+            }
+            next.push((Step::Tick, after));
+        }
+
+        for packet in &state.in_flight {
+            let mut after = state.clone();
+            after.in_flight.remove(packet);
+            let to = packet.to as usize;
+            let actions = match packet.kind {
+                Kind::View => after.nodes[to].on_view_message(packet.from, packet.view, after.time),
+                Kind::Certificate => after.nodes[to].on_certificate(packet.view, after.time),
+            };
+            self.apply(&mut after, to, actions);
+            next.push((Step::Deliver(*packet), after));
+        }
+
+        for to in 0..self.honest {
+            for view in state.nodes[to].view() + 1..=self.max_view + 1 {
+                for from in self.honest..self.nodes {
+                    let mut after = state.clone();
+                    let actions = after.nodes[to].on_view_message(from as u8, view, after.time);
+                    self.apply(&mut after, to, actions);
+                    let step = Step::ByzantineView {
+                        from: from as u8,
+                        to: to as u8,
+                        view,
+                    };
+                    next.push((step, after));
+                }
+                if self.nodes > self.honest && state.announced.contains(&view) {
+                    let mut after = state.clone();
+                    let actions = after.nodes[to].on_certificate(view, after.time);
+                    self.apply(&mut after, to, actions);
+                    next.push((Step::ByzantineCertificate { to: to as u8, view }, after));
+                }
+            }
+        }
+        next
+    }
+
+    // Sends what honest `node` asked for to every other honest node.
+    fn apply(&self, state: &mut State, node: usize, actions: Vec<SyncAction>) {
+        for action in actions {
+            let (kind, view) = match action {
+                SyncAction::SendView(view) => {
+                    state.announced.insert(view);
+                    (Kind::View, view)
+                }
+                SyncAction::SendCertificate(view) => (Kind::Certificate, view),
+                SyncAction::Entered(_) | SyncAction::Bumped { .. } => continue,
+            };
+            for to in (0..self.honest).filter(|to| *to != node) {
+                state.in_flight.insert(Packet {
+                    from: node as u8,
+                    to: to as u8,
+                    kind,
+                    view,
+                });
+            }
+        }
+        // A node ignores messages for views it already reached, so delivering
+        // them changes nothing. Dropping them keeps the state space small.
+        let nodes = &state.nodes;
+        state.in_flight.retain(|packet| nodes[packet.to as usize].view() < packet.view);
+    }
+
+    fn gap(&self, state: &State) -> u64 {
+        let views = state.nodes.iter().map(|node| node.view());
+        views.clone().max().unwrap_or(0) - views.min().unwrap_or(0)
+    }
+
+    fn check(&self, state: &State) -> Option<String> {
+        let gap = self.gap(state);
+        if gap > self.bound {
+            let views: Vec<_> = state.nodes.iter().map(|node| node.view()).collect();
+            return Some(format!(
+                "honest views {:?} are {} apart, more than the bound of {}",
+                views, gap, self.bound
+            ));
+        }
+        for (index, node) in state.nodes.iter().enumerate() {
+            let clock = node.clock(state.time);
+            if clock > state.time + state.fastest {
+                return Some(format!(
+                    "node {} clock is at {}, ahead of every honest clock at time {}",
+                    index, clock, state.time
+                ));
+            }
+        }
+        None
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    if args.faults >= args.nodes || args.nodes > u8::MAX as usize {
+        eprintln!("Need at least one honest node and at most {} nodes", u8::MAX);
+        process::exit(2);
+    }
+    let model = Model {
+        honest: args.nodes - args.faults,
+        nodes: args.nodes,
+        threshold: args.faults + 1,
+        view_length: args.view_length.max(1),
+        max_view: args.max_view,
+        skew: args.skew,
+        bound: args.skew.div_ceil(args.view_length.max(1)),
+    };
+
+    // Breadth first, so the first violation found has a shortest trace.
+    let mut seen: HashMap<State, usize> = HashMap::new();
+    let mut steps: Vec<(Option<usize>, Step)> = Vec::new();
+    let mut queue = VecDeque::new();
+    for (step, state) in model.initial_states() {
+        if !seen.contains_key(&state) {
+            seen.insert(state.clone(), steps.len());
+            queue.push_back((steps.len(), state));
+            steps.push((None, step));
+        }
+    }
+
+    let mut largest_gap = 0;
+    let mut truncated = false;
+    let mut violation = None;
+    while let Some((index, state)) = queue.pop_front() {
+        largest_gap = largest_gap.max(model.gap(&state));
+        if let Some(description) = model.check(&state) {
+            violation = Some((index, description));
+            break;
+        }
+        if seen.len() >= args.max_states {
+            truncated = true;
+            continue;
+        }
+        for (step, after) in model.successors(&state) {
+            if seen.contains_key(&after) {
+                continue;
+            }
+            seen.insert(after.clone(), steps.len());
+            queue.push_back((steps.len(), after));
+            steps.push((Some(index), step));
+        }
+    }
+
+    println!(
+        "Explored {} states: {} nodes ({} byzantine), {} ticks per view, views up to {}, clock skew up to {}",
+        seen.len(),
+        args.nodes,
+        args.faults,
+        model.view_length,
+        model.max_view,
+        model.skew
+    );
+    println!("Largest honest view gap: {} (bound {})", largest_gap, model.bound);
+    if truncated {
+        println!("Stopped at --max-states {}; the state space was not covered", args.max_states);
+    }
+
+    let Some((index, description)) = violation else {
+        println!("All invariants hold");
+        return;
+    };
+    println!("Violation: {}", description);
+    println!("Counter-example:");
+    let mut trace = Vec::new();
+    let mut at = Some(index);
+    while let Some(current) = at {
+        trace.push(&steps[current].1);
+        at = steps[current].0;
+    }
+    for step in trace.iter().rev() {
+        println!("  {}", step);
+    }
+    process::exit(1);
+}
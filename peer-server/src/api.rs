@@ -0,0 +1,330 @@
+use crate::auth::Caller;
+use crate::cluster::{Cluster, ClusterError, Decision, Fault, Heartbeat, Liveness, Node, Queued, Registration};
+use crate::experiment::{self, Experiment, ExperimentError, Experiments, Spec};
+use crate::shutdown;
+use crate::store::{Snapshot, Store};
+use crate::{CLUSTER, EXPERIMENTS, EXPERIMENT_STORE, STORE};
+use axum::extract::{Extension, Path, Query};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, post, put};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+// The JSON control plane, mounted under /api:
+//
+//   GET    /nodes                    registered nodes
+//   POST   /nodes                    register a node (peers do this themselves)
+//   GET    /nodes/{id}                one node and its queued commands
+//   PUT    /nodes/{id}/heartbeat      a peer's status, answered with its new commands
+//   PUT    /nodes/{id}/role           {"role": "inverted"}
+//   POST   /nodes/{id}/faults         {"kind": "drop", "rate": 0.5, "duration_ms": 10000}
+//   GET    /nodes/{id}/commands       commands for the node, ?after=<seq>
+//   GET    /membership               peer ids of live and stale nodes
+//   GET    /leader, PUT /leader      {"peer_id": "12D3KooW..."}
+//   POST   /rounds                   {"value": "ATTACK"}, proposed by the leader
+//   DELETE /rounds/current           close the round in progress on every node
+//   GET    /experiments              every experiment and its results
+//   POST   /experiments              run an experiment, see experiment::Spec
+//   GET    /experiments/{id}          one experiment and its results
+pub fn routes() -> Router {
+    Router::new()
+        .route("/nodes", get(list_nodes).post(register))
+        .route("/nodes/{id}", get(node))
+        .route("/nodes/{id}/role", put(set_role))
+        .route("/nodes/{id}/faults", post(inject_fault))
+        .route("/nodes/{id}/commands", get(commands))
+        .route("/nodes/{id}/heartbeat", put(heartbeat))
+        .route("/membership", get(membership))
+        .route("/leader", get(leader).put(set_leader))
+        .route("/rounds", post(start_round))
+        .route("/rounds/current", delete(stop_round))
+        .route("/experiments", get(list_experiments).post(start_experiment))
+        .route("/experiments/{id}", get(experiment))
+}
+
+// An error as a status code and a JSON body, e.g. {"error": "no leader is set"}.
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl From<ClusterError> for ApiError {
+    fn from(error: ClusterError) -> Self {
+        let status = match error {
+            ClusterError::UnknownNode(_) => StatusCode::NOT_FOUND,
+            ClusterError::NoLeader | ClusterError::StaleNode(_) | ClusterError::Full => StatusCode::CONFLICT,
+            ClusterError::InvalidPeerId(_) | ClusterError::InvalidRole(_) | ClusterError::InvalidFault(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+        };
+        ApiError {
+            status,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<ExperimentError> for ApiError {
+    fn from(error: ExperimentError) -> Self {
+        let status = match error {
+            ExperimentError::Cluster(error) => return error.into(),
+            ExperimentError::Unknown(_) => StatusCode::NOT_FOUND,
+            ExperimentError::Running(_) => StatusCode::CONFLICT,
+            ExperimentError::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        };
+        ApiError {
+            status,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl ApiError {
+    pub fn bad_request(message: String) -> Self {
+        ApiError {
+            status: StatusCode::BAD_REQUEST,
+            message,
+        }
+    }
+
+    pub fn unauthorized(message: String) -> Self {
+        ApiError {
+            status: StatusCode::UNAUTHORIZED,
+            message,
+        }
+    }
+
+    pub fn forbidden(message: String) -> Self {
+        ApiError {
+            status: StatusCode::FORBIDDEN,
+            message,
+        }
+    }
+
+    pub fn internal(message: String) -> Self {
+        ApiError {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            message,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+// Locks shared state, or fails the request if a handler panicked while holding it.
+pub fn lock<T>(state: &'static Mutex<T>) -> ApiResult<MutexGuard<'static, T>> {
+    state
+        .lock()
+        .map_err(|_| ApiError::internal("peer-server state is unavailable after an earlier failure".to_string()))
+}
+
+// Takes a snapshot of the cluster if the request changed it, to be saved once
+// the lock is released.
+pub fn snapshot(cluster: &mut Cluster) -> ApiResult<Option<Snapshot>> {
+    if !cluster.unsaved() {
+        return Ok(None);
+    }
+    let snapshot = snapshot_of(&STORE, &*cluster)?;
+    cluster.mark_saved();
+    Ok(snapshot)
+}
+
+// Saves a snapshot of the cluster. The change stays in effect even if it could
+// not be saved, and the next one saves it again.
+pub async fn save(snapshot: Option<Snapshot>) -> ApiResult<()> {
+    let result = write(&STORE, snapshot).await;
+    if result.is_err() {
+        lock(&CLUSTER)?.mark_unsaved();
+    }
+    result
+}
+
+pub fn snapshot_experiments(experiments: &Experiments) -> ApiResult<Option<Snapshot>> {
+    snapshot_of(&EXPERIMENT_STORE, experiments)
+}
+
+pub async fn save_experiments(snapshot: Option<Snapshot>) -> ApiResult<()> {
+    write(&EXPERIMENT_STORE, snapshot).await
+}
+
+fn snapshot_of<T: Serialize>(store: &OnceLock<Store>, state: &T) -> ApiResult<Option<Snapshot>> {
+    match store.get() {
+        Some(store) => store
+            .snapshot(state)
+            .map(Some)
+            .map_err(|e| ApiError::internal(format!("could not save state to {}: {}", store.path().display(), e))),
+        None => Ok(None),
+    }
+}
+
+// Writes on a blocking thread, so the disk holds up neither the runtime nor
+// other requests.
+async fn write(store: &'static OnceLock<Store>, snapshot: Option<Snapshot>) -> ApiResult<()> {
+    let (Some(store), Some(snapshot)) = (store.get(), snapshot) else {
+        return Ok(());
+    };
+    let failed = |e: String| ApiError::internal(format!("could not save state to {}: {}", store.path().display(), e));
+    tokio::task::spawn_blocking(move || store.write(snapshot))
+        .await
+        .map_err(|e| failed(e.to_string()))?
+        .map_err(|e| failed(e.to_string()))
+}
+
+async fn list_nodes() -> ApiResult<Json<Vec<Node>>> {
+    Ok(Json(lock(&CLUSTER)?.nodes().cloned().collect()))
+}
+
+async fn register(
+    Extension(caller): Extension<Caller>,
+    Json(registration): Json<Registration>,
+) -> ApiResult<(StatusCode, Json<Node>)> {
+    caller.speaks_for(&registration.peer_id)?;
+    let (created, node, snapshot) = {
+        let mut cluster = lock(&CLUSTER)?;
+        let peer_id = registration.peer_id.clone();
+        let created = cluster.register(registration)?;
+        (created, cluster.node(&peer_id)?.clone(), snapshot(&mut cluster)?)
+    };
+    save(snapshot).await?;
+    let status = if created { StatusCode::CREATED } else { StatusCode::OK };
+    Ok((status, Json(node)))
+}
+
+async fn node(Path(peer_id): Path<String>) -> ApiResult<Json<Node>> {
+    Ok(Json(lock(&CLUSTER)?.node(&peer_id)?.clone()))
+}
+
+#[derive(Deserialize)]
+struct RoleRequest {
+    role: String,
+}
+
+async fn set_role(Path(peer_id): Path<String>, Json(request): Json<RoleRequest>) -> ApiResult<(StatusCode, Json<Value>)> {
+    let (seq, snapshot) = {
+        let mut cluster = lock(&CLUSTER)?;
+        let seq = cluster.set_role(&peer_id, request.role)?;
+        (seq, snapshot(&mut cluster)?)
+    };
+    save(snapshot).await?;
+    Ok((StatusCode::ACCEPTED, Json(json!({ "seq": seq }))))
+}
+
+async fn inject_fault(Path(peer_id): Path<String>, Json(fault): Json<Fault>) -> ApiResult<(StatusCode, Json<Value>)> {
+    let (seq, snapshot) = {
+        let mut cluster = lock(&CLUSTER)?;
+        let seq = cluster.inject_fault(&peer_id, fault)?;
+        (seq, snapshot(&mut cluster)?)
+    };
+    save(snapshot).await?;
+    Ok((StatusCode::ACCEPTED, Json(json!({ "seq": seq }))))
+}
+
+#[derive(Deserialize)]
+struct CommandsQuery {
+    #[serde(default)]
+    after: u64,
+}
+
+async fn commands(Path(peer_id): Path<String>, Query(query): Query<CommandsQuery>) -> ApiResult<Json<Vec<Queued>>> {
+    Ok(Json(lock(&CLUSTER)?.commands_after(&peer_id, query.after)?))
+}
+
+async fn heartbeat(
+    Extension(caller): Extension<Caller>,
+    Path(peer_id): Path<String>,
+    Json(heartbeat): Json<Heartbeat>,
+) -> ApiResult<Json<Value>> {
+    caller.speaks_for(&peer_id)?;
+    let (commands, snapshot) = {
+        let mut cluster = lock(&CLUSTER)?;
+        let commands = cluster.heartbeat(&peer_id, heartbeat)?;
+        (commands, snapshot(&mut cluster)?)
+    };
+    save(snapshot).await?;
+    Ok(Json(json!({ "commands": commands, "stopping": shutdown::stopping() })))
+}
+
+async fn membership() -> ApiResult<Json<Value>> {
+    let cluster = lock(&CLUSTER)?;
+    Ok(Json(json!({
+        "live": cluster.members(Liveness::Live),
+        "stale": cluster.members(Liveness::Stale),
+    })))
+}
+
+async fn leader() -> ApiResult<Json<Value>> {
+    Ok(Json(json!({ "peer_id": lock(&CLUSTER)?.leader() })))
+}
+
+#[derive(Deserialize)]
+struct LeaderRequest {
+    peer_id: String,
+}
+
+async fn set_leader(Json(request): Json<LeaderRequest>) -> ApiResult<Json<Value>> {
+    let snapshot = {
+        let mut cluster = lock(&CLUSTER)?;
+        cluster.set_leader(&request.peer_id)?;
+        snapshot(&mut cluster)?
+    };
+    save(snapshot).await?;
+    Ok(Json(json!({ "peer_id": request.peer_id })))
+}
+
+#[derive(Deserialize)]
+struct RoundRequest {
+    value: Decision,
+}
+
+async fn start_round(Json(request): Json<RoundRequest>) -> ApiResult<(StatusCode, Json<Value>)> {
+    let (leader, snapshot) = {
+        let mut cluster = lock(&CLUSTER)?;
+        let leader = cluster.start_round(request.value)?;
+        (leader, snapshot(&mut cluster)?)
+    };
+    save(snapshot).await?;
+    Ok((StatusCode::ACCEPTED, Json(json!({ "leader": leader, "value": request.value }))))
+}
+
+async fn stop_round() -> ApiResult<(StatusCode, Json<Value>)> {
+    let (nodes, snapshot) = {
+        let mut cluster = lock(&CLUSTER)?;
+        let nodes = cluster.stop_round();
+        (nodes, snapshot(&mut cluster)?)
+    };
+    save(snapshot).await?;
+    Ok((StatusCode::ACCEPTED, Json(json!({ "nodes": nodes }))))
+}
+
+async fn list_experiments() -> ApiResult<Json<Vec<Experiment>>> {
+    Ok(Json(lock(&EXPERIMENTS)?.list().to_vec()))
+}
+
+async fn start_experiment(Json(spec): Json<Spec>) -> ApiResult<(StatusCode, Json<Value>)> {
+    let nodes = experiment::participants(&*lock(&CLUSTER)?, &spec)?;
+    let (id, snapshot) = {
+        let mut experiments = lock(&EXPERIMENTS)?;
+        let id = experiments.start(spec.clone(), nodes.clone())?;
+        (id, snapshot_experiments(&experiments)?)
+    };
+    save_experiments(snapshot).await?;
+    tokio::spawn(experiment::run(id, spec, nodes));
+    Ok((StatusCode::ACCEPTED, Json(json!({ "id": id }))))
+}
+
+async fn experiment(Path(id): Path<u64>) -> ApiResult<Json<Experiment>> {
+    Ok(Json(lock(&EXPERIMENTS)?.get(id)?.clone()))
+}
@@ -0,0 +1,231 @@
+use crate::api::ApiError;
+use crate::TOKENS;
+use axum::extract::Request;
+use axum::http::{header, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+// The cookie a browser keeps the token in once it opened a page with ?token=.
+const COOKIE: &str = "peer_server_token";
+
+// What a token lets its holder do. Each level includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    // Read the dashboard, the timeline and the API.
+    Viewer,
+    // Also register, send heartbeats and push events, as peers do.
+    Peer,
+    // Also change roles, leaders, rounds and faults and run experiments.
+    Operator,
+}
+
+impl Access {
+    fn name(&self) -> &'static str {
+        match self {
+            Access::Viewer => "viewer",
+            Access::Peer => "peer",
+            Access::Operator => "operator",
+        }
+    }
+}
+
+// Who made a request. A peer token only speaks for the peer it was given to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Caller {
+    pub access: Access,
+    pub peer: Option<String>,
+}
+
+impl Caller {
+    // Anyone, when peer-server runs without tokens.
+    const ANYONE: Caller = Caller {
+        access: Access::Operator,
+        peer: None,
+    };
+
+    // Fails unless the caller may report for `peer_id`.
+    pub fn speaks_for(&self, peer_id: &str) -> Result<(), ApiError> {
+        match &self.peer {
+            Some(peer) if peer != peer_id => Err(ApiError::forbidden(format!(
+                "the token belongs to {}, not {}",
+                peer, peer_id
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+// The tokens peer-server accepts. With none, every request is allowed.
+pub struct Tokens {
+    tokens: Vec<(String, Caller)>,
+    // Whether the cookie may only travel over https, as when serving TLS.
+    secure: bool,
+}
+
+impl Tokens {
+    // Comma-separated tokens from PEER_SERVER_VIEWER_TOKENS,
+    // PEER_SERVER_PEER_TOKENS and PEER_SERVER_OPERATOR_TOKENS.
+    pub fn from_env() -> Result<Self, String> {
+        let variable = |name| std::env::var(name).unwrap_or_default();
+        Tokens::parse(
+            &variable("PEER_SERVER_VIEWER_TOKENS"),
+            &variable("PEER_SERVER_PEER_TOKENS"),
+            &variable("PEER_SERVER_OPERATOR_TOKENS"),
+        )
+    }
+
+    // Peer tokens are given as <peer id>=<token>, e.g. 12D3KooW...=secret.
+    pub fn parse(viewers: &str, peers: &str, operators: &str) -> Result<Self, String> {
+        let list = |value: &str| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        let mut tokens = Vec::new();
+        for token in list(viewers) {
+            tokens.push((token, Caller { access: Access::Viewer, peer: None }));
+        }
+        for entry in list(peers) {
+            let (peer, token) = entry
+                .split_once('=')
+                .filter(|(peer, token)| !peer.is_empty() && !token.is_empty())
+                .ok_or_else(|| "peer tokens must be given as <peer id>=<token>".to_string())?;
+            tokens.push((
+                token.to_string(),
+                Caller {
+                    access: Access::Peer,
+                    peer: Some(peer.to_string()),
+                },
+            ));
+        }
+        for token in list(operators) {
+            tokens.push((token, Caller { access: Access::Operator, peer: None }));
+        }
+        Ok(Tokens { tokens, secure: false })
+    }
+
+    // Marks the cookie Secure, for a peer-server serving https.
+    pub fn with_secure_cookie(self, secure: bool) -> Self {
+        Tokens { secure, ..self }
+    }
+
+    // The Set-Cookie value that keeps `token` in the browser.
+    pub fn cookie(&self, token: &str) -> String {
+        let secure = if self.secure { "; Secure" } else { "" };
+        format!("{}={}; Path=/; HttpOnly; SameSite=Strict{}", COOKIE, token, secure)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    // The caller with the highest access any matching token grants. Every
+    // token is compared in full so that the time taken does not tell how much
+    // of one matched.
+    pub fn caller(&self, presented: &str) -> Option<Caller> {
+        self.tokens
+            .iter()
+            .filter(|(token, _)| same(token.as_bytes(), presented.as_bytes()))
+            .map(|(_, caller)| caller)
+            .max_by_key(|caller| caller.access)
+            .cloned()
+    }
+}
+
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (x, y)| difference | (x ^ y)) == 0
+}
+
+// What a request needs: reading needs a viewer, what peers report about
+// themselves needs a peer, and anything else an operator.
+pub fn required(method: &Method, path: &str) -> Access {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match (method, segments.as_slice()) {
+        (&Method::GET | &Method::HEAD, _) => Access::Viewer,
+        (&Method::POST, ["api", "nodes"] | ["events"]) => Access::Peer,
+        (&Method::PUT, ["api", "nodes", _, "heartbeat"]) => Access::Peer,
+        _ => Access::Operator,
+    }
+}
+
+// The bearer token of the request, or for browsers, which cannot set headers
+// on links and event streams, the token cookie.
+pub fn presented(request: &Request) -> Option<&str> {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    bearer.or_else(|| {
+        request
+            .headers()
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(';'))
+            .find_map(|pair| pair.trim().strip_prefix(COOKIE)?.strip_prefix('='))
+    })
+}
+
+// A page opened with ?token=..., and the address without it.
+fn query_token(request: &Request) -> Option<(&str, String)> {
+    let query = request.uri().query()?;
+    let token = query.split('&').find_map(|pair| pair.strip_prefix("token="))?;
+    let rest: Vec<&str> = query.split('&').filter(|pair| !pair.starts_with("token=")).collect();
+    let location = if rest.is_empty() {
+        request.uri().path().to_string()
+    } else {
+        format!("{}?{}", request.uri().path(), rest.join("&"))
+    };
+    Some((token, location))
+}
+
+// Lets the request through if its token grants the access it needs, and tells
+// the handlers who made it.
+//
+// A token in the query, as in a link to the dashboard, is moved into an
+// HttpOnly, SameSite=Strict cookie, Secure when serving TLS, and the browser
+// sent back to the address without it, so that the token stays out of logs,
+// history and links.
+pub async fn check(mut request: Request, next: Next) -> Result<Response, ApiError> {
+    let Some(tokens) = TOKENS.get().filter(|tokens| !tokens.is_empty()) else {
+        request.extensions_mut().insert(Caller::ANYONE);
+        return Ok(next.run(request).await);
+    };
+    if let Some((token, location)) = query_token(&request) {
+        if tokens.caller(token).is_none() {
+            return Err(ApiError::unauthorized("a valid token is required".to_string()));
+        }
+        let cookie = tokens.cookie(token);
+        let headers = [
+            (header::LOCATION, HeaderValue::from_str(&location)),
+            (header::SET_COOKIE, HeaderValue::from_str(&cookie)),
+        ];
+        let mut response = StatusCode::SEE_OTHER.into_response();
+        for (name, value) in headers {
+            let value = value.map_err(|_| ApiError::bad_request("invalid token".to_string()))?;
+            response.headers_mut().insert(name, value);
+        }
+        return Ok(response);
+    }
+    let required = required(request.method(), request.uri().path());
+    match presented(&request).and_then(|token| tokens.caller(token)) {
+        None => Err(ApiError::unauthorized("a valid token is required".to_string())),
+        Some(caller) if caller.access < required => Err(ApiError::forbidden(format!(
+            "{} access is required, the token grants {}",
+            required.name(),
+            caller.access.name()
+        ))),
+        Some(caller) => {
+            request.extensions_mut().insert(caller);
+            Ok(next.run(request).await)
+        }
+    }
+}
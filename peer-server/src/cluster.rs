@@ -0,0 +1,367 @@
+use libp2p_identity::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Commands kept per node for peers that poll late. Older ones are dropped.
+const KEPT_COMMANDS: usize = 100;
+// Nodes peer-server keeps track of. Registrations beyond this are refused.
+pub const MAX_NODES: usize = 256;
+// A node that sent no heartbeat for this long is marked stale. Peers send one
+// every second.
+pub const STALE_AFTER_MS: u64 = 5_000;
+
+// The two instructions a leader can give, as the peers spell them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Decision {
+    Attack,
+    Retreat,
+}
+
+// Something to inject into a node for `duration_ms`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Fault {
+    // Drop each outgoing message with probability `rate`.
+    Drop { rate: f64, duration_ms: u64 },
+    // Hold every outgoing message back for `delay_ms`.
+    Delay { delay_ms: u64, duration_ms: u64 },
+    // Neither send nor handle any message.
+    Crash { duration_ms: u64 },
+}
+
+// What a node is asked to do. Peers receive these in answer to their heartbeats.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    StartRound { value: Decision },
+    StopRound,
+    SetRole { role: String },
+    InjectFault { fault: Fault },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Queued {
+    pub seq: u64,
+    pub command: Command,
+}
+
+// What a peer tells about itself when it registers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Registration {
+    pub peer_id: String,
+    // The behaviour the peer runs, e.g. "honest" or "inverted".
+    pub role: String,
+    pub version: String,
+    #[serde(default)]
+    pub addresses: Vec<String>,
+}
+
+// What a peer sends every second: where it listens, how far it got, and the
+// last command it received.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Heartbeat {
+    #[serde(default)]
+    pub after: u64,
+    // The behaviour the peer runs now, which only changes once it applied a
+    // `set_role` command.
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    pub view: u64,
+    pub height: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Liveness {
+    Live,
+    // No heartbeat for `STALE_AFTER_MS`.
+    Stale,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Node {
+    pub peer_id: String,
+    pub role: String,
+    pub version: String,
+    pub addresses: Vec<String>,
+    pub status: Liveness,
+    pub view: u64,
+    pub height: u64,
+    pub registered_at_ms: u64,
+    pub last_seen_ms: u64,
+    // Sequence number of the latest command queued for the node.
+    pub last_seq: u64,
+    pub commands: VecDeque<Queued>,
+}
+
+impl Node {
+    fn queue(&mut self, command: Command) -> u64 {
+        self.last_seq += 1;
+        self.commands.push_back(Queued {
+            seq: self.last_seq,
+            command,
+        });
+        if self.commands.len() > KEPT_COMMANDS {
+            self.commands.pop_front();
+        }
+        self.last_seq
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClusterError {
+    UnknownNode(String),
+    StaleNode(String),
+    InvalidPeerId(String),
+    Full,
+    NoLeader,
+    InvalidRole(String),
+    InvalidFault(String),
+}
+
+impl fmt::Display for ClusterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClusterError::UnknownNode(peer_id) => write!(f, "no node {} is registered", peer_id),
+            ClusterError::StaleNode(peer_id) => write!(f, "node {} sent no heartbeat recently", peer_id),
+            ClusterError::InvalidPeerId(peer_id) => write!(f, "'{}' is not a peer id", peer_id),
+            ClusterError::Full => write!(f, "{} nodes are registered already", MAX_NODES),
+            ClusterError::NoLeader => write!(f, "no leader is set"),
+            ClusterError::InvalidRole(role) => write!(f, "invalid role '{}'", role),
+            ClusterError::InvalidFault(reason) => write!(f, "invalid fault: {}", reason),
+        }
+    }
+}
+
+// The nodes that registered and the commands waiting for them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cluster {
+    nodes: BTreeMap<String, Node>,
+    leader: Option<String>,
+    // Whether anything worth saving changed since the last save. Heartbeats
+    // that only move a node's view and height do not count.
+    #[serde(skip)]
+    unsaved: bool,
+}
+
+impl Default for Cluster {
+    fn default() -> Self {
+        Cluster::new()
+    }
+}
+
+impl Cluster {
+    pub const fn new() -> Self {
+        Cluster {
+            nodes: BTreeMap::new(),
+            leader: None,
+            unsaved: false,
+        }
+    }
+
+    pub fn unsaved(&self) -> bool {
+        self.unsaved
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.unsaved = false;
+    }
+
+    pub fn mark_unsaved(&mut self) {
+        self.unsaved = true;
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.values()
+    }
+
+    pub fn node(&self, peer_id: &str) -> Result<&Node, ClusterError> {
+        self.nodes
+            .get(peer_id)
+            .ok_or_else(|| ClusterError::UnknownNode(peer_id.to_string()))
+    }
+
+    fn node_mut(&mut self, peer_id: &str) -> Result<&mut Node, ClusterError> {
+        self.nodes
+            .get_mut(peer_id)
+            .ok_or_else(|| ClusterError::UnknownNode(peer_id.to_string()))
+    }
+
+    // Adds a node, or refreshes one that registers again. Returns whether it
+    // is new. Commands already queued for the node are kept for a peer that
+    // lost touch, while a restarted peer asks only for those after the
+    // node's `last_seq`.
+    pub fn register(&mut self, registration: Registration) -> Result<bool, ClusterError> {
+        validate_peer_id(&registration.peer_id)?;
+        validate_role(&registration.role)?;
+        if !self.nodes.contains_key(&registration.peer_id) && self.nodes.len() >= MAX_NODES {
+            return Err(ClusterError::Full);
+        }
+        let now = now_ms();
+        self.unsaved = true;
+        match self.nodes.get_mut(&registration.peer_id) {
+            Some(node) => {
+                node.role = registration.role;
+                node.version = registration.version;
+                node.addresses = registration.addresses;
+                node.status = Liveness::Live;
+                node.last_seen_ms = now;
+                Ok(false)
+            }
+            None => {
+                self.nodes.insert(
+                    registration.peer_id.clone(),
+                    Node {
+                        peer_id: registration.peer_id,
+                        role: registration.role,
+                        version: registration.version,
+                        addresses: registration.addresses,
+                        status: Liveness::Live,
+                        view: 0,
+                        height: 0,
+                        registered_at_ms: now,
+                        last_seen_ms: now,
+                        last_seq: 0,
+                        commands: VecDeque::new(),
+                    },
+                );
+                Ok(true)
+            }
+        }
+    }
+
+    // Commands queued for the node after `after`.
+    pub fn commands_after(&self, peer_id: &str, after: u64) -> Result<Vec<Queued>, ClusterError> {
+        let node = self.node(peer_id)?;
+        Ok(node.commands.iter().filter(|queued| queued.seq > after).cloned().collect())
+    }
+
+    // Records a heartbeat and returns the commands the node has not received yet.
+    pub fn heartbeat(&mut self, peer_id: &str, heartbeat: Heartbeat) -> Result<Vec<Queued>, ClusterError> {
+        if let Some(role) = &heartbeat.role {
+            validate_role(role)?;
+        }
+        let node = self.node_mut(peer_id)?;
+        let mut changed = false;
+        if node.status == Liveness::Stale {
+            println!("Node {} is live again", peer_id);
+            node.status = Liveness::Live;
+            changed = true;
+        }
+        node.last_seen_ms = now_ms();
+        if let Some(role) = heartbeat.role {
+            changed |= node.role != role;
+            node.role = role;
+        }
+        node.addresses = heartbeat.addresses;
+        node.view = heartbeat.view;
+        node.height = heartbeat.height;
+        self.unsaved |= changed;
+        self.commands_after(peer_id, heartbeat.after)
+    }
+
+    // Marks nodes stale that sent no heartbeat for `STALE_AFTER_MS`.
+    pub fn sweep(&mut self, now_ms: u64) {
+        for node in self.nodes.values_mut() {
+            if node.status == Liveness::Live && now_ms.saturating_sub(node.last_seen_ms) > STALE_AFTER_MS {
+                println!("Node {} is stale, last heartbeat at {}", node.peer_id, node.last_seen_ms);
+                node.status = Liveness::Stale;
+                self.unsaved = true;
+            }
+        }
+    }
+
+    // Peer ids of the nodes with the given liveness.
+    pub fn members(&self, status: Liveness) -> Vec<&str> {
+        self.nodes
+            .values()
+            .filter(|node| node.status == status)
+            .map(|node| node.peer_id.as_str())
+            .collect()
+    }
+
+    // Asks the node to switch behaviour. Its role changes once a heartbeat
+    // reports the switch.
+    pub fn set_role(&mut self, peer_id: &str, role: String) -> Result<u64, ClusterError> {
+        validate_role(&role)?;
+        let seq = self.node_mut(peer_id)?.queue(Command::SetRole { role });
+        self.unsaved = true;
+        Ok(seq)
+    }
+
+    pub fn inject_fault(&mut self, peer_id: &str, fault: Fault) -> Result<u64, ClusterError> {
+        if let Fault::Drop { rate, .. } = fault {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(ClusterError::InvalidFault(format!("drop rate {} is not between 0 and 1", rate)));
+            }
+        }
+        let seq = self.node_mut(peer_id)?.queue(Command::InjectFault { fault });
+        self.unsaved = true;
+        Ok(seq)
+    }
+
+    pub fn leader(&self) -> Option<&str> {
+        self.leader.as_deref()
+    }
+
+    pub fn set_leader(&mut self, peer_id: &str) -> Result<(), ClusterError> {
+        self.node(peer_id)?;
+        self.leader = Some(peer_id.to_string());
+        self.unsaved = true;
+        Ok(())
+    }
+
+    // Asks the leader to propose `value`. Returns the leader.
+    pub fn start_round(&mut self, value: Decision) -> Result<String, ClusterError> {
+        let leader = self.leader.clone().ok_or(ClusterError::NoLeader)?;
+        let node = self.node_mut(&leader)?;
+        if node.status == Liveness::Stale {
+            return Err(ClusterError::StaleNode(leader));
+        }
+        node.queue(Command::StartRound { value });
+        self.unsaved = true;
+        Ok(leader)
+    }
+
+    // Asks every node to close the round in progress with the votes it has.
+    pub fn stop_round(&mut self) -> usize {
+        for node in self.nodes.values_mut() {
+            node.queue(Command::StopRound);
+        }
+        self.unsaved = true;
+        self.nodes.len()
+    }
+}
+
+// Roles are the peer's strategy names, optionally with an argument like
+// "delayed:8000". The peer itself checks that it knows the strategy.
+pub fn validate_role(role: &str) -> Result<(), ClusterError> {
+    let valid = !role.is_empty()
+        && role
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == ':');
+    if valid {
+        Ok(())
+    } else {
+        Err(ClusterError::InvalidRole(role.to_string()))
+    }
+}
+
+// Peers register under their libp2p peer id, e.g. "12D3KooW...".
+pub fn validate_peer_id(peer_id: &str) -> Result<(), ClusterError> {
+    match peer_id.parse::<PeerId>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(ClusterError::InvalidPeerId(peer_id.to_string())),
+    }
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
@@ -0,0 +1,217 @@
+use crate::cluster::Liveness;
+use crate::shutdown;
+use crate::timeline::Round;
+use crate::{CLUSTER, TIMELINE};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::Html;
+use futures::stream::{self, Stream};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::watch;
+
+// How often the dashboard state is rebuilt. Browsers only get it when it changed.
+const UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+// Views shown on the dashboard.
+const SHOWN_ROUNDS: usize = 20;
+
+// The latest dashboard state, shared with every open dashboard.
+static SNAPSHOTS: OnceLock<watch::Sender<Snapshot>> = OnceLock::new();
+
+// Everything the dashboard shows, sent to the browser as one JSON object.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+struct Snapshot {
+    leader: Option<String>,
+    nodes: Vec<NodeStatus>,
+    // Newest first.
+    rounds: Vec<Round>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct NodeStatus {
+    peer_id: String,
+    role: String,
+    status: Liveness,
+    view: u64,
+    height: u64,
+    // Latest NTP offset of the node's clock, in microseconds.
+    offset_us: Option<i64>,
+}
+
+fn snapshots() -> &'static watch::Sender<Snapshot> {
+    SNAPSHOTS.get_or_init(|| watch::channel(Snapshot::default()).0)
+}
+
+// Rebuilds the dashboard state from the registered nodes and their events
+// for as long as peer-server runs.
+pub async fn run() {
+    let mut interval = tokio::time::interval(UPDATE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let Some(snapshot) = snapshot() else {
+            continue;
+        };
+        snapshots().send_if_modified(|current| {
+            if *current == snapshot {
+                return false;
+            }
+            *current = snapshot;
+            true
+        });
+    }
+}
+
+// None while a lock is unusable after an earlier failure.
+fn snapshot() -> Option<Snapshot> {
+    let (leader, nodes) = {
+        let cluster = CLUSTER.lock().ok()?;
+        let nodes: Vec<NodeStatus> = cluster
+            .nodes()
+            .map(|node| NodeStatus {
+                peer_id: node.peer_id.clone(),
+                role: node.role.clone(),
+                status: node.status,
+                view: node.view,
+                height: node.height,
+                offset_us: None,
+            })
+            .collect();
+        (cluster.leader().map(str::to_string), nodes)
+    };
+    let timeline = TIMELINE.lock().ok()?;
+    let nodes = nodes
+        .into_iter()
+        .map(|node| NodeStatus {
+            offset_us: timeline.offset_us(&node.peer_id),
+            ..node
+        })
+        .collect();
+    Some(Snapshot {
+        leader,
+        nodes,
+        rounds: timeline.rounds(SHOWN_ROUNDS),
+    })
+}
+
+// Handles requests to "/dashboard/stream": the dashboard state as server-sent
+// events, the current one first and then every change, until peer-server stops.
+pub async fn stream() -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let receiver = snapshots().subscribe();
+    let events = stream::unfold((receiver, true), |(mut receiver, first)| async move {
+        if !first {
+            tokio::select! {
+                changed = receiver.changed() => changed.ok()?,
+                _ = shutdown::stopped() => return None,
+            }
+        }
+        let event = Event::default()
+            .event("snapshot")
+            .json_data(&*receiver.borrow_and_update())
+            .ok()?;
+        Some((Ok(event), (receiver, false)))
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+// Handles requests to "/"
+pub async fn page() -> Html<&'static str> {
+    Html(PAGE)
+}
+
+const PAGE: &str = r#"<html>
+<head>
+    <title>Peer Server</title>
+    <style>
+        body { font-family: sans-serif; }
+        table { border-collapse: collapse; margin-bottom: 1em; }
+        th, td { border: 1px solid #ccc; padding: 2px 6px; font-size: 12px; text-align: left; vertical-align: top; }
+        .stale { color: #999; }
+        .leader { font-weight: bold; color: #2196F3; }
+        .timeout { color: #d32f2f; }
+        .committed { color: #388e3c; }
+        #connection { color: #777; }
+    </style>
+</head>
+<body>
+    <h1>Peer Server</h1>
+    <p><a href="/timeline">Timeline</a> - <span id="connection">connecting...</span></p>
+    <h2>Nodes</h2>
+    <table>
+        <thead><tr><th>node</th><th>status</th><th>role</th><th>byzantine</th><th>view</th><th>height</th><th>clock offset (ms)</th><th>votes in its view</th></tr></thead>
+        <tbody id="nodes"></tbody>
+    </table>
+    <h2>Rounds</h2>
+    <table>
+        <thead><tr><th>view</th><th>leader</th><th>proposed</th><th>votes received</th><th>decided</th><th>committed</th><th>timeouts</th></tr></thead>
+        <tbody id="rounds"></tbody>
+    </table>
+    <script>
+        const escape = (text) => String(text).replace(/[&<>"']/g, (c) => `&#${c.charCodeAt(0)};`);
+        const short = (node) => escape(node.slice(-6));
+        const counts = (tally) => Object.entries(tally || {}).map(([value, count]) => `${count} ${escape(value)}`).join(', ');
+
+        async function toggle(peerId, byzantine) {
+            const response = await fetch(`/api/nodes/${encodeURIComponent(peerId)}/role`, {
+                method: 'PUT',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify({ role: byzantine ? 'inverted' : 'honest' }),
+            });
+            if (!response.ok) {
+                console.error('Toggle failed');
+            }
+        }
+
+        // Peer ids only ever reach the page as data, never as code.
+        document.getElementById('nodes').addEventListener('change', (event) => {
+            const peerId = event.target.dataset.peerId;
+            if (peerId) {
+                toggle(peerId, event.target.checked);
+            }
+        });
+
+        function render(snapshot) {
+            const rounds = new Map(snapshot.rounds.map((round) => [round.view, round]));
+            document.getElementById('nodes').innerHTML = snapshot.nodes.map((node) => {
+                const round = rounds.get(node.view);
+                const leader = node.peer_id === snapshot.leader ? ' class="leader"' : '';
+                const offset = node.offset_us === null ? '' : (node.offset_us / 1000).toFixed(1);
+                return `<tr class="${escape(node.status)}">
+                    <td title="${escape(node.peer_id)}"${leader}>${short(node.peer_id)}</td>
+                    <td>${escape(node.status)}</td>
+                    <td>${escape(node.role)}</td>
+                    <td><input type="checkbox" data-peer-id="${escape(node.peer_id)}" ${node.role !== 'honest' ? 'checked' : ''}></td>
+                    <td>${node.view}</td>
+                    <td>${node.height}</td>
+                    <td>${offset}</td>
+                    <td>${round ? counts(round.votes[node.peer_id]) : ''}</td>
+                </tr>`;
+            }).join('');
+            document.getElementById('rounds').innerHTML = snapshot.rounds.map((round) => {
+                const received = {};
+                for (const tally of Object.values(round.votes)) {
+                    for (const [value, count] of Object.entries(tally)) {
+                        received[value] = (received[value] || 0) + count;
+                    }
+                }
+                const decided = Object.entries(round.decided).map(([node, value]) => `${short(node)} ${escape(value)}`).join(', ');
+                return `<tr>
+                    <td>${round.view}</td>
+                    <td class="leader">${round.leader ? short(round.leader) : ''}</td>
+                    <td>${round.proposed ? escape(round.proposed) : ''}</td>
+                    <td>${counts(received)}</td>
+                    <td>${decided}</td>
+                    <td class="committed">${round.committed.map(short).join(', ')}</td>
+                    <td class="timeout">${round.timeouts.map(short).join(', ')}</td>
+                </tr>`;
+            }).join('');
+        }
+
+        const events = new EventSource('/dashboard/stream');
+        events.addEventListener('snapshot', (event) => render(JSON.parse(event.data)));
+        events.onopen = () => document.getElementById('connection').textContent = 'live';
+        events.onerror = () => document.getElementById('connection').textContent = 'reconnecting...';
+    </script>
+</body>
+</html>
+"#;
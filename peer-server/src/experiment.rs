@@ -0,0 +1,411 @@
+use crate::api;
+use crate::cluster::{now_ms, validate_role, Cluster, ClusterError, Decision, Fault, Liveness};
+use crate::timeline::Timeline;
+use crate::{CLUSTER, EXPERIMENTS, TIMELINE};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+// How long nodes get to report the roles an experiment gave them.
+const ROLE_TIMEOUT: Duration = Duration::from_secs(10);
+// Time after a round is closed for the last commits to reach peer-server.
+const SETTLE: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// What an experiment runs, e.g.
+// {"rounds": 10, "round_ms": 5000, "value": "ATTACK", "leaders": ["12D3KooW..."],
+//  "byzantine": {"12D3KooW...": "inverted"},
+//  "faults": [{"at_ms": 12000, "peer_id": "12D3KooW...", "kind": "crash", "duration_ms": 5000}]}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Spec {
+    pub rounds: u32,
+    // How long each round runs before it is closed and its decisions are collected.
+    pub round_ms: u64,
+    #[serde(default = "attack")]
+    pub value: Decision,
+    // Leader of each round in turn. Empty lets every node lead in turn.
+    #[serde(default)]
+    pub leaders: Vec<String>,
+    // Roles of the byzantine nodes. Every other node runs "honest".
+    #[serde(default)]
+    pub byzantine: BTreeMap<String, String>,
+    #[serde(default)]
+    pub faults: Vec<ScheduledFault>,
+}
+
+fn attack() -> Decision {
+    Decision::Attack
+}
+
+// A fault injected `at_ms` after the first round started.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledFault {
+    pub at_ms: u64,
+    pub peer_id: String,
+    #[serde(flatten)]
+    pub fault: Fault,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Running,
+    Finished,
+    Failed,
+}
+
+// What the nodes decided in one round.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoundResult {
+    pub round: u32,
+    pub leader: String,
+    pub started_at_ms: u64,
+    // The block the leader proposed, if it proposed one.
+    pub height: Option<u64>,
+    pub proposed: Option<String>,
+    // The value each node committed at that height, or None if it committed nothing.
+    pub decisions: BTreeMap<String, Option<String>>,
+    // Whether every honest node committed the same value.
+    pub agreement: bool,
+    // Whether the honest nodes committed what an honest leader proposed.
+    // Always true under a byzantine leader.
+    pub validity: bool,
+}
+
+// An experiment and its results so far.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Experiment {
+    pub id: u64,
+    pub spec: Spec,
+    pub nodes: Vec<String>,
+    pub status: Status,
+    pub started_at_ms: u64,
+    pub finished_at_ms: Option<u64>,
+    pub error: Option<String>,
+    pub rounds: Vec<RoundResult>,
+    pub agreed_rounds: u32,
+    pub valid_rounds: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExperimentError {
+    Cluster(ClusterError),
+    Invalid(String),
+    // Only one experiment runs at a time.
+    Running(u64),
+    Unknown(u64),
+}
+
+impl fmt::Display for ExperimentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExperimentError::Cluster(e) => write!(f, "{}", e),
+            ExperimentError::Invalid(reason) => write!(f, "invalid experiment: {}", reason),
+            ExperimentError::Running(id) => write!(f, "experiment {} is still running", id),
+            ExperimentError::Unknown(id) => write!(f, "no experiment {}", id),
+        }
+    }
+}
+
+impl From<ClusterError> for ExperimentError {
+    fn from(e: ClusterError) -> Self {
+        ExperimentError::Cluster(e)
+    }
+}
+
+// Every experiment peer-server ran, oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Experiments {
+    runs: Vec<Experiment>,
+}
+
+impl Default for Experiments {
+    fn default() -> Self {
+        Experiments::new()
+    }
+}
+
+impl Experiments {
+    pub const fn new() -> Self {
+        Experiments { runs: Vec::new() }
+    }
+
+    pub fn list(&self) -> &[Experiment] {
+        &self.runs
+    }
+
+    pub fn get(&self, id: u64) -> Result<&Experiment, ExperimentError> {
+        self.runs
+            .iter()
+            .find(|experiment| experiment.id == id)
+            .ok_or(ExperimentError::Unknown(id))
+    }
+
+    fn get_mut(&mut self, id: u64) -> Option<&mut Experiment> {
+        self.runs.iter_mut().find(|experiment| experiment.id == id)
+    }
+
+    // Records a new running experiment and returns its id.
+    pub fn start(&mut self, spec: Spec, nodes: Vec<String>) -> Result<u64, ExperimentError> {
+        if let Some(running) = self.runs.iter().find(|experiment| experiment.status == Status::Running) {
+            return Err(ExperimentError::Running(running.id));
+        }
+        let id = self.runs.last().map_or(1, |experiment| experiment.id + 1);
+        self.runs.push(Experiment {
+            id,
+            spec,
+            nodes,
+            status: Status::Running,
+            started_at_ms: now_ms(),
+            finished_at_ms: None,
+            error: None,
+            rounds: Vec::new(),
+            agreed_rounds: 0,
+            valid_rounds: 0,
+        });
+        Ok(id)
+    }
+
+    pub fn record(&mut self, id: u64, result: RoundResult) {
+        if let Some(experiment) = self.get_mut(id) {
+            experiment.agreed_rounds += u32::from(result.agreement);
+            experiment.valid_rounds += u32::from(result.validity);
+            experiment.rounds.push(result);
+        }
+    }
+
+    pub fn finish(&mut self, id: u64, error: Option<String>) {
+        if let Some(experiment) = self.get_mut(id) {
+            experiment.status = if error.is_some() { Status::Failed } else { Status::Finished };
+            experiment.finished_at_ms = Some(now_ms());
+            experiment.error = error;
+        }
+    }
+
+    // Fails the experiments that were running when peer-server stopped.
+    pub fn abandon_running(&mut self) {
+        for experiment in self.runs.iter_mut().filter(|experiment| experiment.status == Status::Running) {
+            experiment.status = Status::Failed;
+            experiment.error = Some("peer-server stopped while it ran".to_string());
+        }
+    }
+}
+
+// The live nodes that take part in the experiment, after checking that the
+// spec only names them.
+pub fn participants(cluster: &Cluster, spec: &Spec) -> Result<Vec<String>, ExperimentError> {
+    if spec.rounds == 0 {
+        return Err(ExperimentError::Invalid("rounds must be at least 1".to_string()));
+    }
+    if spec.round_ms == 0 {
+        return Err(ExperimentError::Invalid("round_ms must be at least 1".to_string()));
+    }
+    let nodes: Vec<String> = cluster.members(Liveness::Live).into_iter().map(str::to_string).collect();
+    if nodes.is_empty() {
+        return Err(ExperimentError::Invalid("no node is live".to_string()));
+    }
+    for role in spec.byzantine.values() {
+        validate_role(role)?;
+    }
+    let named = spec
+        .leaders
+        .iter()
+        .chain(spec.byzantine.keys())
+        .chain(spec.faults.iter().map(|scheduled| &scheduled.peer_id));
+    for peer_id in named {
+        if cluster.node(peer_id)?.status == Liveness::Stale {
+            return Err(ClusterError::StaleNode(peer_id.clone()).into());
+        }
+    }
+    Ok(nodes)
+}
+
+// Drives the cluster through the experiment and records its results. Failures
+// end the experiment and are recorded with it.
+pub async fn run(id: u64, spec: Spec, nodes: Vec<String>) {
+    println!("Experiment {} started with {} nodes", id, nodes.len());
+    let result = drive(id, &spec, &nodes).await;
+    if let Err(e) = &result {
+        println!("Experiment {} failed: {}", id, e);
+    } else {
+        println!("Experiment {} finished", id);
+    }
+    update(|experiments| experiments.finish(id, result.err())).await;
+}
+
+async fn drive(id: u64, spec: &Spec, nodes: &[String]) -> Result<(), String> {
+    let roles: BTreeMap<&str, &str> = nodes
+        .iter()
+        .map(|node| {
+            let role = spec.byzantine.get(node).map_or("honest", String::as_str);
+            (node.as_str(), role)
+        })
+        .collect();
+    with_cluster(|cluster| {
+        for (node, role) in &roles {
+            cluster.set_role(node, role.to_string())?;
+        }
+        Ok(())
+    })
+    .await?;
+    wait_for_roles(&roles).await?;
+
+    let faults: Vec<_> = spec
+        .faults
+        .iter()
+        .cloned()
+        .map(|scheduled| {
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(scheduled.at_ms)).await;
+                if let Err(e) = with_cluster(|cluster| cluster.inject_fault(&scheduled.peer_id, scheduled.fault)).await {
+                    println!("Could not inject a fault into {}: {}", scheduled.peer_id, e);
+                }
+            })
+        })
+        .collect();
+    let result = rounds(id, spec, nodes, &roles).await;
+    for fault in faults {
+        fault.abort();
+    }
+    result
+}
+
+async fn rounds(id: u64, spec: &Spec, nodes: &[String], roles: &BTreeMap<&str, &str>) -> Result<(), String> {
+    let leaders = if spec.leaders.is_empty() { nodes } else { &spec.leaders[..] };
+    for round in 0..spec.rounds {
+        let leader = &leaders[round as usize % leaders.len()];
+        let started_at_ms = now_ms();
+        with_cluster(|cluster| {
+            cluster.set_leader(leader)?;
+            cluster.start_round(spec.value)
+        })
+        .await?;
+        tokio::time::sleep(Duration::from_millis(spec.round_ms)).await;
+        with_cluster(|cluster| Ok(cluster.stop_round())).await?;
+        tokio::time::sleep(SETTLE).await;
+
+        let result = with_timeline(|timeline| result(timeline, round + 1, leader, started_at_ms, nodes, roles))?;
+        update(|experiments| experiments.record(id, result)).await;
+    }
+    Ok(())
+}
+
+// Waits until every node reports the role it was given.
+async fn wait_for_roles(roles: &BTreeMap<&str, &str>) -> Result<(), String> {
+    let deadline = tokio::time::Instant::now() + ROLE_TIMEOUT;
+    loop {
+        let pending: Vec<String> = {
+            let cluster = CLUSTER.lock().map_err(|_| "peer-server state is unavailable".to_string())?;
+            roles
+                .iter()
+                .filter(|(node, role)| cluster.node(node).map_or(true, |node| node.role != **role))
+                .map(|(node, _)| node.to_string())
+                .collect()
+        };
+        if pending.is_empty() {
+            return Ok(());
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(format!("nodes did not switch roles: {}", pending.join(", ")));
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+// What the nodes decided in the round `leader` was asked to lead at
+// `started_at_ms`, going by the first block it proposed since then. Proposals
+// are matched by the peer's clock, so earlier rounds whose proposals arrive
+// late do not shift the ones that follow.
+pub fn result(
+    timeline: &Timeline,
+    round: u32,
+    leader: &str,
+    started_at_ms: u64,
+    nodes: &[String],
+    roles: &BTreeMap<&str, &str>,
+) -> RoundResult {
+    let proposal = timeline
+        .entries()
+        .iter()
+        .map(|entry| &entry.event)
+        .find(|event| event.event == "proposed" && event.node == leader && event.at_ms >= started_at_ms);
+    let height = proposal.and_then(|event| event.fields.get("height")).and_then(Value::as_u64);
+    let proposed = proposal
+        .and_then(|event| event.fields.get("value"))
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let decisions = decisions(timeline, nodes, height);
+    let honest: Vec<&Option<String>> = decisions
+        .iter()
+        .filter(|(node, _)| roles.get(node.as_str()) == Some(&"honest"))
+        .map(|(_, decision)| decision)
+        .collect();
+    let agreement = honest.iter().all(|decision| decision.is_some() && *decision == honest[0]);
+    let validity =
+        roles.get(leader) != Some(&"honest") || (proposed.is_some() && honest.iter().all(|decision| **decision == proposed));
+    RoundResult {
+        round,
+        leader: leader.to_string(),
+        started_at_ms,
+        height,
+        proposed,
+        decisions,
+        agreement,
+        validity,
+    }
+}
+
+// The value each node committed at `height`.
+fn decisions(timeline: &Timeline, nodes: &[String], height: Option<u64>) -> BTreeMap<String, Option<String>> {
+    let mut decisions: BTreeMap<String, Option<String>> = nodes.iter().map(|node| (node.clone(), None)).collect();
+    let Some(height) = height else {
+        return decisions;
+    };
+    for entry in timeline.entries() {
+        let event = &entry.event;
+        if event.event != "committed" || event.fields.get("height").and_then(Value::as_u64) != Some(height) {
+            continue;
+        }
+        if let Some(decision @ None) = decisions.get_mut(&event.node) {
+            *decision = event.fields.get("value").and_then(Value::as_str).map(str::to_string);
+        }
+    }
+    decisions
+}
+
+fn with_timeline<T>(read: impl FnOnce(&Timeline) -> T) -> Result<T, String> {
+    let timeline = api::lock(&TIMELINE).map_err(|e| e.message().to_string())?;
+    Ok(read(&timeline))
+}
+
+// Changes the cluster and saves it, as an API request would.
+async fn with_cluster<T>(change: impl FnOnce(&mut Cluster) -> Result<T, ClusterError>) -> Result<T, String> {
+    let (result, snapshot) = {
+        let mut cluster = api::lock(&CLUSTER).map_err(|e| e.message().to_string())?;
+        let result = change(&mut cluster).map_err(|e| e.to_string());
+        (result, api::snapshot(&mut cluster).map_err(|e| e.message().to_string())?)
+    };
+    api::save(snapshot).await.map_err(|e| e.message().to_string())?;
+    result
+}
+
+// Changes the experiments and saves them. A failed save is only logged, since
+// the experiment goes on either way.
+async fn update(change: impl FnOnce(&mut Experiments)) {
+    let snapshot = {
+        let Ok(mut experiments) = EXPERIMENTS.lock() else {
+            return;
+        };
+        change(&mut experiments);
+        api::snapshot_experiments(&experiments)
+    };
+    let saved = match snapshot {
+        Ok(snapshot) => api::save_experiments(snapshot).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = saved {
+        println!("{}", e.message());
+    }
+}
@@ -0,0 +1,32 @@
+use auth::Tokens;
+use cluster::Cluster;
+use experiment::Experiments;
+use std::sync::{Mutex, OnceLock};
+use store::Store;
+use timeline::Timeline;
+
+pub mod api;
+pub mod auth;
+pub mod cluster;
+pub mod dashboard;
+pub mod experiment;
+pub mod shutdown;
+pub mod store;
+pub mod timeline;
+pub mod tls;
+
+// Global state to hold the registered peers (shared between all requests)
+pub static CLUSTER: Mutex<Cluster> = Mutex::new(Cluster::new());
+
+// Where the cluster is saved whenever it changes
+pub static STORE: OnceLock<Store> = OnceLock::new();
+
+// Experiments run so far, and where they are saved
+pub static EXPERIMENTS: Mutex<Experiments> = Mutex::new(Experiments::new());
+pub static EXPERIMENT_STORE: OnceLock<Store> = OnceLock::new();
+
+// Tokens that grant access to peer-server
+pub static TOKENS: OnceLock<Tokens> = OnceLock::new();
+
+// Events pushed by the peers, merged into one timeline
+pub static TIMELINE: Mutex<Timeline> = Mutex::new(Timeline::new());
@@ -1,142 +1,127 @@
-use axum::{response::Html, routing::get, Router};
-use std::{net::SocketAddr, sync::Mutex};
-use serde::{Serialize, Deserialize};
-use std::fs;
-
-// Define a struct to represent the state of each peer
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct PeerState {
-    peer_id: String,
-    role: String,
-}
-
-// Define a struct to represent the state of the entire network
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct NetworkState {
-    peer_list: Vec<PeerState>,
-}
+use axum::{extract::Extension, middleware, response::Html, routing::get, Json, Router};
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use peer_server::api::{self, lock, ApiError};
+use peer_server::auth::{self, Caller, Tokens};
+use peer_server::cluster::{self, Cluster};
+use peer_server::experiment::Experiments;
+use peer_server::store::Store;
+use peer_server::timeline::{Entry, Event};
+use peer_server::{dashboard, shutdown, tls, CLUSTER, EXPERIMENTS, EXPERIMENT_STORE, STORE, TIMELINE, TOKENS};
+use std::error::Error;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::net::TcpListener;
 
+// How long requests in progress may take to finish once peer-server stops
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
 
-// Global state to hold the network state (shared between all requests)
-static NETWORK_STATE: Mutex<NetworkState> = Mutex::new(NetworkState { peer_list: Vec::new() });
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    // Pick up where the last run left off. Nodes that do not send heartbeats
+    // any more are marked stale by the sweep below.
+    let path = std::env::var_os("PEER_SERVER_STATE").map_or_else(|| PathBuf::from("network_state.json"), PathBuf::from);
+    let store = Store::new(path);
+    match store.load::<Cluster>() {
+        Ok(Some(cluster)) => {
+            println!("Loaded {} nodes from {}", cluster.nodes().count(), store.path().display());
+            *CLUSTER.lock().map_err(|_| "peer-server state is unavailable")? = cluster;
+        }
+        Ok(None) => println!("No saved state at {}, starting empty", store.path().display()),
+        Err(e) => return Err(format!("could not load {}: {}", store.path().display(), e).into()),
+    }
+    let _ = STORE.set(store);
 
+    let path = std::env::var_os("PEER_SERVER_EXPERIMENTS").map_or_else(|| PathBuf::from("experiments.json"), PathBuf::from);
+    let store = Store::new(path);
+    match store.load::<Experiments>() {
+        Ok(Some(mut experiments)) => {
+            println!("Loaded {} experiments from {}", experiments.list().len(), store.path().display());
+            experiments.abandon_running();
+            *EXPERIMENTS.lock().map_err(|_| "peer-server state is unavailable")? = experiments;
+        }
+        Ok(None) => {}
+        Err(e) => return Err(format!("could not load {}: {}", store.path().display(), e).into()),
+    }
+    let _ = EXPERIMENT_STORE.set(store);
 
-#[tokio::main]
-async fn main() {
     // build our application with routes
     let app = Router::new()
-        .route("/", get(handler))
-        .route("/toggle", get(toggle_handler))
-        .route("/update", get(update_handler));
-
-    // run it
-    let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
-    println!("listening on {}", addr);
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
-}
-
-// Handles requests to "/"
-async fn handler() -> Html<String> {
-    let network_state = NETWORK_STATE.lock().unwrap();
+        .route("/", get(dashboard::page))
+        .route("/dashboard/stream", get(dashboard::stream))
+        .nest("/api", api::routes())
+        .route("/events", get(events_handler).post(collect_handler))
+        .route("/timeline", get(timeline_handler))
+        .layer(middleware::from_fn(auth::check));
 
-    let mut peer_list_html = String::new();
-    for peer in &network_state.peer_list {
-        let checked = if peer.role == "byzantine" { "checked" } else { "" };
-        peer_list_html.push_str(&format!(
-            "<li>
-                {} - Role: {}
-                <label class=\"switch\">
-                    <input type=\"checkbox\" {} onclick=\"toggle('{}')\">
-                    <span class=\"slider round\"></span>
-                </label>
-            </li>",
-            peer.peer_id, peer.role, checked, peer.peer_id
-        ));
+    let tls_dir = std::env::var_os("PEER_SERVER_TLS_DIR");
+    let tokens = Tokens::from_env()?.with_secure_cookie(tls_dir.is_some());
+    if tokens.is_empty() {
+        println!("No tokens set, anyone who can reach peer-server may control the cluster");
+    } else {
+        println!("Accepting {} tokens", tokens.len());
     }
+    let _ = TOKENS.set(tokens);
 
-    let html = format!(
-        "<html>
-        <head>
-            <title>Peer Server</title>
-        </head>
-        <body>
-            <h1>Peer List</h1>
-            <ul>
-                {}
-            </ul>
-            <script>
-                async function toggle(peerId) {{
-                    const response = await fetch(`/toggle?peer_id=${{peerId}}`);
-                    if (response.ok) {{
-                        location.reload(); // Reload the page after toggling
-                    }} else {{
-                        console.error('Toggle failed');
-                    }}
-                }}
-            </script>
-            <style>
-                .switch {{
-                    position: relative;
-                    display: inline-block;
-                    width: 60px;
-                    height: 34px;
-                }}
-                .switch input {{
-                    opacity: 0;
-                    width: 0;
-                    height: 0;
-                }}
-                .slider {{
-                    position: absolute;
-                    cursor: pointer;
-                    top: 0;
-                    left: 0;
-                    right: 0;
-                    bottom: 0;
-                    background-color: #ccc;
-                    -webkit-transition: .4s;
-                    transition: .4s;
-                }}
-                .slider:before {{
-                    position: absolute;
-                    content: \"\";
-                    height: 26px;
-                    width: 26px;
-                    left: 4px;
-                    bottom: 4px;
-                    background-color: white;
-                    -webkit-transition: .4s;
-                    transition: .4s;
-                }}
-                input:checked + .slider {{
-                    background-color: #2196F3;
-                }}
-                input:focus + .slider {{
-                    box-shadow: 0 0 1px #2196F3;
-                }}
-                input:checked + .slider:before {{
-                    -webkit-transform: translateX(26px);
-                    -ms-transform: translateX(26px);
-                    transform: translateX(26px);
-                }}
-                .slider.round {{
-                    border-radius: 34px;
-                }}
-                .slider.round:before {{
-                    border-radius: 50%;
-                }}
-            </style>
-        </body>
-        </html>",
-        peer_list_html
-    );
+    // Mark nodes that stopped sending heartbeats
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            let snapshot = {
+                let Ok(mut cluster) = CLUSTER.lock() else {
+                    continue;
+                };
+                cluster.sweep(cluster::now_ms());
+                api::snapshot(&mut cluster)
+            };
+            let saved = match snapshot {
+                Ok(snapshot) => api::save(snapshot).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = saved {
+                println!("{}", e.message());
+            }
+        }
+    });
 
-    Html(html)
-}
+    // Keep the dashboard state up to date
+    tokio::spawn(dashboard::run());
 
+    // run it
+    // Until SIGTERM or Ctrl-C, then finish the requests in progress
+    let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
+    match tls_dir {
+        Some(dir) => {
+            let _ = rustls::crypto::ring::default_provider().install_default();
+            let (cert, key) = tls::certificate(Path::new(&dir))?;
+            let config = RustlsConfig::from_pem_file(cert, key).await?;
+            let handle = Handle::new();
+            tokio::spawn({
+                let handle = handle.clone();
+                async move {
+                    shutdown::signal().await;
+                    handle.graceful_shutdown(Some(DRAIN_TIMEOUT));
+                }
+            });
+            println!("listening on https://{}", addr);
+            axum_server::bind_rustls(addr, config)
+                .handle(handle)
+                .serve(app.into_make_service())
+                .await?;
+        }
+        None => {
+            let listener = TcpListener::bind(addr).await?;
+            println!("listening on {}", addr);
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown::signal())
+                .await?;
+        }
+    }
+    println!("peer-server stopped");
+    Ok(())
+}
 
 /*
 // Global state to hold the network state (shared between all requests)
@@ -250,43 +235,58 @@ async fn handler() -> Html<&'static str> {
 
 */
 
-// Handles requests to "/toggle?peer_id=peer01"
-async fn toggle_handler(params: axum::extract::Query<(String,)>) -> Html<&'static str> {
-    let (peer_id,) = params.0;
-
-    // Lock the global network state
-    let mut network_state = NETWORK_STATE.lock().unwrap();
-
-    // Find the peer with the specified ID and toggle its role
-    for peer in &mut network_state.peer_list {
-        if peer.peer_id == peer_id {
-            if peer.role == "normal" {
-                peer.role = "byzantine".to_string();
-            } else {
-                peer.role = "normal".to_string();
-            }
-        }
+// Handles requests to "/events" posted by peers: one JSON event per line
+async fn collect_handler(Extension(caller): Extension<Caller>, body: String) -> Result<Html<String>, ApiError> {
+    // Reject the whole batch if a line is malformed, so that none is added twice
+    // if the peer fixes and resends it.
+    let mut events = Vec::new();
+    for (number, line) in body.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let event: Event = serde_json::from_str(line)
+            .map_err(|e| ApiError::bad_request(format!("line {}: {}", number + 1, e)))?;
+        caller.speaks_for(&event.node)?;
+        events.push(event);
     }
 
-    Html("Toggle successful")
+    let count = events.len();
+    let mut timeline = lock(&TIMELINE)?;
+    for event in events {
+        timeline.add(event);
+    }
+    Ok(Html(format!("Collected {} events", count)))
 }
 
-// Handles requests to "/update"
-async fn update_handler() -> Html<&'static str> {
-    // Lock the global network state
-    let network_state = NETWORK_STATE.lock().unwrap();
-
-    // Clone the network state to avoid holding the lock while serializing
-    let network_state = network_state.clone();
-
-    // Drop the lock to prevent deadlock and unnecessary blocking
-    drop(network_state);
+// Handles requests to "/events": the merged timeline as JSON
+async fn events_handler() -> Result<Json<Vec<Entry>>, ApiError> {
+    Ok(Json(lock(&TIMELINE)?.entries().iter().cloned().collect()))
+}
 
-    // Serialize the network state to JSON
-    let json = serde_json::to_string_pretty(&NETWORK_STATE.lock().unwrap().peer_list).unwrap();
+// Handles requests to "/timeline"
+async fn timeline_handler() -> Result<Html<String>, ApiError> {
+    let timeline = lock(&TIMELINE)?.render();
 
-    // Write the JSON to a file
-    fs::write("network_state.json", json).unwrap();
+    let html = format!(
+        "<html>
+        <head>
+            <title>Peer Server - Timeline</title>
+            <meta http-equiv=\"refresh\" content=\"2\">
+            <style>
+                body {{ font-family: sans-serif; }}
+                table {{ border-collapse: collapse; }}
+                th, td {{ border: 1px solid #ccc; padding: 2px 6px; vertical-align: top; font-size: 12px; }}
+                .proposed {{ font-weight: bold; color: #2196F3; }}
+                .timeout {{ color: #d32f2f; }}
+                .qc, .committed {{ color: #388e3c; }}
+                .received, .entered {{ color: #777; }}
+            </style>
+        </head>
+        <body>
+            <h1>Timeline</h1>
+            <p><a href=\"/\">Dashboard</a> - views are newest first, times are NTP corrected</p>
+            {}
+        </body>
+        </html>",
+        timeline
+    );
 
-    Html("Update successful")
+    Ok(Html(html))
 }
@@ -0,0 +1,75 @@
+use crate::cluster::{now_ms, Liveness};
+use crate::CLUSTER;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+// How long the peers get to learn that peer-server stops. They send a
+// heartbeat every second.
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(3);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Whether peer-server is shutting down.
+static STOPPING: OnceLock<watch::Sender<bool>> = OnceLock::new();
+
+fn stopping_sender() -> &'static watch::Sender<bool> {
+    STOPPING.get_or_init(|| watch::channel(false).0)
+}
+
+pub fn stopping() -> bool {
+    *stopping_sender().borrow()
+}
+
+// Resolves once peer-server starts shutting down.
+pub async fn stopped() {
+    let mut stopping = stopping_sender().subscribe();
+    let _ = stopping.wait_for(|stopping| *stopping).await;
+}
+
+// Waits for SIGTERM or Ctrl-C, then tells the live peers that peer-server
+// stops, in the answer to their next heartbeat. Returns once each of them
+// got it, or after `NOTIFY_TIMEOUT`, for the server to drain its connections.
+pub async fn signal() {
+    let interrupt = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = interrupt => {}
+        _ = terminate => {}
+    }
+
+    println!("Shutting down, telling the peers");
+    stopping_sender().send_replace(true);
+    // Heartbeats answered after this saw that peer-server stops.
+    let since = now_ms();
+    let deadline = Instant::now() + NOTIFY_TIMEOUT;
+    loop {
+        let waiting = match CLUSTER.lock() {
+            Ok(cluster) => cluster
+                .nodes()
+                .filter(|node| node.status == Liveness::Live && node.last_seen_ms <= since)
+                .count(),
+            Err(_) => 0,
+        };
+        if waiting == 0 {
+            break;
+        }
+        if Instant::now() >= deadline {
+            println!("{} peers did not hear that peer-server stops", waiting);
+            break;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
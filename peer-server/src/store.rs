@@ -0,0 +1,116 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+#[derive(Debug)]
+pub enum StoreError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::Io(e) => write!(f, "{}", e),
+            StoreError::Json(e) => write!(f, "invalid state: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        StoreError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        StoreError::Json(e)
+    }
+}
+
+// Keeps a part of peer-server's state in one JSON file. A save writes a temporary file
+// next to it, syncs it and renames it over the old one, so the file always
+// holds either the previous state or the new one in full.
+//
+// Callers take a snapshot while they hold the lock on the state and write it
+// after releasing the lock, so that requests do not wait for the disk. Snapshots
+// are numbered in the order they were taken, and one older than the file is
+// not written.
+pub struct Store {
+    path: PathBuf,
+    taken: AtomicU64,
+    // Number of the snapshot in the file, held while writing.
+    written: Mutex<u64>,
+}
+
+// The state as JSON at one point in time.
+pub struct Snapshot {
+    number: u64,
+    json: Vec<u8>,
+}
+
+impl Store {
+    pub fn new(path: PathBuf) -> Self {
+        Store {
+            path,
+            taken: AtomicU64::new(0),
+            written: Mutex::new(0),
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    // The saved state, or None if nothing was saved yet.
+    pub fn load<T: DeserializeOwned>(&self) -> Result<Option<T>, StoreError> {
+        match fs::read(&self.path) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn snapshot<T: Serialize>(&self, state: &T) -> Result<Snapshot, StoreError> {
+        Ok(Snapshot {
+            number: self.taken.fetch_add(1, Ordering::SeqCst) + 1,
+            json: serde_json::to_vec_pretty(state)?,
+        })
+    }
+
+    // Writes the snapshot unless a later one was written already. Blocks on
+    // the disk.
+    pub fn write(&self, snapshot: Snapshot) -> Result<(), StoreError> {
+        let mut written = self.written.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if snapshot.number <= *written {
+            return Ok(());
+        }
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+        let mut file = File::create(&temporary)?;
+        file.write_all(&snapshot.json)?;
+        file.sync_all()?;
+        fs::rename(&temporary, &self.path)?;
+        // The rename only survives a crash once the directory is synced too.
+        let directory = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(directory)?.sync_all()?;
+        *written = snapshot.number;
+        Ok(())
+    }
+
+    pub fn save<T: Serialize>(&self, state: &T) -> Result<(), StoreError> {
+        self.write(self.snapshot(state)?)
+    }
+}
@@ -0,0 +1,309 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+
+// Oldest entries are dropped beyond this many.
+const MAX_ENTRIES: usize = 100_000;
+// Oldest views are dropped beyond this many, and events of one view beyond this
+// many are only kept in the merged list.
+const MAX_VIEWS: usize = 1000;
+const MAX_VIEW_ENTRIES: usize = 10_000;
+// Views and events shown on the timeline page.
+const SHOWN_VIEWS: usize = 50;
+const SHOWN_EVENTS: usize = 200;
+
+// One event as a peer writes it, e.g.
+// {"at_ms":1718000000000,"node":"12D3KooW...","event":"view_entered","view":3}
+// Fields other than the time, node and kind are kept as they come.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub at_ms: u64,
+    pub node: String,
+    pub event: String,
+    #[serde(flatten)]
+    pub fields: Map<String, Value>,
+}
+
+impl Event {
+    fn field(&self, name: &str) -> Option<&Value> {
+        self.fields.get(name)
+    }
+
+    fn view(&self) -> Option<u64> {
+        self.field("view").and_then(Value::as_u64)
+    }
+}
+
+// An event placed on the merged timeline. `time_ms` is the node's wall clock
+// corrected by the NTP offset of its latest `clock_sync`, so that events of
+// nodes with drifting clocks still sort in the order they happened.
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    pub time_ms: i64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+// Events of every node, merged in corrected time order.
+pub struct Timeline {
+    entries: VecDeque<Entry>,
+    // The events that name a view, by view, so that the dashboard and the
+    // timeline page only go through the views they show.
+    views: BTreeMap<u64, VecDeque<Entry>>,
+    // Latest NTP offset of each node's clock, in microseconds.
+    offsets_us: BTreeMap<String, i64>,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Timeline::new()
+    }
+}
+
+impl Timeline {
+    pub const fn new() -> Self {
+        Timeline {
+            entries: VecDeque::new(),
+            views: BTreeMap::new(),
+            offsets_us: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, event: Event) {
+        if event.event == "clock_sync" {
+            if let Some(offset) = event.field("offset_us").and_then(Value::as_i64) {
+                self.offsets_us.insert(event.node.clone(), offset);
+            }
+        }
+        let offset_us = self.offsets_us.get(&event.node).copied().unwrap_or(0);
+        let entry = Entry {
+            time_ms: event.at_ms as i64 + offset_us / 1000,
+            event,
+        };
+        if let Some(view) = entry.event.view() {
+            let entries = self.views.entry(view).or_default();
+            if entries.len() < MAX_VIEW_ENTRIES {
+                insert(entries, entry.clone());
+            }
+            if self.views.len() > MAX_VIEWS {
+                self.views.pop_first();
+            }
+        }
+        insert(&mut self.entries, entry);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    pub fn entries(&self) -> &VecDeque<Entry> {
+        &self.entries
+    }
+
+    // Latest NTP offset of the node's clock, in microseconds.
+    pub fn offset_us(&self, node: &str) -> Option<i64> {
+        self.offsets_us.get(node).copied()
+    }
+
+    // The latest `count` views, newest first.
+    pub fn rounds(&self, count: usize) -> Vec<Round> {
+        self.views
+            .iter()
+            .rev()
+            .take(count)
+            .map(|(&view, entries)| {
+                let mut round = Round {
+                    view,
+                    ..Round::default()
+                };
+                for event in entries.iter().map(|entry| &entry.event) {
+                    let node = || event.node.clone();
+                    let value = || event.field("value").map(text).unwrap_or_default();
+                    match event.event.as_str() {
+                        "proposed" => {
+                            round.leader = Some(node());
+                            round.proposed = Some(value());
+                        }
+                        "vote_received" => *round.votes.entry(node()).or_default().entry(value()).or_default() += 1,
+                        "qc_formed" => {
+                            round.decided.insert(node(), value());
+                        }
+                        "committed" => round.committed.push(node()),
+                        "timeout" => round.timeouts.push(node()),
+                        _ => {}
+                    }
+                }
+                round
+            })
+            .collect()
+    }
+
+    // The latest views as rows and nodes as columns, with what each node did
+    // in each view, followed by the latest events.
+    pub fn render(&self) -> String {
+        let views: Vec<(u64, View)> = self
+            .views
+            .iter()
+            .rev()
+            .take(SHOWN_VIEWS)
+            .map(|(&view, entries)| {
+                let mut row = View::default();
+                for entry in entries {
+                    row.add(entry);
+                }
+                (view, row)
+            })
+            .collect();
+        let nodes: Vec<&str> = {
+            let mut nodes: Vec<&str> = views.iter().flat_map(|(_, row)| row.cells.keys().chain(row.received.keys())).copied().collect();
+            nodes.sort_unstable();
+            nodes.dedup();
+            nodes
+        };
+
+        let mut html = String::from("<table class=\"timeline\"><tr><th>view</th><th>leader</th>");
+        for node in &nodes {
+            let _ = write!(html, "<th title=\"{}\">{}</th>", escape(node), escape(short(node)));
+        }
+        html.push_str("</tr>");
+        for (view, row) in &views {
+            let _ = write!(
+                html,
+                "<tr><td>{}</td><td>{}</td>",
+                view,
+                row.leader.map_or(String::new(), |leader| escape(short(leader)))
+            );
+            for node in &nodes {
+                html.push_str("<td>");
+                for item in row.cells.get(node).into_iter().flatten() {
+                    let _ = write!(html, "<div class=\"{}\">{}</div>", item.class, escape(&item.text));
+                }
+                if let Some(received) = row.received.get(node) {
+                    let counts: Vec<String> = received.iter().map(|(value, count)| format!("{count} {value}")).collect();
+                    let _ = write!(html, "<div class=\"received\">got {}</div>", escape(&counts.join(", ")));
+                }
+                html.push_str("</td>");
+            }
+            html.push_str("</tr>");
+        }
+        html.push_str("</table><h2>Latest events</h2><table class=\"events\"><tr><th>time (ms)</th><th>node</th><th>event</th><th>details</th></tr>");
+        for entry in self.entries.iter().rev().take(SHOWN_EVENTS) {
+            let details: Vec<String> = entry
+                .event
+                .fields
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, text(value)))
+                .collect();
+            let _ = write!(
+                html,
+                "<tr><td>{}</td><td title=\"{}\">{}</td><td>{}</td><td>{}</td></tr>",
+                entry.time_ms,
+                escape(&entry.event.node),
+                escape(short(&entry.event.node)),
+                escape(&entry.event.event),
+                escape(&details.join(" "))
+            );
+        }
+        html.push_str("</table>");
+        html
+    }
+}
+
+// What the nodes did in one view, as the dashboard shows it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Round {
+    pub view: u64,
+    pub leader: Option<String>,
+    pub proposed: Option<String>,
+    // Votes each node received, by value.
+    pub votes: BTreeMap<String, BTreeMap<String, u32>>,
+    // The value each node's quorum certificate decided.
+    pub decided: BTreeMap<String, String>,
+    pub committed: Vec<String>,
+    pub timeouts: Vec<String>,
+}
+
+// What every node did in one view.
+#[derive(Default)]
+struct View<'a> {
+    leader: Option<&'a str>,
+    // When the first node entered the view, to show how far behind the others were.
+    entered_ms: Option<i64>,
+    cells: BTreeMap<&'a str, Vec<Item>>,
+    // Votes each node received in the view, by value.
+    received: BTreeMap<&'a str, BTreeMap<String, u32>>,
+}
+
+struct Item {
+    class: &'static str,
+    text: String,
+}
+
+impl<'a> View<'a> {
+    fn add(&mut self, entry: &'a Entry) {
+        let event = &entry.event;
+        let value = || event.field("value").map(text).unwrap_or_default();
+        let (class, text) = match event.event.as_str() {
+            "view_entered" => {
+                let first = *self.entered_ms.get_or_insert(entry.time_ms);
+                ("entered", format!("entered +{} ms", entry.time_ms - first))
+            }
+            "proposed" => {
+                self.leader = Some(&event.node);
+                ("proposed", format!("proposed {}", value()))
+            }
+            "vote_sent" => ("vote", format!("voted {}", value())),
+            "vote_received" => {
+                *self.received.entry(&event.node).or_default().entry(value()).or_default() += 1;
+                return;
+            }
+            "timeout" => ("timeout", "timeout".to_string()),
+            "qc_formed" => (
+                "qc",
+                format!(
+                    "QC {} ({}/{})",
+                    value(),
+                    event.field("attack").map(text).unwrap_or_default(),
+                    event.field("retreat").map(text).unwrap_or_default()
+                ),
+            ),
+            "committed" => (
+                "committed",
+                format!("committed #{}", event.field("height").map(text).unwrap_or_default()),
+            ),
+            other => ("other", other.to_string()),
+        };
+        self.cells.entry(&event.node).or_default().push(Item { class, text });
+    }
+}
+
+// Inserts `entry` in time order. Events usually arrive in order, so this is
+// nearly always a push at the back.
+fn insert(entries: &mut VecDeque<Entry>, entry: Entry) {
+    let at = match entries.back() {
+        Some(last) if last.time_ms > entry.time_ms => entries.partition_point(|held| held.time_ms <= entry.time_ms),
+        _ => entries.len(),
+    };
+    entries.insert(at, entry);
+}
+
+// The tail of a peer id, which is enough to tell the nodes of a cluster apart.
+fn short(node: &str) -> &str {
+    let start = node.char_indices().rev().nth(5).map_or(0, |(index, _)| index);
+    &node[start..]
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
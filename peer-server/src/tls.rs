@@ -0,0 +1,36 @@
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+// Names the generated certificate is valid for, unless PEER_SERVER_TLS_NAMES
+// lists others.
+const DEFAULT_NAMES: &str = "localhost,127.0.0.1,peer-server";
+
+// The certificate and key in `dir`, as cert.pem and key.pem. A self-signed
+// pair is generated there if there is none yet, which peers then trust with
+// `--server-ca <dir>/cert.pem`.
+pub fn certificate(dir: &Path) -> Result<(PathBuf, PathBuf), Box<dyn Error>> {
+    let cert = dir.join("cert.pem");
+    let key = dir.join("key.pem");
+    if cert.exists() && key.exists() {
+        return Ok((cert, key));
+    }
+
+    let names = std::env::var("PEER_SERVER_TLS_NAMES").unwrap_or_else(|_| DEFAULT_NAMES.to_string());
+    let names: Vec<String> = names.split(',').map(str::trim).filter(|name| !name.is_empty()).map(str::to_string).collect();
+    let generated = rcgen::generate_simple_self_signed(names.clone())?;
+    fs::create_dir_all(dir)?;
+    fs::write(&cert, generated.serialize_pem()?)?;
+    // Only the owner may read the key.
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&key)?
+        .write_all(generated.serialize_private_key_pem().as_bytes())?;
+    println!("Generated a self-signed certificate for {} in {}", names.join(", "), dir.display());
+    Ok((cert, key))
+}
@@ -0,0 +1,87 @@
+// Tokens grant viewer, peer or operator access, and a peer token only speaks
+// for its own peer.
+
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{header, Method};
+use peer_server::auth::{self, Access, Caller, Tokens};
+
+const A: &str = "12D3KooWKRWhW3dKa3UKy1AdZLVmcGyR8nzZMbBqbCySi9VVXz7i";
+const B: &str = "12D3KooWPdDXSbFDY9TVeczXLo8X78AExYAJufzngdfoWAjKGniC";
+
+#[test]
+fn requests_need_the_access_of_what_they_do() {
+    let cases = [
+        (Method::GET, "/", Access::Viewer),
+        (Method::GET, "/api/nodes", Access::Viewer),
+        (Method::HEAD, "/timeline", Access::Viewer),
+        (Method::POST, "/api/nodes", Access::Peer),
+        (Method::POST, "/events", Access::Peer),
+        (Method::PUT, "/api/nodes/12D3KooW/heartbeat", Access::Peer),
+        (Method::PUT, "/api/nodes/12D3KooW/role", Access::Operator),
+        (Method::POST, "/api/nodes/12D3KooW/faults", Access::Operator),
+        (Method::POST, "/api/rounds", Access::Operator),
+        (Method::DELETE, "/api/rounds/current", Access::Operator),
+        (Method::POST, "/api/experiments", Access::Operator),
+        (Method::PUT, "/api/nodes/12D3KooW/heartbeat/extra", Access::Operator),
+    ];
+    for (method, path, access) in cases {
+        assert_eq!(auth::required(&method, path), access, "{} {}", method, path);
+    }
+}
+
+#[test]
+fn peer_tokens_belong_to_one_peer() {
+    let tokens = Tokens::parse("look", &format!("{A}=a-secret, {B}=b-secret"), "root").unwrap();
+    assert_eq!(tokens.len(), 4);
+    assert_eq!(tokens.caller("look").map(|caller| caller.access), Some(Access::Viewer));
+    assert_eq!(tokens.caller("root").map(|caller| caller.access), Some(Access::Operator));
+    assert_eq!(tokens.caller("a-secre"), None);
+    assert_eq!(tokens.caller(""), None);
+
+    let a = tokens.caller("a-secret").unwrap();
+    assert_eq!(
+        a,
+        Caller {
+            access: Access::Peer,
+            peer: Some(A.to_string())
+        }
+    );
+    assert!(a.speaks_for(A).is_ok());
+    assert!(a.speaks_for(B).is_err());
+    assert!(tokens.caller("root").unwrap().speaks_for(B).is_ok());
+
+    assert!(Tokens::parse("", "unbound", "").is_err());
+    assert!(Tokens::parse("", &format!("{A}="), "").is_err());
+    assert!(Tokens::parse("", "", "").unwrap().is_empty());
+}
+
+#[test]
+fn the_cookie_only_travels_over_https_when_serving_tls() {
+    let tokens = Tokens::parse("", "", "root").unwrap();
+    assert_eq!(tokens.cookie("root"), "peer_server_token=root; Path=/; HttpOnly; SameSite=Strict");
+    assert_eq!(
+        tokens.with_secure_cookie(true).cookie("root"),
+        "peer_server_token=root; Path=/; HttpOnly; SameSite=Strict; Secure"
+    );
+}
+
+#[test]
+fn tokens_come_from_the_header_or_the_cookie() {
+    let request = |name: header::HeaderName, value: &str| {
+        Request::builder()
+            .uri("/api/nodes?token=ignored")
+            .header(name, value)
+            .body(Body::empty())
+            .unwrap()
+    };
+    assert_eq!(auth::presented(&request(header::AUTHORIZATION, "Bearer secret")), Some("secret"));
+    assert_eq!(auth::presented(&request(header::AUTHORIZATION, "Basic secret")), None);
+    assert_eq!(
+        auth::presented(&request(header::COOKIE, "theme=dark; peer_server_token=secret")),
+        Some("secret")
+    );
+    assert_eq!(auth::presented(&request(header::COOKIE, "peer_server_tokens=secret")), None);
+    // The query only ever sets the cookie.
+    assert_eq!(auth::presented(&Request::builder().uri("/?token=secret").body(Body::empty()).unwrap()), None);
+}
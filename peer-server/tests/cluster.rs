@@ -0,0 +1,205 @@
+// The cluster peer-server keeps: commands queued per node in sequence, nodes
+// going stale without heartbeats, and roles changing once a peer applies them.
+
+use peer_server::cluster::{Cluster, ClusterError, Command, Decision, Heartbeat, Liveness, Registration, STALE_AFTER_MS};
+
+const A: &str = "12D3KooWKRWhW3dKa3UKy1AdZLVmcGyR8nzZMbBqbCySi9VVXz7i";
+const B: &str = "12D3KooWPdDXSbFDY9TVeczXLo8X78AExYAJufzngdfoWAjKGniC";
+
+fn registration(peer_id: &str) -> Registration {
+    Registration {
+        peer_id: peer_id.to_string(),
+        role: "honest".to_string(),
+        version: "0.1.0".to_string(),
+        addresses: vec!["/ip4/127.0.0.1/tcp/4000".to_string()],
+    }
+}
+
+fn cluster() -> Cluster {
+    let mut cluster = Cluster::new();
+    assert_eq!(cluster.register(registration(A)), Ok(true));
+    assert_eq!(cluster.register(registration(B)), Ok(true));
+    cluster
+}
+
+fn seqs(cluster: &Cluster, peer_id: &str, after: u64) -> Vec<u64> {
+    cluster
+        .commands_after(peer_id, after)
+        .unwrap()
+        .iter()
+        .map(|queued| queued.seq)
+        .collect()
+}
+
+#[test]
+fn commands_are_numbered_per_node() {
+    let mut cluster = cluster();
+    cluster.set_leader(A).unwrap();
+    assert_eq!(cluster.start_round(Decision::Attack).unwrap(), A);
+    assert_eq!(cluster.stop_round(), 2);
+
+    let commands = cluster.commands_after(A, 0).unwrap();
+    assert_eq!(commands.iter().map(|queued| queued.seq).collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(commands[0].command, Command::StartRound { value: Decision::Attack });
+    assert_eq!(commands[1].command, Command::StopRound);
+    assert_eq!(seqs(&cluster, A, 1), vec![2]);
+    assert_eq!(seqs(&cluster, A, 2), Vec::<u64>::new());
+    assert_eq!(seqs(&cluster, B, 0), vec![1]);
+}
+
+#[test]
+fn only_the_latest_commands_are_kept() {
+    let mut cluster = cluster();
+    for _ in 0..150 {
+        cluster.stop_round();
+    }
+    let kept = seqs(&cluster, A, 0);
+    assert_eq!(kept.len(), 100);
+    assert_eq!(kept.first(), Some(&51));
+    assert_eq!(kept.last(), Some(&150));
+    assert_eq!(cluster.node(A).unwrap().last_seq, 150);
+}
+
+#[test]
+fn rounds_need_a_live_leader() {
+    let mut cluster = cluster();
+    assert!(cluster.start_round(Decision::Retreat).is_err());
+    assert!(cluster.set_leader("12D3KooWunknown").is_err());
+    cluster.set_leader(B).unwrap();
+    cluster.sweep(cluster.node(B).unwrap().last_seen_ms + 60_000);
+    assert!(cluster.start_round(Decision::Retreat).is_err());
+    assert_eq!(seqs(&cluster, B, 0), Vec::<u64>::new());
+}
+
+#[test]
+fn heartbeats_return_the_commands_not_received_yet() {
+    let mut cluster = cluster();
+    cluster.stop_round();
+    cluster.stop_round();
+    let heartbeat = |after| Heartbeat {
+        after,
+        role: None,
+        addresses: Vec::new(),
+        view: 3,
+        height: 2,
+    };
+    let commands = cluster.heartbeat(A, heartbeat(1)).unwrap();
+    assert_eq!(commands.iter().map(|queued| queued.seq).collect::<Vec<_>>(), vec![2]);
+    let node = cluster.node(A).unwrap();
+    assert_eq!((node.view, node.height, node.status), (3, 2, Liveness::Live));
+}
+
+#[test]
+fn registering_again_keeps_the_queued_commands() {
+    let mut cluster = cluster();
+    cluster.stop_round();
+    let mut again = registration(A);
+    again.version = "0.2.0".to_string();
+    assert_eq!(cluster.register(again), Ok(false));
+    assert_eq!(cluster.node(A).unwrap().version, "0.2.0");
+    assert_eq!(seqs(&cluster, A, 0), vec![1]);
+    assert_eq!(cluster.nodes().count(), 2);
+}
+
+#[test]
+fn a_restarted_peer_gets_only_commands_queued_after_it_registered() {
+    let mut cluster = cluster();
+    cluster.set_leader(A).unwrap();
+    cluster.start_round(Decision::Attack).unwrap();
+    cluster.set_role(A, "inverted".to_string()).unwrap();
+    assert_eq!(cluster.register(registration(A)), Ok(false));
+    let registered = cluster.node(A).unwrap().last_seq;
+    assert_eq!(registered, 2);
+
+    let heartbeat = |after| Heartbeat {
+        after,
+        role: None,
+        addresses: Vec::new(),
+        view: 0,
+        height: 0,
+    };
+    assert!(cluster.heartbeat(A, heartbeat(registered)).unwrap().is_empty());
+    cluster.stop_round();
+    let commands = cluster.heartbeat(A, heartbeat(registered)).unwrap();
+    assert_eq!(commands.iter().map(|queued| queued.seq).collect::<Vec<_>>(), vec![3]);
+    assert_eq!(commands[0].command, Command::StopRound);
+}
+
+#[test]
+fn nodes_without_heartbeats_go_stale() {
+    let mut cluster = cluster();
+    let seen = cluster.node(A).unwrap().last_seen_ms;
+    cluster.mark_saved();
+    cluster.sweep(seen + STALE_AFTER_MS);
+    assert_eq!(cluster.members(Liveness::Stale), Vec::<&str>::new());
+    assert!(!cluster.unsaved());
+
+    cluster.sweep(seen + STALE_AFTER_MS + 1);
+    assert_eq!(cluster.members(Liveness::Stale), vec![A, B]);
+    assert!(cluster.unsaved());
+
+    let heartbeat = Heartbeat {
+        after: 0,
+        role: None,
+        addresses: Vec::new(),
+        view: 0,
+        height: 0,
+    };
+    cluster.heartbeat(B, heartbeat).unwrap();
+    assert_eq!(cluster.members(Liveness::Live), vec![B]);
+}
+
+#[test]
+fn registrations_and_heartbeats_are_checked() {
+    let mut cluster = cluster();
+    assert_eq!(
+        cluster.register(registration("<img src=x onerror=alert(1)>")),
+        Err(ClusterError::InvalidPeerId("<img src=x onerror=alert(1)>".to_string()))
+    );
+    let mut role = registration("12D3KooWEMELWfNrajsPyDHAQrV2sNxXsD3Zkyq7daMx3w1NvbjR");
+    role.role = "honest<script>".to_string();
+    assert_eq!(cluster.register(role), Err(ClusterError::InvalidRole("honest<script>".to_string())));
+
+    let heartbeat = Heartbeat {
+        after: 0,
+        role: Some("\"onclick=".to_string()),
+        addresses: Vec::new(),
+        view: 0,
+        height: 0,
+    };
+    assert!(matches!(cluster.heartbeat(A, heartbeat), Err(ClusterError::InvalidRole(_))));
+    assert_eq!(cluster.node(A).unwrap().role, "honest");
+    assert!(matches!(
+        cluster.heartbeat(
+            "12D3KooWEMELWfNrajsPyDHAQrV2sNxXsD3Zkyq7daMx3w1NvbjR",
+            Heartbeat { after: 0, role: None, addresses: Vec::new(), view: 0, height: 0 }
+        ),
+        Err(ClusterError::UnknownNode(_))
+    ));
+}
+
+#[test]
+fn roles_change_once_the_peer_reports_them() {
+    let mut cluster = cluster();
+    assert_eq!(cluster.set_role(A, "delayed:8000".to_string()), Ok(1));
+    assert_eq!(
+        cluster.commands_after(A, 0).unwrap()[0].command,
+        Command::SetRole {
+            role: "delayed:8000".to_string()
+        }
+    );
+    assert_eq!(cluster.node(A).unwrap().role, "honest");
+    assert_eq!(cluster.set_role(A, "no such role!".to_string()), Err(ClusterError::InvalidRole("no such role!".to_string())));
+
+    cluster.mark_saved();
+    let heartbeat = Heartbeat {
+        after: 1,
+        role: Some("delayed:8000".to_string()),
+        addresses: Vec::new(),
+        view: 0,
+        height: 0,
+    };
+    assert!(cluster.heartbeat(A, heartbeat).unwrap().is_empty());
+    assert_eq!(cluster.node(A).unwrap().role, "delayed:8000");
+    assert!(cluster.unsaved());
+}
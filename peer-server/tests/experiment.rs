@@ -0,0 +1,93 @@
+// Experiments run one at a time, and each round is judged by the block its
+// leader proposed after the round started.
+
+use peer_server::experiment::{self, ExperimentError, Experiments, Spec, Status};
+use peer_server::timeline::{Event, Timeline};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+fn spec() -> Spec {
+    serde_json::from_value(json!({"rounds": 2, "round_ms": 1000})).unwrap()
+}
+
+fn nodes() -> Vec<String> {
+    ["a", "b", "c", "d"].iter().map(|node| node.to_string()).collect()
+}
+
+fn add(timeline: &mut Timeline, value: serde_json::Value) {
+    timeline.add(serde_json::from_value::<Event>(value).unwrap());
+}
+
+fn proposed(timeline: &mut Timeline, at_ms: u64, height: u64, value: &str) {
+    add(timeline, json!({"at_ms": at_ms, "node": "a", "event": "proposed", "view": height, "height": height, "value": value}));
+}
+
+fn committed(timeline: &mut Timeline, at_ms: u64, node: &str, height: u64, value: &str) {
+    add(
+        timeline,
+        json!({"at_ms": at_ms, "node": node, "event": "committed", "view": height, "height": height, "hash": "00", "value": value, "synced": false}),
+    );
+}
+
+#[test]
+fn only_one_experiment_runs_at_a_time() {
+    let mut experiments = Experiments::new();
+    assert_eq!(experiments.start(spec(), nodes()), Ok(1));
+    assert_eq!(experiments.start(spec(), nodes()), Err(ExperimentError::Running(1)));
+    experiments.finish(1, None);
+    assert_eq!(experiments.get(1).unwrap().status, Status::Finished);
+    assert_eq!(experiments.start(spec(), nodes()), Ok(2));
+    assert_eq!(experiments.get(3).err(), Some(ExperimentError::Unknown(3)));
+}
+
+#[test]
+fn experiments_running_at_a_restart_fail() {
+    let mut experiments = Experiments::new();
+    experiments.start(spec(), nodes()).unwrap();
+    experiments.finish(1, Some("no node is live".to_string()));
+    experiments.start(spec(), nodes()).unwrap();
+    experiments.abandon_running();
+
+    let failed = experiments.get(2).unwrap();
+    assert_eq!(failed.status, Status::Failed);
+    assert_eq!(failed.error.as_deref(), Some("peer-server stopped while it ran"));
+    assert_eq!(experiments.get(1).unwrap().error.as_deref(), Some("no node is live"));
+    // A new experiment may start right away.
+    assert_eq!(experiments.start(spec(), nodes()), Ok(3));
+}
+
+#[test]
+fn rounds_are_judged_by_the_proposal_made_after_they_started() {
+    let mut timeline = Timeline::new();
+    // An earlier round's block, whose events arrive late.
+    proposed(&mut timeline, 1000, 1, "RETREAT");
+    proposed(&mut timeline, 5000, 2, "ATTACK");
+    committed(&mut timeline, 1100, "a", 1, "RETREAT");
+    for node in ["a", "b", "c"] {
+        committed(&mut timeline, 5100, node, 2, "ATTACK");
+    }
+    committed(&mut timeline, 5200, "d", 2, "RETREAT");
+
+    let nodes = nodes();
+    let honest: BTreeMap<&str, &str> = [("a", "honest"), ("b", "honest"), ("c", "honest"), ("d", "inverted")].into();
+    let result = experiment::result(&timeline, 2, "a", 4000, &nodes, &honest);
+    assert_eq!(result.round, 2);
+    assert_eq!(result.height, Some(2));
+    assert_eq!(result.proposed.as_deref(), Some("ATTACK"));
+    assert_eq!(result.decisions["d"].as_deref(), Some("RETREAT"));
+    assert!(result.agreement);
+    assert!(result.validity);
+
+    // With d honest the honest nodes disagree, and not all of them kept to the proposal.
+    let all_honest: BTreeMap<&str, &str> = nodes.iter().map(|node| (node.as_str(), "honest")).collect();
+    let result = experiment::result(&timeline, 2, "a", 4000, &nodes, &all_honest);
+    assert!(!result.agreement);
+    assert!(!result.validity);
+
+    // No proposal since the round started: nothing was decided.
+    let result = experiment::result(&timeline, 3, "a", 6000, &nodes, &honest);
+    assert_eq!((result.height, result.proposed.as_deref()), (None, None));
+    assert!(result.decisions.values().all(Option::is_none));
+    assert!(!result.agreement);
+    assert!(!result.validity);
+}
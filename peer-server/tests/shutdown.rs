@@ -0,0 +1,48 @@
+// On SIGTERM peer-server flags that it stops and waits for the live peers to
+// hear it with their next heartbeat. The test signals its own process, so it
+// keeps to a file of its own.
+
+use peer_server::cluster::{Heartbeat, Registration};
+use peer_server::{shutdown, CLUSTER};
+use std::process::Command;
+use std::time::{Duration, Instant};
+use tokio::signal::unix::{signal, SignalKind};
+
+const PEER: &str = "12D3KooWKRWhW3dKa3UKy1AdZLVmcGyR8nzZMbBqbCySi9VVXz7i";
+
+#[tokio::test]
+async fn sigterm_waits_for_the_peers_to_hear_it() {
+    // Handles SIGTERM from here on, so the signal does not end the test.
+    let _terminate = signal(SignalKind::terminate()).unwrap();
+    let registration = Registration {
+        peer_id: PEER.to_string(),
+        role: "honest".to_string(),
+        version: "0.1.0".to_string(),
+        addresses: Vec::new(),
+    };
+    CLUSTER.lock().unwrap().register(registration).unwrap();
+
+    let signalled = tokio::spawn(shutdown::signal());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!shutdown::stopping());
+    let status = Command::new("kill").args(["-TERM", &std::process::id().to_string()]).status().unwrap();
+    assert!(status.success());
+    tokio::time::timeout(Duration::from_secs(1), shutdown::stopped()).await.unwrap();
+    assert!(shutdown::stopping());
+
+    // Still waiting for the peer.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!signalled.is_finished());
+
+    let started = Instant::now();
+    let heartbeat = Heartbeat {
+        after: 0,
+        role: None,
+        addresses: Vec::new(),
+        view: 0,
+        height: 0,
+    };
+    CLUSTER.lock().unwrap().heartbeat(PEER, heartbeat).unwrap();
+    tokio::time::timeout(Duration::from_secs(1), signalled).await.unwrap().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
@@ -0,0 +1,70 @@
+// State saved by a store reads back in full, even next to the remains of a
+// save that was cut short, and an older snapshot never overwrites a newer one.
+
+use peer_server::store::Store;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+// A fresh directory per test, so the tests can run in parallel.
+fn directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("peer-server-store-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn state(height: u64) -> BTreeMap<String, u64> {
+    [("height".to_string(), height)].into()
+}
+
+#[test]
+fn saved_state_reloads() {
+    let directory = directory("reload");
+    let store = Store::new(directory.join("state.json"));
+    assert_eq!(store.load::<BTreeMap<String, u64>>().unwrap(), None);
+
+    store.save(&state(1)).unwrap();
+    store.save(&state(2)).unwrap();
+    let reopened = Store::new(directory.join("state.json"));
+    assert_eq!(reopened.load().unwrap(), Some(state(2)));
+    assert!(!directory.join("state.json.tmp").exists());
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn a_torn_temporary_file_is_ignored_and_replaced() {
+    let directory = directory("torn");
+    let store = Store::new(directory.join("state.json"));
+    store.save(&state(1)).unwrap();
+
+    // A save that crashed halfway through writing.
+    fs::write(directory.join("state.json.tmp"), b"{\"height\": ").unwrap();
+    assert_eq!(store.load().unwrap(), Some(state(1)));
+
+    store.save(&state(2)).unwrap();
+    assert_eq!(store.load().unwrap(), Some(state(2)));
+    assert!(!directory.join("state.json.tmp").exists());
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn older_snapshots_are_not_written() {
+    let directory = directory("order");
+    let store = Store::new(directory.join("state.json"));
+    let older = store.snapshot(&state(1)).unwrap();
+    let newer = store.snapshot(&state(2)).unwrap();
+    store.write(newer).unwrap();
+    store.write(older).unwrap();
+    assert_eq!(store.load().unwrap(), Some(state(2)));
+    fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn a_corrupt_state_file_fails_to_load() {
+    let directory = directory("corrupt");
+    fs::write(directory.join("state.json"), b"not json").unwrap();
+    let store = Store::new(directory.join("state.json"));
+    assert!(store.load::<BTreeMap<String, u64>>().is_err());
+    fs::remove_dir_all(directory).unwrap();
+}
@@ -0,0 +1,80 @@
+// The timeline merges the events of every node in NTP-corrected order and sums
+// up what happened in each view for the dashboard.
+
+use peer_server::timeline::{Event, Timeline};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+fn event(value: Value) -> Event {
+    serde_json::from_value(value).unwrap()
+}
+
+fn order(timeline: &Timeline) -> Vec<(i64, String)> {
+    timeline
+        .entries()
+        .iter()
+        .map(|entry| (entry.time_ms, format!("{} {}", entry.event.node, entry.event.event)))
+        .collect()
+}
+
+#[test]
+fn events_merge_in_corrected_time_order() {
+    let mut timeline = Timeline::new();
+    // a's clock is 2 s behind, so its events happened later than they say.
+    timeline.add(event(json!({"at_ms": 1000, "node": "a", "event": "clock_sync", "offset_us": 2_000_000, "delay_us": 100})));
+    timeline.add(event(json!({"at_ms": 2000, "node": "b", "event": "view_entered", "view": 1})));
+    timeline.add(event(json!({"at_ms": 2500, "node": "b", "event": "view_entered", "view": 2})));
+    timeline.add(event(json!({"at_ms": 1500, "node": "a", "event": "view_entered", "view": 1})));
+    // Late and out of order.
+    timeline.add(event(json!({"at_ms": 2200, "node": "b", "event": "vote_sent", "view": 1, "height": 1, "value": "ATTACK"})));
+
+    assert_eq!(
+        order(&timeline),
+        vec![
+            (2000, "b view_entered".to_string()),
+            (2200, "b vote_sent".to_string()),
+            (2500, "b view_entered".to_string()),
+            (3000, "a clock_sync".to_string()),
+            (3500, "a view_entered".to_string()),
+        ]
+    );
+    assert_eq!(timeline.offset_us("a"), Some(2_000_000));
+    assert_eq!(timeline.offset_us("b"), None);
+}
+
+#[test]
+fn rounds_sum_up_each_view() {
+    let mut timeline = Timeline::new();
+    let events = [
+        json!({"at_ms": 100, "node": "a", "event": "view_entered", "view": 1}),
+        json!({"at_ms": 110, "node": "a", "event": "proposed", "view": 1, "height": 1, "value": "ATTACK"}),
+        json!({"at_ms": 120, "node": "b", "event": "vote_received", "view": 1, "height": 1, "value": "ATTACK", "from": "a"}),
+        json!({"at_ms": 121, "node": "b", "event": "vote_received", "view": 1, "height": 1, "value": "ATTACK", "from": "c"}),
+        json!({"at_ms": 122, "node": "b", "event": "vote_received", "view": 1, "height": 1, "value": "RETREAT", "from": "d"}),
+        json!({"at_ms": 130, "node": "b", "event": "qc_formed", "view": 1, "height": 1, "value": "ATTACK", "attack": 2, "retreat": 1}),
+        json!({"at_ms": 131, "node": "b", "event": "committed", "view": 1, "height": 1, "hash": "00", "value": "ATTACK", "synced": false}),
+        json!({"at_ms": 140, "node": "c", "event": "timeout", "view": 1}),
+        json!({"at_ms": 200, "node": "a", "event": "timeout", "view": 2}),
+    ];
+    for value in events {
+        timeline.add(event(value));
+    }
+
+    let rounds = timeline.rounds(10);
+    assert_eq!(rounds.iter().map(|round| round.view).collect::<Vec<_>>(), vec![2, 1]);
+    assert_eq!(rounds[0].timeouts, vec!["a".to_string()]);
+    assert_eq!(rounds[0].leader, None);
+
+    let first = &rounds[1];
+    assert_eq!(first.leader.as_deref(), Some("a"));
+    assert_eq!(first.proposed.as_deref(), Some("ATTACK"));
+    let received: BTreeMap<String, u32> = [("ATTACK".to_string(), 2), ("RETREAT".to_string(), 1)].into();
+    assert_eq!(first.votes, [("b".to_string(), received)].into());
+    assert_eq!(first.decided, [("b".to_string(), "ATTACK".to_string())].into());
+    assert_eq!(first.committed, vec!["b".to_string()]);
+    assert_eq!(first.timeouts, vec!["c".to_string()]);
+
+    // Only the newest views.
+    assert_eq!(timeline.rounds(1).len(), 1);
+    assert_eq!(timeline.rounds(1)[0].view, 2);
+}
@@ -0,0 +1,92 @@
+#![no_main]
+
+// Drives a node's consensus core with an arbitrary sequence of proposals, votes,
+// view announcements, own proposals, round timeouts and clock ticks decoded from
+// the input. The core must not panic, its view must never go back and its chain
+// must grow one block at a time.
+
+use libfuzzer_sys::fuzz_target;
+use libp2p::identity::Keypair;
+use peer::byzantine::Honest;
+use peer::consensus::{Action, Core};
+use peer::protocol::{BlockHash, Decision, Message, Proposal, Signed, ViewMessage, Vote};
+use std::time::Duration;
+use peer::validation;
+use peer::validators::Validators;
+use peer::viewsync::{ViewSync, VIEW_LENGTH_MS};
+
+const SIGNERS: u8 = 4;
+
+fn keypair(index: u8) -> Keypair {
+    Keypair::ed25519_from_bytes([index + 1; 32]).unwrap()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let validators = Validators::new((0..SIGNERS).map(|index| keypair(index).public().to_peer_id()), 1).unwrap();
+    let mut core = Core::new(keypair(0), Box::new(Honest), ViewSync::new(VIEW_LENGTH_MS, 2), validators);
+    let mut view = 0;
+    let mut height = 0;
+    let mut parents: Vec<BlockHash> = vec![core.chain().tip_hash()];
+    let mut now = 0;
+
+    for op in data.chunks_exact(4).take(64) {
+        let signer = keypair(op[1] % SIGNERS);
+        let value = if op[2] & 1 == 1 { Decision::Attack } else { Decision::Retreat };
+        // Views and heights close to the node's own, so rounds actually complete.
+        let op_view = core.view().saturating_add(op[2] as u64 >> 5).saturating_sub(1);
+        let op_height = core.chain().height() + (op[3] as u64 % 3);
+        let parent = parents[op[3] as usize % parents.len()];
+
+        let actions = match op[0] % 6 {
+            0 if !core.in_round() => core.propose(value),
+            0 => continue,
+            1..=3 => {
+                let message = if op[0] % 6 == 1 {
+                    let proposal = Proposal {
+                        view: op_view,
+                        height: op_height,
+                        parent,
+                        value,
+                    };
+                    Message::Proposal(Signed::new(proposal, &signer))
+                } else if op[0] % 6 == 3 {
+                    Message::View(Signed::new(ViewMessage { view: op_view + 1 }, &signer))
+                } else {
+                    let vote = Vote {
+                        view: op_view,
+                        height: op_height,
+                        parent,
+                        value,
+                    };
+                    Message::Vote(Signed::new(vote, &signer))
+                };
+                match validation::validate(&message.encode(), core.view()) {
+                    Ok(message) => core.on_message(signer.public().to_peer_id(), message),
+                    Err(_) => continue,
+                }
+            }
+            4 => core.close_round(op_view),
+            _ => {
+                now += op[3] as u64 * 100;
+                core.tick(Duration::from_millis(now))
+            }
+        };
+
+        for action in actions {
+            match action {
+                Action::ViewEntered { view: entered } => {
+                    assert!(entered > view);
+                    view = entered;
+                }
+                Action::Applied { block, .. } => {
+                    assert_eq!(block.height, height + 1);
+                    height = block.height;
+                    parents.push(block.hash());
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(core.view(), view);
+        assert_eq!(core.chain().height(), height);
+    }
+});
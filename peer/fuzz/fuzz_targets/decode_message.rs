@@ -0,0 +1,17 @@
+#![no_main]
+
+// Gossip payloads straight off the wire. Validation must never panic, and
+// whatever it accepts must verify and survive a round trip.
+
+use libfuzzer_sys::fuzz_target;
+use peer::protocol::Message;
+use peer::validation;
+
+fuzz_target!(|data: &[u8]| {
+    for view in [0, 1, 1 << 32, u64::MAX] {
+        if let Ok(message) = validation::validate(data, view) {
+            assert!(message.verify());
+            assert_eq!(Message::decode(&message.encode()).unwrap(), message);
+        }
+    }
+});
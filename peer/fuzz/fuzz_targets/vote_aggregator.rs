@@ -0,0 +1,96 @@
+#![no_main]
+
+// Every two input bytes make one vote for the slot a leader proposed: the first
+// picks the signer, the second whether it votes ATTACK, is cast for another
+// slot or carries a broken signature. The votes are counted both by building a
+// certificate directly and by running them through a node's round.
+
+use libfuzzer_sys::fuzz_target;
+use libp2p::identity::Keypair;
+use peer::byzantine::Honest;
+use peer::chain::{Block, QuorumCertificate, GENESIS_HASH};
+use peer::consensus::{Action, Core};
+use peer::protocol::{Decision, Message, Proposal, Signed, Vote};
+use peer::validation;
+use peer::validators::Validators;
+use peer::viewsync::{ViewSync, VIEW_LENGTH_MS};
+use std::collections::HashSet;
+
+const SIGNERS: u8 = 8;
+
+fn keypair(index: u8) -> Keypair {
+    Keypair::ed25519_from_bytes([index + 1; 32]).unwrap()
+}
+
+fuzz_target!(|data: &[u8]| {
+    // The signers and the follower below, tolerating two faults.
+    let validators = Validators::new((0..=SIGNERS).map(|index| keypair(index).public().to_peer_id()), 2).unwrap();
+    let leader = keypair(0);
+    let proposal = Proposal {
+        view: 1,
+        height: 1,
+        parent: GENESIS_HASH,
+        value: Decision::Attack,
+    };
+
+    let mut votes = Vec::new();
+    for chunk in data.chunks_exact(2).take(64) {
+        let (signer, flags) = (chunk[0] % SIGNERS, chunk[1]);
+        let value = if flags & 1 == 1 { Decision::Attack } else { Decision::Retreat };
+        let height = if flags & 2 == 2 { 2 } else { 1 };
+        let mut vote = Signed::new(
+            Vote {
+                view: 1,
+                height,
+                parent: GENESIS_HASH,
+                value,
+            },
+            &keypair(signer),
+        );
+        if flags & 4 == 4 {
+            vote.signature[0] ^= 1;
+        }
+        votes.push(vote);
+    }
+
+    let qc = QuorumCertificate { votes: votes.clone() };
+    let (attack, retreat) = qc.tally();
+    assert_eq!((attack + retreat) as usize, votes.len());
+    let block = Block {
+        view: 1,
+        height: 1,
+        parent: GENESIS_HASH,
+        value: qc.decision(),
+    };
+    let mut signers = HashSet::new();
+    let well_formed = !votes.is_empty()
+        && votes.iter().all(|vote| {
+            vote.verify() && vote.body.height == 1 && signers.insert(vote.public_key.clone())
+        })
+        && votes.iter().filter(|vote| vote.body.value == qc.decision()).count() >= validators.quorum();
+    assert_eq!(qc.verify(&block, &validators).is_ok(), well_formed);
+
+    // The same votes through a follower's round, as the network would deliver
+    // them. The follower must count each valid signer at most once.
+    let follower = keypair(SIGNERS);
+    let mut core = Core::new(follower, Box::new(Honest), ViewSync::new(VIEW_LENGTH_MS, 3), validators);
+    let from = leader.public().to_peer_id();
+    let message = Message::Proposal(Signed::new(proposal, &leader));
+    core.on_message(from, message);
+    for vote in votes.iter().cloned() {
+        if let Ok(message) = validation::validate(&Message::Vote(vote).encode(), core.view()) {
+            core.on_message(from, message);
+        }
+    }
+    let valid_signers: HashSet<_> = votes
+        .iter()
+        .filter(|vote| vote.verify() && vote.body.height == 1)
+        .map(|vote| vote.public_key.clone())
+        .collect();
+    for action in core.close_round(1) {
+        if let Action::Decided { attack, retreat, .. } = action {
+            // +1 for the follower's own vote.
+            assert!((attack + retreat) as usize <= valid_signers.len() + 1);
+        }
+    }
+});
@@ -0,0 +1,159 @@
+use clap::{Args, Parser, Subcommand};
+use peer::byzantine;
+use peer::compose::{self, ClusterSpec};
+use peer::launcher::{self, Spec};
+use peer::validators;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+#[derive(Parser, Debug)]
+#[command(name = "feverbft", about = "Run FeverBFT clusters locally or generate them for docker-compose")]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Run a cluster of peers on this machine until Ctrl-C; lines typed go to node 0, or to node i as "i: START ATTACK"
+    Cluster(ClusterArgs),
+    /// Generate docker-compose.yaml, cluster.json and the nodes' keys from a cluster spec
+    Compose(ComposeArgs),
+}
+
+#[derive(Args, Debug)]
+struct ClusterArgs {
+    /// Number of nodes
+    #[arg(long, short = 'n', default_value_t = 4)]
+    nodes: usize,
+
+    /// Directory for the nodes' keys, logs and event files, kept between runs
+    #[arg(long, default_value = "fever-cluster")]
+    dir: PathBuf,
+
+    /// TCP port of node 0 on 127.0.0.1; node i listens on this port plus i
+    #[arg(long, default_value_t = 4000)]
+    base_port: u16,
+
+    /// Metrics port of node 0; node i serves metrics on this port plus i
+    #[arg(long)]
+    metrics_base_port: Option<u16>,
+
+    /// Strategy of one node as <index>=<strategy>, e.g. 3=inverted; may be repeated
+    #[arg(long, value_parser = parse_role)]
+    byzantine: Vec<(usize, String)>,
+
+    /// Byzantine nodes the cluster tolerates, (nodes - 1) / 3 by default
+    #[arg(long)]
+    faults: Option<usize>,
+
+    /// peer-server for the nodes to register with, e.g. http://localhost:3000
+    #[arg(long)]
+    server: Option<String>,
+
+    /// Stop after this many seconds instead of waiting for Ctrl-C, e.g. in CI
+    #[arg(long)]
+    duration: Option<u64>,
+
+    /// peer executable, the one next to feverbft by default
+    #[arg(long)]
+    peer: Option<PathBuf>,
+
+    /// Further options for every peer, after --
+    #[arg(last = true)]
+    peer_args: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+struct ComposeArgs {
+    /// Cluster spec in YAML, e.g. cluster.yaml
+    spec: PathBuf,
+
+    /// Directory to write to, kept between runs so the nodes keep their keys
+    #[arg(long, default_value = "fever-compose")]
+    dir: PathBuf,
+}
+
+fn parse_role(value: &str) -> Result<(usize, String), String> {
+    let (index, role) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected <index>=<strategy>, got '{value}'"))?;
+    let index = index.parse().map_err(|_| format!("invalid node index '{index}'"))?;
+    byzantine::from_name(role, 0).map_err(|e| e.to_string())?;
+    Ok((index, role.to_string()))
+}
+
+#[tokio::main]
+async fn main() {
+    let result = match Cli::parse().command {
+        Commands::Cluster(args) => cluster(args).await,
+        Commands::Compose(args) => generate(args),
+    };
+    // Exit right away: the runtime would otherwise wait for a pending read of stdin.
+    if let Err(e) = result {
+        eprintln!("{e}");
+        process::exit(1);
+    }
+    process::exit(0);
+}
+
+async fn cluster(args: ClusterArgs) -> Result<(), Box<dyn Error>> {
+    let peer = match args.peer {
+        Some(peer) => peer,
+        None => std::env::current_exe()?.with_file_name(format!("peer{}", std::env::consts::EXE_SUFFIX)),
+    };
+    let spec = Spec {
+        nodes: args.nodes,
+        dir: args.dir,
+        base_port: args.base_port,
+        metrics_base_port: args.metrics_base_port,
+        roles: args.byzantine.into_iter().collect(),
+        faults: args.faults.unwrap_or(validators::max_faults(args.nodes)),
+        server: args.server,
+        peer,
+        peer_args: args.peer_args,
+    };
+    let nodes = launcher::prepare(&spec)?;
+    println!("Wrote {}", launcher::manifest_path(&spec).display());
+    launcher::run(&spec, &nodes, stop(args.duration)).await
+}
+
+fn generate(args: ComposeArgs) -> Result<(), Box<dyn Error>> {
+    let spec = ClusterSpec::load(&args.spec)?;
+    let nodes = compose::generate(&spec, &args.dir)?;
+    for node in &nodes {
+        println!("{} ({}) is {}", node.service, node.role, node.peer_id);
+    }
+    println!(
+        "Wrote {} nodes tolerating {} faults to {}; run `docker compose up` there",
+        nodes.len(),
+        spec.faults(),
+        args.dir.display()
+    );
+    Ok(())
+}
+
+// Resolves on Ctrl-C or SIGTERM, or once `duration` seconds passed.
+async fn stop(duration: Option<u64>) {
+    let elapsed = async {
+        match duration {
+            Some(seconds) => tokio::time::sleep(Duration::from_secs(seconds)).await,
+            None => std::future::pending().await,
+        }
+    };
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate => {}
+        _ = elapsed => {}
+    }
+}
@@ -0,0 +1,334 @@
+use crate::protocol::{Decision, Message, Proposal, Signed, Vote};
+use libp2p::identity::Keypair;
+use libp2p::PeerId;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// How long the `delayed` strategy holds its vote back by default: just past the
+// 5 second window in which peers count votes.
+const DEFAULT_DELAY_MS: u64 = 6000;
+
+// Messages the `replay` strategy keeps to replay later.
+const MAX_REPLAYED: usize = 64;
+
+// Who a message goes to: everyone over gossip, or only one half of the other
+// peers sorted by peer id, sent to each of them directly so that nobody
+// forwards it to the other half.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recipients {
+    All,
+    Lower,
+    Upper,
+}
+
+impl Recipients {
+    // The peers among `peers` that get a message sent to these recipients.
+    pub fn select(self, mut peers: Vec<PeerId>) -> Vec<PeerId> {
+        peers.sort();
+        peers.dedup();
+        let half = peers.len().div_ceil(2);
+        match self {
+            Recipients::All => peers,
+            Recipients::Lower => peers[..half].to_vec(),
+            Recipients::Upper => peers[half..].to_vec(),
+        }
+    }
+}
+
+// A message the node wants sent to `to`, after `delay`.
+pub struct Outgoing {
+    pub message: Message,
+    pub delay: Duration,
+    pub to: Recipients,
+}
+
+impl Outgoing {
+    fn now(message: Message) -> Self {
+        Outgoing::to(Recipients::All, message)
+    }
+
+    fn to(to: Recipients, message: Message) -> Self {
+        Outgoing {
+            message,
+            delay: Duration::ZERO,
+            to,
+        }
+    }
+}
+
+// Decides how a node answers the consensus traffic it sees. The honest protocol
+// is just one strategy; the others inject the faults we test the protocol
+// against.
+pub trait ByzantineStrategy: Send {
+    fn name(&self) -> &'static str;
+
+    // Messages to publish for a proposal this node leads with.
+    fn on_propose(&mut self, proposal: &Proposal, keypair: &Keypair) -> Vec<Outgoing> {
+        vec![Outgoing::now(Message::Proposal(Signed::new(proposal.clone(), keypair)))]
+    }
+
+    // Messages to publish in answer to a leader's proposal.
+    fn on_proposal(&mut self, proposal: &Proposal, keypair: &Keypair) -> Vec<Outgoing>;
+
+    // Called for every consensus message received from the network.
+    fn observe(&mut self, _message: &Message) {}
+
+    // Whether the node answers block sync requests from other peers.
+    fn serves_sync(&self) -> bool {
+        true
+    }
+}
+
+fn vote(proposal: &Proposal, value: Decision, keypair: &Keypair) -> Message {
+    Message::Vote(Signed::new(
+        Vote {
+            view: proposal.view,
+            height: proposal.height,
+            parent: proposal.parent,
+            value,
+        },
+        keypair,
+    ))
+}
+
+// Votes for whatever the leader proposed.
+pub struct Honest;
+
+impl ByzantineStrategy for Honest {
+    fn name(&self) -> &'static str {
+        "honest"
+    }
+
+    fn on_proposal(&mut self, proposal: &Proposal, keypair: &Keypair) -> Vec<Outgoing> {
+        vec![Outgoing::now(vote(proposal, proposal.value, keypair))]
+    }
+}
+
+// Tells one half of its peers the opposite of what it tells the other: it signs
+// both ATTACK and RETREAT for the same proposal, and as leader proposes both,
+// sending each half only one of them.
+pub struct Equivocation;
+
+impl ByzantineStrategy for Equivocation {
+    fn name(&self) -> &'static str {
+        "equivocation"
+    }
+
+    fn on_propose(&mut self, proposal: &Proposal, keypair: &Keypair) -> Vec<Outgoing> {
+        let opposite = Proposal {
+            value: proposal.value.opposite(),
+            ..proposal.clone()
+        };
+        vec![
+            Outgoing::to(Recipients::Lower, Message::Proposal(Signed::new(proposal.clone(), keypair))),
+            Outgoing::to(Recipients::Upper, Message::Proposal(Signed::new(opposite, keypair))),
+        ]
+    }
+
+    fn on_proposal(&mut self, proposal: &Proposal, keypair: &Keypair) -> Vec<Outgoing> {
+        vec![
+            Outgoing::to(Recipients::Lower, vote(proposal, proposal.value, keypair)),
+            Outgoing::to(Recipients::Upper, vote(proposal, proposal.value.opposite(), keypair)),
+        ]
+    }
+}
+
+// Always votes the opposite of the leader's instruction (the old `peerb`).
+pub struct Inverted;
+
+impl ByzantineStrategy for Inverted {
+    fn name(&self) -> &'static str {
+        "inverted"
+    }
+
+    fn on_proposal(&mut self, proposal: &Proposal, keypair: &Keypair) -> Vec<Outgoing> {
+        vec![Outgoing::now(vote(proposal, proposal.value.opposite(), keypair))]
+    }
+}
+
+// Votes ATTACK or RETREAT at random, irrespective of the leader. Seeded, so
+// simulated runs replay exactly.
+pub struct RandomVote {
+    rng: StdRng,
+}
+
+impl RandomVote {
+    pub fn new(seed: u64) -> Self {
+        RandomVote {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl ByzantineStrategy for RandomVote {
+    fn name(&self) -> &'static str {
+        "random"
+    }
+
+    fn on_proposal(&mut self, proposal: &Proposal, keypair: &Keypair) -> Vec<Outgoing> {
+        let value = if self.rng.gen::<bool>() {
+            Decision::Attack
+        } else {
+            Decision::Retreat
+        };
+        vec![Outgoing::now(vote(proposal, value, keypair))]
+    }
+}
+
+// Sends nothing: no votes and no sync responses.
+pub struct Silence;
+
+impl ByzantineStrategy for Silence {
+    fn name(&self) -> &'static str {
+        "silence"
+    }
+
+    fn on_proposal(&mut self, _proposal: &Proposal, _keypair: &Keypair) -> Vec<Outgoing> {
+        Vec::new()
+    }
+
+    fn serves_sync(&self) -> bool {
+        false
+    }
+}
+
+// Votes honestly, but only after `delay`.
+pub struct Delayed {
+    pub delay: Duration,
+}
+
+impl ByzantineStrategy for Delayed {
+    fn name(&self) -> &'static str {
+        "delayed"
+    }
+
+    fn on_proposal(&mut self, proposal: &Proposal, keypair: &Keypair) -> Vec<Outgoing> {
+        vec![Outgoing {
+            message: vote(proposal, proposal.value, keypair),
+            delay: self.delay,
+            to: Recipients::All,
+        }]
+    }
+}
+
+// Never votes, and as leader withholds its proposal from half of its peers, who
+// then never see the round the others vote on. Still serves sync.
+pub struct Withholding;
+
+impl ByzantineStrategy for Withholding {
+    fn name(&self) -> &'static str {
+        "withholding"
+    }
+
+    fn on_propose(&mut self, proposal: &Proposal, keypair: &Keypair) -> Vec<Outgoing> {
+        vec![Outgoing::to(
+            Recipients::Lower,
+            Message::Proposal(Signed::new(proposal.clone(), keypair)),
+        )]
+    }
+
+    fn on_proposal(&mut self, _proposal: &Proposal, _keypair: &Keypair) -> Vec<Outgoing> {
+        Vec::new()
+    }
+}
+
+// Votes honestly and re-publishes, once, the consensus messages it saw in
+// earlier views, at most the latest `MAX_REPLAYED`.
+#[derive(Default)]
+pub struct Replay {
+    seen: VecDeque<Message>,
+}
+
+impl ByzantineStrategy for Replay {
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+
+    fn on_proposal(&mut self, proposal: &Proposal, keypair: &Keypair) -> Vec<Outgoing> {
+        let (earlier, current): (VecDeque<_>, VecDeque<_>) = std::mem::take(&mut self.seen)
+            .into_iter()
+            .partition(|message| message.view() < proposal.view);
+        self.seen = current;
+        let mut outgoing: Vec<Outgoing> = earlier.into_iter().map(Outgoing::now).collect();
+        outgoing.push(Outgoing::now(vote(proposal, proposal.value, keypair)));
+        outgoing
+    }
+
+    fn observe(&mut self, message: &Message) {
+        if self.seen.len() == MAX_REPLAYED {
+            self.seen.pop_front();
+        }
+        self.seen.push_back(message.clone());
+    }
+}
+
+// Votes honestly but corrupts the vote signature.
+pub struct InvalidSignature;
+
+impl ByzantineStrategy for InvalidSignature {
+    fn name(&self) -> &'static str {
+        "invalid-signature"
+    }
+
+    fn on_proposal(&mut self, proposal: &Proposal, keypair: &Keypair) -> Vec<Outgoing> {
+        let mut message = vote(proposal, proposal.value, keypair);
+        if let Message::Vote(vote) = &mut message {
+            for byte in vote.signature.iter_mut() {
+                *byte ^= 0xff;
+            }
+        }
+        vec![Outgoing::now(message)]
+    }
+}
+
+pub const STRATEGIES: &[&str] = &[
+    "honest",
+    "equivocation",
+    "inverted",
+    "random",
+    "silence",
+    "delayed[:ms]",
+    "withholding",
+    "replay",
+    "invalid-signature",
+];
+
+// Builds a strategy from its name, e.g. "inverted" or "delayed:8000". `seed`
+// drives any randomness the strategy uses.
+pub fn from_name(spec: &str, seed: u64) -> Result<Box<dyn ByzantineStrategy>, String> {
+    let (name, argument) = match spec.split_once(':') {
+        Some((name, argument)) => (name, Some(argument)),
+        None => (spec, None),
+    };
+    let strategy: Box<dyn ByzantineStrategy> = match (name, argument) {
+        ("honest", None) => Box::new(Honest),
+        ("equivocation", None) => Box::new(Equivocation),
+        ("inverted", None) => Box::new(Inverted),
+        ("random", None) => Box::new(RandomVote::new(seed)),
+        ("silence", None) => Box::new(Silence),
+        ("delayed", argument) => {
+            let delay_ms = match argument {
+                Some(ms) => ms
+                    .parse()
+                    .map_err(|_| format!("invalid delay '{}', expected milliseconds", ms))?,
+                None => DEFAULT_DELAY_MS,
+            };
+            Box::new(Delayed {
+                delay: Duration::from_millis(delay_ms),
+            })
+        }
+        ("withholding", None) => Box::new(Withholding),
+        ("replay", None) => Box::new(Replay::default()),
+        ("invalid-signature", None) => Box::new(InvalidSignature),
+        _ => {
+            return Err(format!(
+                "unknown byzantine strategy '{}', expected one of: {}",
+                spec,
+                STRATEGIES.join(", ")
+            ))
+        }
+    };
+    Ok(strategy)
+}
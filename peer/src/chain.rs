@@ -0,0 +1,237 @@
+use crate::protocol::{BlockHash, Decision, Signed, Vote};
+use crate::validators::Validators;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+// Hash of the (empty) parent of the first block.
+pub const GENESIS_HASH: BlockHash = [0u8; 32];
+
+// A decided round: the value the peers settled on at `height`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Block {
+    pub view: u64,
+    pub height: u64,
+    pub parent: BlockHash,
+    pub value: Decision,
+}
+
+impl Block {
+    pub fn hash(&self) -> BlockHash {
+        let bytes = serde_json::to_vec(self).expect("blocks always serialize");
+        Sha256::digest(bytes).into()
+    }
+}
+
+// The signed votes a node counted before deciding a block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuorumCertificate {
+    pub votes: Vec<Signed<Vote>>,
+}
+
+impl QuorumCertificate {
+    // Number of (ATTACK, RETREAT) votes in the certificate.
+    pub fn tally(&self) -> (u32, u32) {
+        let attack = self
+            .votes
+            .iter()
+            .filter(|vote| vote.body.value == Decision::Attack)
+            .count() as u32;
+        (attack, self.votes.len() as u32 - attack)
+    }
+
+    // Applies the same majority rule as `print_consensus`: ATTACK wins only with
+    // strictly more votes than RETREAT.
+    pub fn decision(&self) -> Decision {
+        let (attack_count, retreat_count) = self.tally();
+        if attack_count > retreat_count {
+            Decision::Attack
+        } else {
+            Decision::Retreat
+        }
+    }
+
+    // Checks that every vote is validly signed by a validator, unique per signer
+    // and cast for this block's slot, that a quorum of them voted for the
+    // block's value, and that they actually decide it. Two certificates for
+    // different values of one slot would need an honest validator to vote twice.
+    pub fn verify(&self, block: &Block, validators: &Validators) -> Result<(), ChainError> {
+        if self.votes.is_empty() {
+            return Err(ChainError::EmptyCertificate(block.height));
+        }
+        let mut voters = HashSet::new();
+        for vote in &self.votes {
+            if !vote.verify() {
+                return Err(ChainError::InvalidSignature(block.height));
+            }
+            if !validators.is_member(&vote.public_key) {
+                return Err(ChainError::UnknownVoter(block.height));
+            }
+            if !voters.insert(vote.public_key.clone()) {
+                return Err(ChainError::DuplicateVoter(block.height));
+            }
+            if vote.body.height != block.height
+                || vote.body.view != block.view
+                || vote.body.parent != block.parent
+            {
+                return Err(ChainError::ForeignVote(block.height));
+            }
+        }
+        let agreeing = self.votes.iter().filter(|vote| vote.body.value == block.value).count();
+        if agreeing < validators.quorum() {
+            return Err(ChainError::NoQuorum {
+                height: block.height,
+                votes: agreeing,
+                quorum: validators.quorum(),
+            });
+        }
+        if self.decision() != block.value {
+            return Err(ChainError::WrongDecision(block.height));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CertifiedBlock {
+    pub block: Block,
+    pub qc: QuorumCertificate,
+}
+
+// The latest certified block, enough for a far-behind node to jump straight to
+// the tip instead of replaying every block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub tip: CertifiedBlock,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainError {
+    UnexpectedHeight { expected: u64, got: u64 },
+    ParentMismatch(u64),
+    EmptyCertificate(u64),
+    InvalidSignature(u64),
+    UnknownVoter(u64),
+    DuplicateVoter(u64),
+    NoQuorum { height: u64, votes: usize, quorum: usize },
+    ForeignVote(u64),
+    WrongDecision(u64),
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainError::UnexpectedHeight { expected, got } => {
+                write!(f, "expected block at height {}, got {}", expected, got)
+            }
+            ChainError::ParentMismatch(h) => write!(f, "block {} does not extend our tip", h),
+            ChainError::EmptyCertificate(h) => write!(f, "block {} has no votes", h),
+            ChainError::InvalidSignature(h) => write!(f, "block {} carries a bad vote signature", h),
+            ChainError::UnknownVoter(h) => write!(f, "block {} carries a vote of a non-validator", h),
+            ChainError::DuplicateVoter(h) => write!(f, "block {} counts a voter twice", h),
+            ChainError::NoQuorum { height, votes, quorum } => {
+                write!(f, "block {} has {} votes, {} are needed", height, votes, quorum)
+            }
+            ChainError::ForeignVote(h) => write!(f, "block {} carries a vote for another slot", h),
+            ChainError::WrongDecision(h) => write!(f, "block {} value is not what its votes decide", h),
+        }
+    }
+}
+
+impl Error for ChainError {}
+
+// The decided blocks this node knows about. After a snapshot restore the chain
+// starts at `base_height` and older blocks are unknown.
+pub struct Chain {
+    validators: Validators,
+    base_height: u64,
+    base_hash: BlockHash,
+    blocks: Vec<CertifiedBlock>,
+}
+
+impl Chain {
+    // Blocks are only accepted with a quorum of `validators`.
+    pub fn new(validators: Validators) -> Self {
+        Chain {
+            validators,
+            base_height: 0,
+            base_hash: GENESIS_HASH,
+            blocks: Vec::new(),
+        }
+    }
+
+    pub fn validators(&self) -> &Validators {
+        &self.validators
+    }
+
+    // Height of the highest certified block, 0 when nothing has been decided.
+    pub fn height(&self) -> u64 {
+        self.base_height + self.blocks.len() as u64
+    }
+
+    pub fn base_height(&self) -> u64 {
+        self.base_height
+    }
+
+    pub fn tip_hash(&self) -> BlockHash {
+        self.blocks
+            .last()
+            .map(|cb| cb.block.hash())
+            .unwrap_or(self.base_hash)
+    }
+
+    pub fn tip(&self) -> Option<&CertifiedBlock> {
+        self.blocks.last()
+    }
+
+    pub fn get(&self, height: u64) -> Option<&CertifiedBlock> {
+        if height <= self.base_height {
+            return None;
+        }
+        self.blocks.get((height - self.base_height - 1) as usize)
+    }
+
+    // Blocks `from..=to` that this node still holds, in order.
+    pub fn range(&self, from: u64, to: u64) -> Vec<CertifiedBlock> {
+        (from..=to).map_while(|h| self.get(h).cloned()).collect()
+    }
+
+    // Verifies a certified block against the tip and appends it.
+    pub fn apply(&mut self, certified: CertifiedBlock) -> Result<(), ChainError> {
+        let block = &certified.block;
+        if block.height != self.height() + 1 {
+            return Err(ChainError::UnexpectedHeight {
+                expected: self.height() + 1,
+                got: block.height,
+            });
+        }
+        if block.parent != self.tip_hash() {
+            return Err(ChainError::ParentMismatch(block.height));
+        }
+        certified.qc.verify(block, &self.validators)?;
+        self.blocks.push(certified);
+        Ok(())
+    }
+
+    pub fn snapshot(&self) -> Option<Snapshot> {
+        self.tip().cloned().map(|tip| Snapshot { tip })
+    }
+
+    // Replaces the local chain with a verified snapshot of a higher tip.
+    pub fn restore(&mut self, snapshot: Snapshot) -> Result<(), ChainError> {
+        let block = &snapshot.tip.block;
+        if block.height <= self.height() {
+            return Err(ChainError::UnexpectedHeight {
+                expected: self.height() + 1,
+                got: block.height,
+            });
+        }
+        snapshot.tip.qc.verify(block, &self.validators)?;
+        self.base_height = block.height - 1;
+        self.base_hash = block.parent;
+        self.blocks = vec![snapshot.tip];
+        Ok(())
+    }
+}
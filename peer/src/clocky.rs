@@ -1,13 +1,22 @@
 use std::net::UdpSocket;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 use sntpc;
 use chrono::{NaiveDateTime, Timelike};
 use tokio::time::interval;
+use peer::events::{self, NodeEvent};
 
 // Global variable to hold the logical clock value
 static mut LOGICAL_CLOCK: u64 = 0;
 
+// NTP server to synchronise with, time.google.com unless `set_ntp_server` chose another.
+static NTP_SERVER: OnceLock<String> = OnceLock::new();
+const DEFAULT_NTP_SERVER: &str = "time.google.com:123";
+
+pub fn set_ntp_server(server: String) {
+    let _ = NTP_SERVER.set(server);
+}
+
 pub async fn start_logical_clock() {
     let socket = UdpSocket::bind("0.0.0.0:0").expect("Unable to create UDP socket");
     socket
@@ -25,13 +34,15 @@ pub async fn start_logical_clock() {
     }
 }
 
-pub fn synchronize_logical_clock() {
+// Returns the NTP offset and round trip in microseconds if the server answered.
+pub fn synchronize_logical_clock() -> Option<(i64, u64)> {
     let socket = UdpSocket::bind("0.0.0.0:0").expect("Unable to create UDP socket");
     socket
         .set_read_timeout(Some(Duration::from_secs(2)))
         .expect("Unable to set UDP socket read timeout");
     
-    match sntpc::simple_get_time("time.google.com:123", socket.try_clone().unwrap()) {
+    let server = NTP_SERVER.get().map(String::as_str).unwrap_or(DEFAULT_NTP_SERVER);
+    match sntpc::simple_get_time(server, socket.try_clone().unwrap()) {
     //match sntpc::simple_get_time("time.uni-paderborn.de", socket.try_clone().unwrap()) {
         Ok(time) => {
             unsafe {
@@ -39,8 +50,16 @@ pub fn synchronize_logical_clock() {
                 LOGICAL_CLOCK = time.sec() as u64 + 1; // Add 1 second offset
                 println!("Logical Clock synchronized with NTP: {}", LOGICAL_CLOCK);
             }
+            events::record(&NodeEvent::ClockSync {
+                offset_us: time.offset(),
+                delay_us: time.roundtrip(),
+            });
+            Some((time.offset(), time.roundtrip()))
+        }
+        Err(err) => {
+            println!("Failed to synchronize with NTP server {}: {:?}", server, err);
+            None
         }
-        Err(err) => println!("Failed to synchronize with NTP: {:?}", err),
     }
 }
 
@@ -0,0 +1,116 @@
+use crate::control::Server;
+use std::io;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing_subscriber::fmt::MakeWriter;
+
+// Events waiting to be pushed. When the collector is unreachable for long, the
+// oldest are dropped rather than growing without bound.
+const MAX_PENDING: usize = 10_000;
+const PUSH_INTERVAL: Duration = Duration::from_millis(500);
+// Largest body of one push, well below the 2 MB peer-server accepts, so that a
+// backlog goes out in several batches.
+const MAX_BATCH_BYTES: usize = 1024 * 1024;
+
+// A tracing writer that hands every formatted event line to `push`. The fmt
+// layer writes each event with a single `write_all`, so a write is one line.
+#[derive(Clone)]
+pub struct Lines(mpsc::UnboundedSender<Vec<u8>>);
+
+impl Lines {
+    pub fn new() -> (Self, mpsc::UnboundedReceiver<Vec<u8>>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Lines(sender), receiver)
+    }
+}
+
+impl io::Write for Lines {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The pushing task only stops with the runtime; events after that are lost.
+        let _ = self.0.send(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Lines {
+    type Writer = Lines;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+// Posts the collected lines to peer-server's `/events` as newline-delimited
+// JSON, in batches of at most `MAX_BATCH_BYTES`, for as long as the node runs.
+// Lines that could not be delivered are kept and sent again with the next
+// push, unless the server refused their batch as malformed. Only the first
+// failure in a row is reported.
+pub async fn push(server: Server, mut lines: mpsc::UnboundedReceiver<Vec<u8>>) {
+    let url = format!("{}/events", server.url());
+    let client = server.client();
+    let mut pending: Vec<Vec<u8>> = Vec::new();
+    let mut failing = false;
+    let mut interval = tokio::time::interval(PUSH_INTERVAL);
+    loop {
+        interval.tick().await;
+        while let Ok(line) = lines.try_recv() {
+            pending.push(line);
+        }
+        if pending.len() > MAX_PENDING {
+            pending.drain(..pending.len() - MAX_PENDING);
+        }
+        while !pending.is_empty() {
+            let batch = batch_len(&pending);
+            match client
+                .post(&url)
+                .header("content-type", "application/x-ndjson")
+                .body(pending[..batch].concat())
+                .send()
+                .await
+            {
+                Ok(response) if response.status().is_success() => {
+                    pending.drain(..batch);
+                    failing = false;
+                }
+                Ok(response) if response.status() == reqwest::StatusCode::BAD_REQUEST => {
+                    if !failing {
+                        println!("Collector rejected {batch} malformed events");
+                    }
+                    pending.drain(..batch);
+                    failing = true;
+                }
+                Ok(response) => {
+                    if !failing {
+                        println!("Collector did not take {} events: {}", pending.len(), response.status());
+                    }
+                    failing = true;
+                    break;
+                }
+                Err(e) => {
+                    if !failing {
+                        println!("Could not push events to collector: {e}");
+                    }
+                    failing = true;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// How many of the pending lines fit into one batch, at least one.
+fn batch_len(pending: &[Vec<u8>]) -> usize {
+    let mut bytes = 0;
+    pending
+        .iter()
+        .take_while(|line| {
+            bytes += line.len();
+            bytes <= MAX_BATCH_BYTES
+        })
+        .count()
+        .max(1)
+}
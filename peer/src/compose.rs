@@ -0,0 +1,282 @@
+use crate::byzantine;
+use crate::keys;
+use crate::validators;
+use libp2p::Multiaddr;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+// Where a key is mounted in each container.
+const KEY_DIR: &str = "/keys";
+// Where cluster.json is mounted in each container, as the validator set.
+const VALIDATORS: &str = "/cluster.json";
+const METRICS_PORT: u16 = 9898;
+
+// A cluster to run with docker-compose, as read from a YAML file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClusterSpec {
+    // Number of nodes running the honest protocol.
+    pub honest: usize,
+    #[serde(default)]
+    pub byzantine: Vec<Group>,
+    // Byzantine nodes the cluster tolerates, (nodes - 1) / 3 by default.
+    #[serde(default)]
+    pub faults: Option<usize>,
+    // NTP servers as host:port, handed out to the nodes in turn.
+    #[serde(default = "default_time_servers")]
+    pub time_servers: Vec<String>,
+    // Shaping of every node's outgoing traffic, unless its group sets its own.
+    #[serde(default)]
+    pub latency: Option<Latency>,
+    #[serde(default = "default_server")]
+    pub server: Option<String>,
+    #[serde(default = "default_image")]
+    pub image: String,
+    #[serde(default = "default_network")]
+    pub network: String,
+    // TCP port every node listens on inside its container.
+    #[serde(default = "default_port")]
+    pub port: u16,
+}
+
+// Nodes that run the same byzantine strategy.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Group {
+    pub strategy: String,
+    pub count: usize,
+    #[serde(default)]
+    pub latency: Option<Latency>,
+}
+
+// Delay, jitter and loss added to a node's outgoing packets with netem.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Latency {
+    #[serde(default)]
+    pub delay_ms: u64,
+    #[serde(default)]
+    pub jitter_ms: u64,
+    #[serde(default)]
+    pub loss_percent: f64,
+}
+
+fn default_time_servers() -> Vec<String> {
+    vec!["time.google.com:123".to_string()]
+}
+
+fn default_server() -> Option<String> {
+    Some("http://peer-server:3000".to_string())
+}
+
+fn default_image() -> String {
+    "peer:latest".to_string()
+}
+
+fn default_network() -> String {
+    "peer-server_default".to_string()
+}
+
+fn default_port() -> u16 {
+    4000
+}
+
+impl ClusterSpec {
+    pub fn load(path: &Path) -> Result<ClusterSpec, Box<dyn Error>> {
+        let text = fs::read_to_string(path).map_err(|e| format!("could not read {}: {e}", path.display()))?;
+        let spec: ClusterSpec = serde_yaml::from_str(&text).map_err(|e| format!("invalid cluster spec {}: {e}", path.display()))?;
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    pub fn nodes(&self) -> usize {
+        self.honest + self.byzantine.iter().map(|group| group.count).sum::<usize>()
+    }
+
+    pub fn faults(&self) -> usize {
+        self.faults.unwrap_or(validators::max_faults(self.nodes()))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.nodes() == 0 {
+            return Err("a cluster needs at least one node".to_string());
+        }
+        if self.faults() > validators::max_faults(self.nodes()) {
+            return Err(format!(
+                "{} nodes tolerate at most {} faults, not {}",
+                self.nodes(),
+                validators::max_faults(self.nodes()),
+                self.faults()
+            ));
+        }
+        for group in &self.byzantine {
+            byzantine::from_name(&group.strategy, 0)?;
+        }
+        if self.time_servers.is_empty() {
+            return Err("time_servers must name at least one NTP server".to_string());
+        }
+        for latency in self.latency.iter().chain(self.byzantine.iter().filter_map(|group| group.latency.as_ref())) {
+            if !(0.0..=100.0).contains(&latency.loss_percent) {
+                return Err(format!("loss_percent {} is not between 0 and 100", latency.loss_percent));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Latency {
+    // The netem parameters, e.g. "delay 50ms 10ms loss 1%".
+    fn netem(&self) -> String {
+        let mut netem = Vec::new();
+        if self.delay_ms > 0 || self.jitter_ms > 0 {
+            netem.push(format!("delay {}ms", self.delay_ms));
+            if self.jitter_ms > 0 {
+                netem.push(format!("{}ms", self.jitter_ms));
+            }
+        }
+        if self.loss_percent > 0.0 {
+            netem.push(format!("loss {}%", self.loss_percent));
+        }
+        netem.join(" ")
+    }
+}
+
+// One node of the cluster, as listed in <dir>/cluster.json.
+#[derive(Debug, Clone, Serialize)]
+pub struct Node {
+    pub index: usize,
+    pub service: String,
+    pub peer_id: String,
+    pub role: String,
+    // The address the other nodes dial.
+    pub address: Multiaddr,
+    pub time_server: String,
+    pub latency: Option<Latency>,
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    faults: usize,
+    nodes: &'a [Node],
+}
+
+#[derive(Serialize)]
+struct Service {
+    image: String,
+    environment: Vec<String>,
+    volumes: Vec<String>,
+    expose: Vec<String>,
+    networks: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    cap_add: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entrypoint: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+struct Network {
+    external: bool,
+}
+
+#[derive(Serialize)]
+struct Compose {
+    services: Mapping,
+    networks: BTreeMap<String, Network>,
+}
+
+// Writes the cluster to `dir`: a key per node under keys/, generated on the
+// first run and reused afterwards, cluster.json listing every node, and
+// docker-compose.yaml with a service per node that dials all the others and
+// reads cluster.json as its validator set.
+pub fn generate(spec: &ClusterSpec, dir: &Path) -> Result<Vec<Node>, Box<dyn Error>> {
+    let mut roles = vec![("peer", "honest".to_string(), spec.latency); spec.honest];
+    for group in &spec.byzantine {
+        roles.extend(vec![("peerb", group.strategy.clone(), group.latency.or(spec.latency)); group.count]);
+    }
+
+    let mut nodes = Vec::with_capacity(roles.len());
+    for (index, (prefix, role, latency)) in roles.into_iter().enumerate() {
+        let service = format!("{prefix}-{index}");
+        let key_dir = dir.join("keys").join(&service);
+        fs::create_dir_all(&key_dir)?;
+        let peer_id = keys::load_or_generate(&key_dir.join("key"))?.public().to_peer_id();
+        nodes.push(Node {
+            index,
+            address: format!("/dns4/{service}/tcp/{}/p2p/{peer_id}", spec.port).parse()?,
+            service,
+            peer_id: peer_id.to_string(),
+            role,
+            time_server: spec.time_servers[index % spec.time_servers.len()].clone(),
+            latency: latency.filter(|latency| *latency != Latency::default()),
+        });
+    }
+
+    let manifest = Manifest {
+        faults: spec.faults(),
+        nodes: &nodes,
+    };
+    fs::write(dir.join("cluster.json"), serde_json::to_vec_pretty(&manifest)?)?;
+
+    let mut services = Mapping::new();
+    for node in &nodes {
+        services.insert(Value::from(node.service.clone()), serde_yaml::to_value(service(spec, node, &nodes))?);
+    }
+    let compose = Compose {
+        services,
+        networks: BTreeMap::from([(spec.network.clone(), Network { external: true })]),
+    };
+    let header = "# Generated by `feverbft compose` from a cluster spec, together with\n# cluster.json and keys/. Change the spec and generate again instead of\n# editing this file.\n";
+    fs::write(dir.join("docker-compose.yaml"), format!("{header}{}", serde_yaml::to_string(&compose)?))?;
+    Ok(nodes)
+}
+
+fn service(spec: &ClusterSpec, node: &Node, nodes: &[Node]) -> Service {
+    let bootstrap: Vec<String> = nodes
+        .iter()
+        .filter(|other| other.index != node.index)
+        .map(|other| other.address.to_string())
+        .collect();
+    let mut environment = vec![
+        format!("FEVER_KEY={KEY_DIR}/key"),
+        format!("FEVER_LISTEN=/ip4/0.0.0.0/tcp/{}", spec.port),
+        format!("FEVER_BYZANTINE={}", node.role),
+        format!("FEVER_VALIDATORS={VALIDATORS}"),
+        format!("FEVER_FAULTS={}", spec.faults()),
+        format!("FEVER_NTP_SERVER={}", node.time_server),
+        format!("FEVER_METRICS_ADDR=0.0.0.0:{METRICS_PORT}"),
+    ];
+    if !bootstrap.is_empty() {
+        environment.push(format!("FEVER_BOOTSTRAP={}", bootstrap.join(",")));
+    }
+    if let Some(server) = &spec.server {
+        environment.push(format!("FEVER_SERVER={server}"));
+    }
+    // netem needs NET_ADMIN to shape the container's own interface.
+    let (cap_add, entrypoint) = match &node.latency {
+        Some(latency) => (
+            vec!["NET_ADMIN".to_string()],
+            Some(vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                format!("tc qdisc add dev eth0 root netem {} && exec peer", latency.netem()),
+            ]),
+        ),
+        None => (Vec::new(), None),
+    };
+    Service {
+        image: spec.image.clone(),
+        environment,
+        volumes: vec![
+            format!("./keys/{}:{KEY_DIR}:ro", node.service),
+            format!("./cluster.json:{VALIDATORS}:ro"),
+        ],
+        expose: vec![METRICS_PORT.to_string()],
+        networks: vec![spec.network.clone()],
+        cap_add,
+        entrypoint,
+    }
+}
@@ -0,0 +1,117 @@
+use clap::{Parser, ValueEnum};
+use libp2p::gossipsub::PeerScoreThresholds;
+use libp2p::Multiaddr;
+use peer::viewsync;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+// Command line options of a peer. Every option can also be set through the
+// environment, which is how the docker-compose services configure replicas.
+#[derive(Parser, Debug)]
+#[command(name = "peer", about = "FeverBFT consensus peer")]
+pub struct Config {
+    /// How this node behaves: honest, equivocation, inverted, random, silence,
+    /// delayed[:ms], withholding, replay or invalid-signature
+    #[arg(long, env = "FEVER_BYZANTINE", default_value = "honest")]
+    pub byzantine: String,
+
+    /// Gossipsub score below which we stop gossiping to a peer
+    #[arg(long, env = "FEVER_GOSSIP_THRESHOLD", default_value_t = -10.0, allow_negative_numbers = true)]
+    pub gossip_threshold: f64,
+
+    /// Gossipsub score below which we stop publishing to a peer
+    #[arg(long, env = "FEVER_PUBLISH_THRESHOLD", default_value_t = -50.0, allow_negative_numbers = true)]
+    pub publish_threshold: f64,
+
+    /// Gossipsub score below which all messages of a peer are ignored
+    #[arg(long, env = "FEVER_GRAYLIST_THRESHOLD", default_value_t = -80.0, allow_negative_numbers = true)]
+    pub graylist_threshold: f64,
+
+    /// Gossipsub score at which a peer is disconnected and blocked
+    #[arg(long, env = "FEVER_BAN_THRESHOLD", default_value_t = -100.0, allow_negative_numbers = true)]
+    pub ban_threshold: f64,
+
+    /// Messages a single author may publish per 10 seconds before it counts as spam
+    #[arg(long, env = "FEVER_SPAM_LIMIT", default_value_t = 100)]
+    pub spam_limit: u32,
+
+    /// Number of byzantine nodes tolerated; f+1 view announcements move a node's clock.
+    /// The most the validator set tolerates by default, or 1 without --validators
+    #[arg(long, env = "FEVER_FAULTS")]
+    pub faults: Option<usize>,
+
+    /// cluster.json listing the validators as nodes[].peer_id; only their votes and view announcements count.
+    /// Without it any key counts, which byzantine peers can exploit with made-up keys
+    #[arg(long, env = "FEVER_VALIDATORS")]
+    pub validators: Option<PathBuf>,
+
+    /// Length of a view in milliseconds
+    #[arg(long, env = "FEVER_VIEW_LENGTH", default_value_t = viewsync::VIEW_LENGTH_MS)]
+    pub view_length: u64,
+
+    /// Format of tracing output on stderr, filtered by RUST_LOG
+    #[arg(long, env = "FEVER_LOG_FORMAT", value_enum, default_value = "text")]
+    pub log_format: LogFormat,
+
+    /// File to append the node's events to, one JSON object per line
+    #[arg(long, env = "FEVER_EVENTS")]
+    pub events: Option<PathBuf>,
+
+    /// peer-server to register with, take commands from and push the node's events to,
+    /// e.g. http://peer-server:3000
+    #[arg(long, env = "FEVER_SERVER")]
+    pub server: Option<String>,
+
+    /// Token to present to peer-server, the one PEER_SERVER_PEER_TOKENS gives this peer's id
+    #[arg(long, env = "FEVER_SERVER_TOKEN", hide_env_values = true)]
+    pub server_token: Option<String>,
+
+    /// PEM certificate to trust for an https --server, e.g. the cert.pem peer-server generated
+    #[arg(long, env = "FEVER_SERVER_CA")]
+    pub server_ca: Option<PathBuf>,
+
+    /// Address to serve Prometheus metrics on at /metrics, e.g. 0.0.0.0:9898
+    #[arg(long, env = "FEVER_METRICS_ADDR")]
+    pub metrics_addr: Option<SocketAddr>,
+
+    /// OTLP/HTTP collector to export consensus round traces to, e.g. http://localhost:4318
+    #[arg(long, env = "FEVER_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+
+    /// File to append consensus round trace spans to, one JSON object per line
+    #[arg(long, env = "FEVER_TRACE_FILE")]
+    pub trace_file: Option<PathBuf>,
+
+    /// File holding the node's identity key, generated there if missing, so the peer id stays the same across restarts
+    #[arg(long, env = "FEVER_KEY")]
+    pub key: Option<PathBuf>,
+
+    /// Addresses to listen on, e.g. /ip4/127.0.0.1/tcp/4000; any port on all interfaces over QUIC and TCP by default
+    #[arg(long, env = "FEVER_LISTEN", value_delimiter = ',')]
+    pub listen: Vec<Multiaddr>,
+
+    /// Peers to connect to on startup and keep connected to, as /ip4/... or /dns4/<host>/tcp/.../p2p/<peer id>, in addition to the ones found over mDNS
+    #[arg(long, env = "FEVER_BOOTSTRAP", value_delimiter = ',')]
+    pub bootstrap: Vec<Multiaddr>,
+
+    /// NTP server to synchronise the logical clock with, as host:port
+    #[arg(long, env = "FEVER_NTP_SERVER", default_value = "time.google.com:123")]
+    pub ntp_server: String,
+}
+
+impl Config {
+    pub fn score_thresholds(&self) -> PeerScoreThresholds {
+        PeerScoreThresholds {
+            gossip_threshold: self.gossip_threshold,
+            publish_threshold: self.publish_threshold,
+            graylist_threshold: self.graylist_threshold,
+            ..Default::default()
+        }
+    }
+}
@@ -0,0 +1,496 @@
+use crate::byzantine::{ByzantineStrategy, Outgoing, Recipients};
+use crate::chain::{Block, CertifiedBlock, Chain, ChainError, QuorumCertificate};
+use crate::evidence::{Evidence, EvidencePool};
+use crate::protocol::{Decision, Message, Proposal, Signed, ViewCertificate, ViewMessage, Vote};
+use crate::sync::{self, SyncRequest, SyncResponse};
+use crate::validators::Validators;
+use crate::viewsync::{SyncAction, ViewSync};
+use libp2p::identity::Keypair;
+use libp2p::PeerId;
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+
+// How long a round collects votes before the node decides.
+pub const VOTE_WINDOW: Duration = Duration::from_millis(5000);
+
+// What the core asks its driver to do. The core never touches the network or the
+// clock itself, so the same logic runs under the libp2p swarm and in the
+// simulator.
+#[derive(Debug, Clone)]
+pub enum Action {
+    // The node moved on to a higher view.
+    ViewEntered { view: u64 },
+    // A view certificate moved the view clock forward, in milliseconds.
+    ClockBumped { from: u64, to: u64 },
+    // Publish `message` to `to` once `delay` has passed.
+    Broadcast {
+        message: Message,
+        delay: Duration,
+        to: Recipients,
+    },
+    // Call `Core::close_round(view)` once `after` has passed on the local clock.
+    CloseRoundAfter { view: u64, after: Duration },
+    // Send a block sync request to `peer`.
+    Sync { peer: PeerId, request: SyncRequest },
+    // A round ended with this tally.
+    Decided {
+        view: u64,
+        height: u64,
+        attack: u32,
+        retreat: u32,
+        value: Decision,
+    },
+    // A block was appended to the chain, decided locally or fetched by sync.
+    Applied { block: Block, synced: bool },
+    // The chain jumped to a snapshot of this block.
+    Restored(Block),
+    // A decided or synced block did not fit the chain.
+    Rejected { height: u64, error: ChainError },
+    // Sync with `peer` finished at `height`.
+    CaughtUp { peer: PeerId, height: u64 },
+    // New equivocation proof; the offender should be penalised.
+    Equivocation(Evidence),
+}
+
+// The votes collected for the proposal currently being decided.
+struct Round {
+    proposal: Proposal,
+    votes: Vec<Signed<Vote>>,
+}
+
+// Consensus state of one node, driven by messages and timers.
+pub struct Core {
+    keypair: Keypair,
+    chain: Chain,
+    views: ViewSync<Vec<u8>>,
+    // Signed announcements of views above ours, by view, for certificates.
+    announcements: BTreeMap<u64, Vec<Signed<ViewMessage>>>,
+    // Local time in milliseconds as of the last tick.
+    now: u64,
+    // The last view this node proposed in; a leader proposes once per view.
+    proposed: Option<u64>,
+    strategy: Box<dyn ByzantineStrategy>,
+    evidence: EvidencePool,
+    round: Option<Round>,
+    // Peer we are catching up from, with the height and base height it reported.
+    sync_target: Option<(PeerId, u64, u64)>,
+}
+
+impl Core {
+    // `views` identifies signers by their encoded public key. Blocks need the
+    // votes of a quorum of `validators`.
+    pub fn new(
+        keypair: Keypair,
+        strategy: Box<dyn ByzantineStrategy>,
+        views: ViewSync<Vec<u8>>,
+        validators: Validators,
+    ) -> Self {
+        Core {
+            keypair,
+            chain: Chain::new(validators),
+            views,
+            announcements: BTreeMap::new(),
+            now: 0,
+            proposed: None,
+            strategy,
+            evidence: EvidencePool::default(),
+            round: None,
+            sync_target: None,
+        }
+    }
+
+    pub fn peer_id(&self) -> PeerId {
+        self.keypair.public().to_peer_id()
+    }
+
+    pub fn view(&self) -> u64 {
+        self.views.view()
+    }
+
+    pub fn view_sync(&self) -> &ViewSync<Vec<u8>> {
+        &self.views
+    }
+
+    pub fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
+    pub fn evidence(&self) -> &EvidencePool {
+        &self.evidence
+    }
+
+    pub fn strategy_name(&self) -> &'static str {
+        self.strategy.name()
+    }
+
+    // Switches behaviour from the next message on. The round in progress, if
+    // any, is still decided from the votes it collects.
+    pub fn set_strategy(&mut self, strategy: Box<dyn ByzantineStrategy>) {
+        self.strategy = strategy;
+    }
+
+    pub fn in_round(&self) -> bool {
+        self.round.is_some()
+    }
+
+    // View of the round whose votes are being counted, if any.
+    pub fn round_view(&self) -> Option<u64> {
+        self.round.as_ref().map(|round| round.proposal.view)
+    }
+
+    // Whether this node already led a round in its current view.
+    pub fn proposed_in_view(&self) -> bool {
+        self.proposed == Some(self.view())
+    }
+
+    // Advances the local clock to `now` since the node started, entering any
+    // view the clock reached.
+    pub fn tick(&mut self, now: Duration) -> Vec<Action> {
+        self.now = now.as_millis() as u64;
+        let sync_actions = self.views.on_time(self.now);
+        self.apply_sync(sync_actions)
+    }
+
+    // Leads a round in the current view: publishes a signed proposal for the
+    // next height as the strategy decides, votes on it like every other
+    // validator and waits for the votes that come back.
+    pub fn propose(&mut self, value: Decision) -> Vec<Action> {
+        let view = self.view();
+        self.proposed = Some(view);
+        let proposal = Proposal {
+            view,
+            height: self.chain.height() + 1,
+            parent: self.chain.tip_hash(),
+            value,
+        };
+        let mut actions: Vec<Action> = self
+            .strategy
+            .on_propose(&proposal, &self.keypair)
+            .into_iter()
+            .map(|Outgoing { message, delay, to }| Action::Broadcast { message, delay, to })
+            .collect();
+        self.vote_on(proposal, &mut actions);
+        actions
+    }
+
+    // Handles a validated gossip message forwarded by `from`.
+    pub fn on_message(&mut self, from: PeerId, message: Message) -> Vec<Action> {
+        self.strategy.observe(&message);
+        let mut actions = Vec::new();
+        match message {
+            Message::Proposal(proposal) => {
+                if !self.chain.validators().is_member(&proposal.public_key)
+                    || self.evidence.is_excluded(&proposal.public_key)
+                {
+                    return actions;
+                }
+                if let Some(evidence) = self.evidence.check_proposal(&proposal, self.view()) {
+                    // We already answered the first proposal for this view.
+                    report(&mut actions, evidence);
+                    return actions;
+                }
+                if self.round.is_some() {
+                    // Still deciding the previous round.
+                    return actions;
+                }
+                self.on_proposal(from, proposal.body, &mut actions);
+            }
+            Message::Vote(vote) => {
+                // Only validators' votes count, so only theirs are worth tracking.
+                if !self.chain.validators().is_member(&vote.public_key) {
+                    return actions;
+                }
+                if let Some(evidence) = self.evidence.check_vote(&vote, self.view()) {
+                    report(&mut actions, evidence);
+                }
+                if let Some(round) = &mut self.round {
+                    if counts_for(&vote, &round.proposal) {
+                        round.votes.push(vote);
+                    }
+                }
+            }
+            Message::Evidence(evidence) => {
+                if self.chain.validators().is_member(evidence.public_key()) && self.evidence.add(evidence.clone()) {
+                    actions.push(Action::Equivocation(evidence));
+                }
+            }
+            Message::View(announcement) => {
+                // Only validators' announcements count towards a certificate, and
+                // only for views within reach, so both maps stay bounded.
+                let view = announcement.body.view;
+                if !self.views.is_pending(view)
+                    || !self.chain.validators().is_member(&announcement.public_key)
+                    || self.evidence.is_excluded(&announcement.public_key)
+                {
+                    return actions;
+                }
+                let signer = announcement.public_key.clone();
+                let held = self.announcements.entry(view).or_default();
+                if !held.iter().any(|held| held.public_key == signer) {
+                    held.push(announcement);
+                }
+                let sync_actions = self.views.on_view_message(signer, view, self.now);
+                actions.extend(self.apply_sync(sync_actions));
+            }
+            Message::ViewCertificate(certificate) => {
+                let view = certificate.view;
+                if view <= self.view() || certificate.signers().is_none() {
+                    return actions;
+                }
+                // Relay only the validators' announcements, and only if enough of
+                // them remain.
+                let validators = self.chain.validators();
+                let messages: Vec<_> = certificate
+                    .messages
+                    .into_iter()
+                    .filter(|message| validators.is_member(&message.public_key))
+                    .collect();
+                if messages.len() < self.views.threshold() {
+                    return actions;
+                }
+                self.announcements.insert(view, messages);
+                let sync_actions = self.views.on_certificate(view, self.now);
+                actions.extend(self.apply_sync(sync_actions));
+            }
+        }
+        actions
+    }
+
+    fn apply_sync(&mut self, sync_actions: Vec<SyncAction>) -> Vec<Action> {
+        let mut actions = Vec::new();
+        for sync_action in sync_actions {
+            match sync_action {
+                SyncAction::Entered(view) => {
+                    self.announcements = self.announcements.split_off(&(view + 1));
+                    actions.push(Action::ViewEntered { view });
+                }
+                SyncAction::SendView(view) => actions.push(Action::Broadcast {
+                    message: Message::View(Signed::new(ViewMessage { view }, &self.keypair)),
+                    delay: Duration::ZERO,
+                    to: Recipients::All,
+                }),
+                SyncAction::SendCertificate(view) => {
+                    let messages: Vec<_> = self
+                        .announcements
+                        .get(&view)
+                        .into_iter()
+                        .flatten()
+                        .take(self.views.threshold())
+                        .cloned()
+                        .collect();
+                    actions.push(Action::Broadcast {
+                        message: Message::ViewCertificate(ViewCertificate { view, messages }),
+                        delay: Duration::ZERO,
+                        to: Recipients::All,
+                    });
+                }
+                SyncAction::Bumped { from, to } => actions.push(Action::ClockBumped { from, to }),
+            }
+        }
+        actions
+    }
+
+    fn on_proposal(&mut self, from: PeerId, proposal: Proposal, actions: &mut Vec<Action>) {
+        if proposal.height > self.chain.height() + 1 {
+            // The leader is building on blocks we never saw.
+            actions.push(Action::Sync {
+                peer: from,
+                request: SyncRequest::Status,
+            });
+        }
+        // Only a proposal extending our tip gets a vote, so we never help decide a
+        // second block at a height we already decided.
+        if proposal.height != self.chain.height() + 1 || proposal.parent != self.chain.tip_hash() {
+            return;
+        }
+        self.vote_on(proposal, actions);
+    }
+
+    // Answers a proposal as the strategy decides and opens its round, counting
+    // our own votes straight away.
+    fn vote_on(&mut self, proposal: Proposal, actions: &mut Vec<Action>) {
+        let mut own_votes = Vec::new();
+        for Outgoing { message, delay, to } in self.strategy.on_proposal(&proposal, &self.keypair) {
+            if delay.is_zero() {
+                if let Message::Vote(vote) = &message {
+                    if counts_for(vote, &proposal) {
+                        own_votes.push(vote.clone());
+                    }
+                }
+            }
+            actions.push(Action::Broadcast { message, delay, to });
+        }
+        actions.push(Action::CloseRoundAfter {
+            view: proposal.view,
+            after: VOTE_WINDOW,
+        });
+        self.round = Some(Round {
+            proposal,
+            votes: own_votes,
+        });
+    }
+
+    // Ends the vote window of `view` and decides its block from the votes counted.
+    pub fn close_round(&mut self, view: u64) -> Vec<Action> {
+        let Some(round) = self.round.take_if(|round| round.proposal.view == view) else {
+            return Vec::new();
+        };
+        let Round { proposal, mut votes } = round;
+
+        // Drop votes of non-validators and of validators caught equivocating, and
+        // count everyone else once.
+        let mut voters = HashSet::new();
+        votes.retain(|vote| {
+            self.chain.validators().is_member(&vote.public_key)
+                && !self.evidence.is_excluded(&vote.public_key)
+                && voters.insert(vote.public_key.clone())
+        });
+        let qc = QuorumCertificate { votes };
+        let (attack, retreat) = qc.tally();
+        let block = Block {
+            view: proposal.view,
+            height: proposal.height,
+            parent: proposal.parent,
+            value: qc.decision(),
+        };
+        // Without a quorum nothing is decided.
+        match self.chain.apply(CertifiedBlock {
+            block: block.clone(),
+            qc,
+        }) {
+            Ok(()) => vec![
+                Action::Decided {
+                    view: block.view,
+                    height: block.height,
+                    attack,
+                    retreat,
+                    value: block.value,
+                },
+                Action::Applied {
+                    block,
+                    synced: false,
+                },
+            ],
+            Err(error) => vec![Action::Rejected {
+                height: block.height,
+                error,
+            }],
+        }
+    }
+
+    // Asks every newly connected peer how far it got, so a node that joins late
+    // learns the decisions it missed.
+    pub fn on_peer_connected(&mut self, peer: PeerId) -> Vec<Action> {
+        vec![Action::Sync {
+            peer,
+            request: SyncRequest::Status,
+        }]
+    }
+
+    // Answers a peer's sync request, unless the strategy keeps quiet.
+    pub fn on_sync_request(&self, request: SyncRequest) -> Option<SyncResponse> {
+        if !self.strategy.serves_sync() {
+            return None;
+        }
+        Some(sync::respond(&self.chain, request))
+    }
+
+    pub fn on_sync_response(&mut self, peer: PeerId, response: SyncResponse) -> Vec<Action> {
+        let mut actions = Vec::new();
+        match response {
+            SyncResponse::Status {
+                height,
+                base_height,
+            } => {
+                if height > self.chain.height() && self.sync_target.is_none() {
+                    self.sync_target = Some((peer, height, base_height));
+                    self.request_next_blocks(&mut actions);
+                }
+            }
+            SyncResponse::Blocks(blocks) => {
+                if blocks.is_empty() {
+                    // The peer pruned what we asked for; jump to its tip instead.
+                    actions.push(Action::Sync {
+                        peer,
+                        request: SyncRequest::Snapshot,
+                    });
+                    return actions;
+                }
+                for certified in blocks {
+                    let block = certified.block.clone();
+                    if let Err(error) = self.chain.apply(certified) {
+                        actions.push(Action::Rejected {
+                            height: block.height,
+                            error,
+                        });
+                        self.sync_target = None;
+                        return actions;
+                    }
+                    actions.push(Action::Applied {
+                        block,
+                        synced: true,
+                    });
+                }
+                self.request_next_blocks(&mut actions);
+            }
+            SyncResponse::Snapshot(Some(snapshot)) => {
+                let block = snapshot.tip.block.clone();
+                if let Err(error) = self.chain.restore(snapshot) {
+                    actions.push(Action::Rejected {
+                        height: block.height,
+                        error,
+                    });
+                    self.sync_target = None;
+                    return actions;
+                }
+                actions.push(Action::Restored(block));
+                self.request_next_blocks(&mut actions);
+            }
+            SyncResponse::Snapshot(None) => {
+                self.sync_target = None;
+            }
+        }
+        actions
+    }
+
+    // A sync request to `peer` failed; stop syncing from it.
+    pub fn on_sync_failure(&mut self, peer: PeerId) {
+        if matches!(self.sync_target, Some((target, _, _)) if target == peer) {
+            self.sync_target = None;
+        }
+    }
+
+    fn request_next_blocks(&mut self, actions: &mut Vec<Action>) {
+        let Some((peer, height, base_height)) = self.sync_target else {
+            return;
+        };
+        match sync::next_request(self.chain.height(), height, base_height) {
+            Some(request) => actions.push(Action::Sync { peer, request }),
+            None => {
+                actions.push(Action::CaughtUp {
+                    peer,
+                    height: self.chain.height(),
+                });
+                self.sync_target = None;
+            }
+        }
+    }
+}
+
+// Locally detected equivocation is both reported and gossiped as proof.
+fn report(actions: &mut Vec<Action>, evidence: Evidence) {
+    actions.push(Action::Equivocation(evidence.clone()));
+    actions.push(Action::Broadcast {
+        message: Message::Evidence(evidence),
+        delay: Duration::ZERO,
+        to: Recipients::All,
+    });
+}
+
+// Whether a vote is validly signed and answers this proposal.
+fn counts_for(vote: &Signed<Vote>, proposal: &Proposal) -> bool {
+    vote.body.height == proposal.height
+        && vote.body.view == proposal.view
+        && vote.body.parent == proposal.parent
+        && vote.verify()
+}
@@ -0,0 +1,267 @@
+use crate::protocol::Decision;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
+
+// peer-server marks a node stale after five seconds without a heartbeat.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+// How to reach peer-server: its URL and a client that presents the node's
+// token and trusts the certificate authority peer-server's certificate is
+// signed with, if it is not a public one.
+#[derive(Clone)]
+pub struct Server {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl Server {
+    pub fn new(url: &str, token: Option<&str>, ca: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let _ = rustls::crypto::ring::default_provider().install_default();
+        let mut headers = HeaderMap::new();
+        if let Some(token) = token {
+            let mut value = HeaderValue::from_str(&format!("Bearer {token}"))?;
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
+        }
+        let mut client = reqwest::Client::builder().default_headers(headers);
+        if let Some(ca) = ca {
+            let pem = std::fs::read(ca).map_err(|e| format!("could not read {}: {e}", ca.display()))?;
+            client = client.tls_certs_merge(reqwest::Certificate::from_pem_bundle(&pem)?);
+        }
+        Ok(Server {
+            url: url.trim_end_matches('/').to_string(),
+            client: client.build()?,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+}
+
+// Something peer-server injects into this node for `duration_ms`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Fault {
+    // Drop each outgoing message with probability `rate`.
+    Drop { rate: f64, duration_ms: u64 },
+    // Hold every outgoing message back for `delay_ms`.
+    Delay { delay_ms: u64, duration_ms: u64 },
+    // Neither send nor handle any message.
+    Crash { duration_ms: u64 },
+}
+
+impl Fault {
+    fn duration(&self) -> Duration {
+        match self {
+            Fault::Drop { duration_ms, .. } | Fault::Delay { duration_ms, .. } | Fault::Crash { duration_ms } => {
+                Duration::from_millis(*duration_ms)
+            }
+        }
+    }
+}
+
+// What peer-server asks this node to do.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    StartRound { value: Decision },
+    StopRound,
+    SetRole { role: String },
+    InjectFault { fault: Fault },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Queued {
+    seq: u64,
+    command: Command,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Registration {
+    pub peer_id: String,
+    pub role: String,
+    pub version: String,
+    pub addresses: Vec<String>,
+}
+
+// Sequence number of the latest command peer-server queued for the node.
+#[derive(Debug, Clone, Deserialize)]
+struct Registered {
+    last_seq: u64,
+}
+
+// What the node reports with every heartbeat.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Status {
+    // The behaviour the node runs now.
+    pub role: String,
+    pub addresses: Vec<String>,
+    pub view: u64,
+    pub height: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Heartbeat<'a> {
+    // The last command received, so peer-server only answers with newer ones.
+    after: u64,
+    #[serde(flatten)]
+    status: &'a Status,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Commands {
+    commands: Vec<Queued>,
+    // peer-server is shutting down.
+    #[serde(default)]
+    stopping: bool,
+}
+
+// Faults in effect and until when.
+#[derive(Default)]
+pub struct Faults {
+    active: Vec<(Fault, Instant)>,
+}
+
+impl Faults {
+    pub fn inject(&mut self, fault: Fault, now: Instant) {
+        let until = now + fault.duration();
+        self.active.push((fault, until));
+    }
+
+    pub fn crashed(&mut self, now: Instant) -> bool {
+        self.expire(now);
+        self.active.iter().any(|(fault, _)| matches!(fault, Fault::Crash { .. }))
+    }
+
+    // How long to hold back a message about to be sent, or None to drop it.
+    // `roll` is uniform in [0, 1) and decides drops.
+    pub fn outgoing(&mut self, now: Instant, roll: f64) -> Option<Duration> {
+        self.expire(now);
+        let mut delay = Duration::ZERO;
+        for (fault, _) in &self.active {
+            match fault {
+                Fault::Crash { .. } => return None,
+                Fault::Drop { rate, .. } if roll < *rate => return None,
+                Fault::Drop { .. } => {}
+                Fault::Delay { delay_ms, .. } => delay = delay.max(Duration::from_millis(*delay_ms)),
+            }
+        }
+        Some(delay)
+    }
+
+    fn expire(&mut self, now: Instant) {
+        self.active.retain(|(_, until)| *until > now);
+    }
+}
+
+// Registers with peer-server, then sends it the node's `status` every second
+// and hands the commands it answers with to `commands`, in order and once
+// each, for as long as the node runs. Commands queued before the first
+// registration were meant for an earlier run of the node and are skipped.
+// Registers again when peer-server no longer knows the node, e.g. after a
+// restart, or after it said it stops.
+pub async fn run(
+    server: Server,
+    mut registration: Registration,
+    status: watch::Receiver<Status>,
+    commands: mpsc::UnboundedSender<Command>,
+) {
+    let client = server.client();
+    let nodes = format!("{}/api/nodes", server.url());
+    let heartbeat = format!("{}/{}/heartbeat", nodes, registration.peer_id);
+    let mut registered = false;
+    let mut first = true;
+    let mut failing = false;
+    let mut after = 0;
+    let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        interval.tick().await;
+        if !registered {
+            registration.addresses = status.borrow().addresses.clone();
+            match register(client, &nodes, &registration).await {
+                Ok(last_seq) => {
+                    println!("Registered with peer-server at {}", server.url());
+                    after = if first {
+                        last_seq
+                    } else {
+                        // A restarted peer-server numbers commands from the start again.
+                        after.min(last_seq)
+                    };
+                    first = false;
+                    registered = true;
+                    failing = false;
+                }
+                Err(e) if !failing => {
+                    println!("Could not register with peer-server: {e}");
+                    failing = true;
+                }
+                Err(_) => {}
+            }
+            continue;
+        }
+        let body = serde_json::to_vec(&Heartbeat {
+            after,
+            status: &status.borrow(),
+        })
+        .expect("heartbeats always serialize");
+        let response = client
+            .put(&heartbeat)
+            .header("content-type", "application/json")
+            .body(body)
+            .send()
+            .await;
+        match response {
+            Ok(response) if response.status() == reqwest::StatusCode::NOT_FOUND => registered = false,
+            Ok(response) => match response.error_for_status() {
+                Ok(response) => {
+                    failing = false;
+                    let Ok(answer) = response.json::<Commands>().await else {
+                        continue;
+                    };
+                    for queued in answer.commands {
+                        after = queued.seq;
+                        let _ = commands.send(queued.command);
+                    }
+                    if answer.stopping {
+                        // Keep quiet until it is back, then register again.
+                        println!("peer-server is shutting down, registering again once it is back");
+                        registered = false;
+                        failing = true;
+                    }
+                }
+                Err(e) if !failing => {
+                    println!("Heartbeat to peer-server failed: {e}");
+                    failing = true;
+                }
+                Err(_) => {}
+            },
+            Err(e) if !failing => {
+                println!("Heartbeat to peer-server failed: {e}");
+                failing = true;
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+async fn register(client: &reqwest::Client, nodes: &str, registration: &Registration) -> Result<u64, reqwest::Error> {
+    let registered: Registered = client
+        .post(nodes)
+        .json(registration)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(registered.last_seq)
+}
@@ -0,0 +1,21 @@
+use crate::protocol::Message;
+use libp2p::request_response::{self, ProtocolSupport};
+use libp2p::StreamProtocol;
+use std::time::Duration;
+
+// Consensus messages sent straight to one peer instead of gossiped, for
+// strategies that tell different peers different things. The receiver checks
+// them like gossip but does not forward them, and answers with nothing.
+const PROTOCOL: StreamProtocol = StreamProtocol::new("/fever/direct/1");
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub type Behaviour = request_response::json::Behaviour<Message, ()>;
+pub type Event = request_response::Event<Message, ()>;
+
+pub fn behaviour() -> Behaviour {
+    request_response::json::Behaviour::new(
+        [(PROTOCOL, ProtocolSupport::Full)],
+        request_response::Config::default().with_request_timeout(REQUEST_TIMEOUT),
+    )
+}
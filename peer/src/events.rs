@@ -0,0 +1,251 @@
+use crate::consensus::Action;
+use crate::protocol::{BlockHash, Decision, Message};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::Level;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+// The tracing target of node events. A subscriber that formats this target as
+// flattened JSON without time, level or target writes exactly `LogEntry` lines.
+pub const TARGET: &str = "fever::events";
+
+// What a node did that matters for checking the protocol's guarantees. One
+// JSON object per line, e.g.
+// {"at_ms":12034,"node":"node-2","event":"committed","view":2,"height":2,...}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum NodeEvent {
+    Started {
+        strategy: String,
+    },
+    // The node switched its behaviour while running.
+    RoleChanged {
+        from: String,
+        to: String,
+    },
+    ViewEntered {
+        view: u64,
+    },
+    // A view certificate moved the view clock forward, in milliseconds.
+    ClockBumped {
+        from: u64,
+        to: u64,
+    },
+    // The node's clock was synchronised over NTP, in microseconds.
+    ClockSync {
+        offset_us: i64,
+        delay_us: u64,
+    },
+    // The node led `view` and proposed `value` for `height`.
+    Proposed {
+        view: u64,
+        height: u64,
+        value: Decision,
+    },
+    VoteSent {
+        view: u64,
+        height: u64,
+        value: Decision,
+    },
+    VoteReceived {
+        view: u64,
+        height: u64,
+        value: Decision,
+        from: String,
+    },
+    // The vote window of `view` ended.
+    Timeout {
+        view: u64,
+    },
+    // The votes counted in a round decided its block.
+    QcFormed {
+        view: u64,
+        height: u64,
+        value: Decision,
+        attack: u32,
+        retreat: u32,
+    },
+    // A block became part of the node's chain, decided locally or synced.
+    Committed {
+        view: u64,
+        height: u64,
+        hash: String,
+        value: Decision,
+        synced: bool,
+    },
+}
+
+impl NodeEvent {
+    pub fn from_action(action: &Action) -> Option<Self> {
+        match action {
+            Action::ViewEntered { view } => Some(NodeEvent::ViewEntered { view: *view }),
+            Action::ClockBumped { from, to } => Some(NodeEvent::ClockBumped { from: *from, to: *to }),
+            Action::Broadcast {
+                message: Message::Proposal(proposal),
+                ..
+            } => Some(NodeEvent::Proposed {
+                view: proposal.body.view,
+                height: proposal.body.height,
+                value: proposal.body.value,
+            }),
+            Action::Broadcast {
+                message: Message::Vote(vote),
+                ..
+            } => Some(NodeEvent::VoteSent {
+                view: vote.body.view,
+                height: vote.body.height,
+                value: vote.body.value,
+            }),
+            Action::Decided {
+                view,
+                height,
+                attack,
+                retreat,
+                value,
+            } => Some(NodeEvent::QcFormed {
+                view: *view,
+                height: *height,
+                value: *value,
+                attack: *attack,
+                retreat: *retreat,
+            }),
+            Action::Applied { block, synced } => Some(NodeEvent::Committed {
+                view: block.view,
+                height: block.height,
+                hash: hex(&block.hash()),
+                value: block.value,
+                synced: *synced,
+            }),
+            Action::Restored(block) => Some(NodeEvent::Committed {
+                view: block.view,
+                height: block.height,
+                hash: hex(&block.hash()),
+                value: block.value,
+                synced: true,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub at_ms: u64,
+    pub node: String,
+    #[serde(flatten)]
+    pub event: NodeEvent,
+}
+
+pub fn hex(hash: &BlockHash) -> String {
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Writes exactly `LogEntry` lines to `writer`: events of `TARGET` as flattened
+// JSON without time, level or target.
+pub fn layer<S, W>(writer: W) -> impl Layer<S>
+where
+    S: tracing::Subscriber + for<'span> LookupSpan<'span>,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    tracing_subscriber::fmt::layer()
+        .json()
+        .flatten_event(true)
+        .with_current_span(false)
+        .with_span_list(false)
+        .with_target(false)
+        .with_level(false)
+        .without_time()
+        .with_writer(writer)
+        .with_filter(Targets::new().with_target(TARGET, Level::INFO))
+}
+
+static NODE: OnceLock<String> = OnceLock::new();
+
+// Names the node in every event recorded from now on. Only the first call counts.
+pub fn set_node(node: String) {
+    let _ = NODE.set(node);
+}
+
+// Emits `event` as a tracing event of `TARGET`, stamped with the wall clock so
+// the logs of different nodes merge in order.
+pub fn record(event: &NodeEvent) {
+    let at_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64);
+    let node = NODE.get().map_or("unknown", String::as_str);
+    match event {
+        NodeEvent::Started { strategy } => {
+            tracing::info!(target: TARGET, at_ms, node, event = "started", strategy = strategy.as_str())
+        }
+        NodeEvent::RoleChanged { from, to } => tracing::info!(
+            target: TARGET,
+            at_ms,
+            node,
+            event = "role_changed",
+            from = from.as_str(),
+            to = to.as_str()
+        ),
+        NodeEvent::ViewEntered { view } => tracing::info!(target: TARGET, at_ms, node, event = "view_entered", view),
+        NodeEvent::ClockBumped { from, to } => {
+            tracing::info!(target: TARGET, at_ms, node, event = "clock_bumped", from, to)
+        }
+        NodeEvent::ClockSync { offset_us, delay_us } => {
+            tracing::info!(target: TARGET, at_ms, node, event = "clock_sync", offset_us, delay_us)
+        }
+        NodeEvent::Proposed { view, height, value } => {
+            tracing::info!(target: TARGET, at_ms, node, event = "proposed", view, height, value = %value)
+        }
+        NodeEvent::VoteSent { view, height, value } => {
+            tracing::info!(target: TARGET, at_ms, node, event = "vote_sent", view, height, value = %value)
+        }
+        NodeEvent::VoteReceived { view, height, value, from } => tracing::info!(
+            target: TARGET,
+            at_ms,
+            node,
+            event = "vote_received",
+            view,
+            height,
+            value = %value,
+            from = from.as_str()
+        ),
+        NodeEvent::Timeout { view } => tracing::info!(target: TARGET, at_ms, node, event = "timeout", view),
+        NodeEvent::QcFormed {
+            view,
+            height,
+            value,
+            attack,
+            retreat,
+        } => tracing::info!(
+            target: TARGET,
+            at_ms,
+            node,
+            event = "qc_formed",
+            view,
+            height,
+            value = %value,
+            attack,
+            retreat
+        ),
+        NodeEvent::Committed {
+            view,
+            height,
+            hash,
+            value,
+            synced,
+        } => tracing::info!(
+            target: TARGET,
+            at_ms,
+            node,
+            event = "committed",
+            view,
+            height,
+            hash = hash.as_str(),
+            value = %value,
+            synced
+        ),
+    }
+}
@@ -0,0 +1,182 @@
+use crate::protocol::{Proposal, Signable, Signed, Vote};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// How many views of signed messages, behind and ahead of ours, are remembered
+// for conflict checks.
+const RETAINED_VIEWS: u64 = 64;
+
+// Bounds on what the pool holds, whatever peers send: signed messages of each
+// kind remembered, and offenders with their evidence.
+const MAX_RECORDED: usize = 4096;
+const MAX_OFFENDERS: usize = 1024;
+
+// Proof that a validator signed two conflicting messages for the same view and
+// height. Anyone can check it without trusting the node that reported it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Evidence {
+    ConflictingVotes(Signed<Vote>, Signed<Vote>),
+    ConflictingProposals(Signed<Proposal>, Signed<Proposal>),
+}
+
+impl Evidence {
+    pub fn view(&self) -> u64 {
+        match self {
+            Evidence::ConflictingVotes(first, _) => first.body.view,
+            Evidence::ConflictingProposals(first, _) => first.body.view,
+        }
+    }
+
+    // The key that signed both messages.
+    pub fn public_key(&self) -> &[u8] {
+        match self {
+            Evidence::ConflictingVotes(first, _) => &first.public_key,
+            Evidence::ConflictingProposals(first, _) => &first.public_key,
+        }
+    }
+
+    pub fn offender(&self) -> Option<PeerId> {
+        match self {
+            Evidence::ConflictingVotes(first, _) => first.signer(),
+            Evidence::ConflictingProposals(first, _) => first.signer(),
+        }
+    }
+
+    pub fn verify(&self) -> bool {
+        match self {
+            Evidence::ConflictingVotes(first, second) => {
+                conflicting(first, second, |vote| (vote.view, vote.height))
+            }
+            Evidence::ConflictingProposals(first, second) => {
+                conflicting(first, second, |proposal| (proposal.view, proposal.height))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Evidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let offender = self
+            .offender()
+            .map(|peer| peer.to_string())
+            .unwrap_or_else(|| "<invalid key>".to_string());
+        match self {
+            Evidence::ConflictingVotes(first, second) => write!(
+                f,
+                "{} voted both {} and {} for height {} in view {}",
+                offender, first.body.value, second.body.value, first.body.height, first.body.view
+            ),
+            Evidence::ConflictingProposals(first, second) => write!(
+                f,
+                "{} proposed both {} and {} for height {} in view {}",
+                offender, first.body.value, second.body.value, first.body.height, first.body.view
+            ),
+        }
+    }
+}
+
+// Same signer, same slot, different content, both signatures valid.
+fn conflicting<T, K>(first: &Signed<T>, second: &Signed<T>, slot: impl Fn(&T) -> K) -> bool
+where
+    T: Signable + PartialEq,
+    K: PartialEq,
+{
+    first.public_key == second.public_key
+        && slot(&first.body) == slot(&second.body)
+        && first.body != second.body
+        && first.verify()
+        && second.verify()
+}
+
+// Remembers the first signed vote and proposal of every validator per view,
+// for views near our own, and collects evidence when a second, different one
+// shows up.
+#[derive(Default)]
+pub struct EvidencePool {
+    votes: HashMap<(Vec<u8>, u64, u64), Signed<Vote>>,
+    proposals: HashMap<(Vec<u8>, u64, u64), Signed<Proposal>>,
+    evidence: Vec<Evidence>,
+    offenders: HashSet<Vec<u8>>,
+    // Our view when messages were last pruned.
+    pruned_at: u64,
+}
+
+impl EvidencePool {
+    // Records a validly signed vote, given our `current_view`. Returns new
+    // evidence if it conflicts with an earlier vote by the same validator.
+    pub fn check_vote(&mut self, vote: &Signed<Vote>, current_view: u64) -> Option<Evidence> {
+        let key = (vote.public_key.clone(), vote.body.view, vote.body.height);
+        self.prune(current_view);
+        match self.votes.get(&key) {
+            Some(first) if first.body != vote.body => {
+                let evidence = Evidence::ConflictingVotes(first.clone(), vote.clone());
+                self.add(evidence.clone()).then_some(evidence)
+            }
+            Some(_) => None,
+            None => {
+                if retained(vote.body.view, current_view) && self.votes.len() < MAX_RECORDED {
+                    self.votes.insert(key, vote.clone());
+                }
+                None
+            }
+        }
+    }
+
+    // Same as `check_vote`, for a leader's proposals.
+    pub fn check_proposal(&mut self, proposal: &Signed<Proposal>, current_view: u64) -> Option<Evidence> {
+        let key = (proposal.public_key.clone(), proposal.body.view, proposal.body.height);
+        self.prune(current_view);
+        match self.proposals.get(&key) {
+            Some(first) if first.body != proposal.body => {
+                let evidence = Evidence::ConflictingProposals(first.clone(), proposal.clone());
+                self.add(evidence.clone()).then_some(evidence)
+            }
+            Some(_) => None,
+            None => {
+                if retained(proposal.body.view, current_view) && self.proposals.len() < MAX_RECORDED {
+                    self.proposals.insert(key, proposal.clone());
+                }
+                None
+            }
+        }
+    }
+
+    // Adds verified evidence, e.g. received from another peer. Returns false if it
+    // is invalid, the offender is already known or the pool is full.
+    pub fn add(&mut self, evidence: Evidence) -> bool {
+        if self.offenders.len() >= MAX_OFFENDERS || !evidence.verify() {
+            return false;
+        }
+        if !self.offenders.insert(evidence.public_key().to_vec()) {
+            return false;
+        }
+        self.evidence.push(evidence);
+        true
+    }
+
+    // Whether messages signed with this key should be ignored.
+    pub fn is_excluded(&self, public_key: &[u8]) -> bool {
+        self.offenders.contains(public_key)
+    }
+
+    pub fn evidence(&self) -> &[Evidence] {
+        &self.evidence
+    }
+
+    // Forgets messages of views that fell out of the window around `current_view`.
+    fn prune(&mut self, current_view: u64) {
+        if current_view <= self.pruned_at {
+            return;
+        }
+        self.pruned_at = current_view;
+        self.votes.retain(|(_, view, _), _| retained(*view, current_view));
+        self.proposals.retain(|(_, view, _), _| retained(*view, current_view));
+    }
+}
+
+// Whether messages of `view` are remembered while we are in `current_view`.
+fn retained(view: u64, current_view: u64) -> bool {
+    view.saturating_add(RETAINED_VIEWS) >= current_view && view <= current_view.saturating_add(RETAINED_VIEWS)
+}
@@ -0,0 +1,28 @@
+use libp2p::identity::Keypair;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+// The node's identity key from `path`, in libp2p's protobuf encoding. A new
+// ed25519 key is generated and written there if the file does not exist, so
+// the node keeps its peer id across restarts.
+pub fn load_or_generate(path: &Path) -> Result<Keypair, Box<dyn Error>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Keypair::from_protobuf_encoding(&bytes)
+            .map_err(|e| format!("invalid key in {}: {e}", path.display()))?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let keypair = Keypair::generate_ed25519();
+            // Only the owner may read the key.
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(path)?
+                .write_all(&keypair.to_protobuf_encoding()?)?;
+            Ok(keypair)
+        }
+        Err(e) => Err(format!("could not read {}: {e}", path.display()).into()),
+    }
+}
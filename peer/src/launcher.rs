@@ -0,0 +1,277 @@
+use crate::keys;
+use crate::validators;
+use libp2p::Multiaddr;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+
+// How often the launcher checks that every node still runs.
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+// How long nodes get to exit after SIGTERM before they are killed.
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+// A cluster of peer processes on this machine.
+#[derive(Debug, Clone)]
+pub struct Spec {
+    pub nodes: usize,
+    // Keys, logs and event files of the nodes, kept between runs.
+    pub dir: PathBuf,
+    // Node i listens on 127.0.0.1 at `base_port + i`, over TCP.
+    pub base_port: u16,
+    // Node i serves metrics at `metrics_base_port + i`, if set.
+    pub metrics_base_port: Option<u16>,
+    // Strategies of nodes by index. The others run "honest".
+    pub roles: BTreeMap<usize, String>,
+    pub faults: usize,
+    pub server: Option<String>,
+    // The peer executable.
+    pub peer: PathBuf,
+    // Further options for every peer.
+    pub peer_args: Vec<String>,
+}
+
+// One node of the cluster, as listed in <dir>/cluster.json.
+#[derive(Debug, Clone, Serialize)]
+pub struct Node {
+    pub index: usize,
+    pub peer_id: String,
+    pub role: String,
+    pub listen: Multiaddr,
+    // `listen` with the peer id, which the other nodes dial.
+    pub address: Multiaddr,
+    pub metrics: Option<SocketAddr>,
+    pub key: PathBuf,
+    pub events: PathBuf,
+    pub log: PathBuf,
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    faults: usize,
+    nodes: &'a [Node],
+}
+
+// Lays the cluster out in `spec.dir`: a directory per node with its identity
+// key, generated on the first run and reused afterwards, and cluster.json
+// listing every node, which the nodes read as their validator set.
+pub fn prepare(spec: &Spec) -> Result<Vec<Node>, Box<dyn Error>> {
+    if spec.nodes == 0 {
+        return Err("a cluster needs at least one node".into());
+    }
+    if spec.faults > validators::max_faults(spec.nodes) {
+        return Err(format!(
+            "{} nodes tolerate at most {} faults, not {}",
+            spec.nodes,
+            validators::max_faults(spec.nodes),
+            spec.faults
+        )
+        .into());
+    }
+    if let Some(index) = spec.roles.keys().find(|index| **index >= spec.nodes) {
+        return Err(format!("there is no node {index} in a cluster of {}", spec.nodes).into());
+    }
+    let mut nodes = Vec::with_capacity(spec.nodes);
+    for index in 0..spec.nodes {
+        let dir = spec.dir.join(format!("node-{index}"));
+        fs::create_dir_all(&dir)?;
+        let key = dir.join("key");
+        let peer_id = keys::load_or_generate(&key)?.public().to_peer_id();
+        let listen: Multiaddr = format!("/ip4/127.0.0.1/tcp/{}", port(spec.base_port, index)?).parse()?;
+        let address: Multiaddr = format!("{listen}/p2p/{peer_id}").parse()?;
+        let metrics = match spec.metrics_base_port {
+            Some(base) => Some(SocketAddr::from(([127, 0, 0, 1], port(base, index)?))),
+            None => None,
+        };
+        nodes.push(Node {
+            index,
+            peer_id: peer_id.to_string(),
+            role: spec.roles.get(&index).cloned().unwrap_or_else(|| "honest".to_string()),
+            listen,
+            address,
+            metrics,
+            key,
+            events: dir.join("events.jsonl"),
+            log: dir.join("peer.log"),
+        });
+    }
+    let manifest = Manifest {
+        faults: spec.faults,
+        nodes: &nodes,
+    };
+    fs::write(manifest_path(spec), serde_json::to_vec_pretty(&manifest)?)?;
+    Ok(nodes)
+}
+
+pub fn manifest_path(spec: &Spec) -> PathBuf {
+    spec.dir.join("cluster.json")
+}
+
+fn port(base: u16, index: usize) -> Result<u16, Box<dyn Error>> {
+    u16::try_from(index)
+        .ok()
+        .and_then(|index| base.checked_add(index))
+        .ok_or_else(|| format!("port {base} + {index} is out of range").into())
+}
+
+// The peer options of `node`: its key, address and files, the validator set,
+// and every other node to connect to.
+fn args(spec: &Spec, node: &Node, nodes: &[Node]) -> Vec<String> {
+    let mut args = vec![
+        "--key".to_string(),
+        node.key.display().to_string(),
+        "--listen".to_string(),
+        node.listen.to_string(),
+        "--events".to_string(),
+        node.events.display().to_string(),
+        "--byzantine".to_string(),
+        node.role.clone(),
+        "--validators".to_string(),
+        manifest_path(spec).display().to_string(),
+        "--faults".to_string(),
+        spec.faults.to_string(),
+    ];
+    let bootstrap: Vec<String> = nodes
+        .iter()
+        .filter(|other| other.index != node.index)
+        .map(|other| other.address.to_string())
+        .collect();
+    if !bootstrap.is_empty() {
+        args.push("--bootstrap".to_string());
+        args.push(bootstrap.join(","));
+    }
+    if let Some(metrics) = node.metrics {
+        args.push("--metrics-addr".to_string());
+        args.push(metrics.to_string());
+    }
+    if let Some(server) = &spec.server {
+        args.push("--server".to_string());
+        args.push(server.clone());
+    }
+    args.extend(spec.peer_args.iter().cloned());
+    args
+}
+
+struct Running {
+    index: usize,
+    log: PathBuf,
+    child: Child,
+    stdin: Option<ChildStdin>,
+}
+
+// Starts every node, then runs until `stop` resolves or a node exits, and
+// stops them all. Lines read from stdin go to node 0, or to node i when
+// prefixed with "i:", e.g. "2: START ATTACK".
+pub async fn run(spec: &Spec, nodes: &[Node], stop: impl Future<Output = ()>) -> Result<(), Box<dyn Error>> {
+    let mut running = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let log = File::create(&node.log)?;
+        // Nodes already started are killed when `running` is dropped on an error.
+        let mut child = Command::new(&spec.peer)
+            .args(args(spec, node, nodes))
+            .stdin(Stdio::piped())
+            .stdout(log.try_clone()?)
+            .stderr(log)
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("could not start {}: {e}", spec.peer.display()))?;
+        println!(
+            "Node {} ({}) is {} on {}, logging to {}",
+            node.index,
+            node.role,
+            node.peer_id,
+            node.listen,
+            node.log.display()
+        );
+        running.push(Running {
+            index: node.index,
+            log: node.log.clone(),
+            stdin: child.stdin.take(),
+            child,
+        });
+    }
+    println!("Cluster of {} nodes is running", running.len());
+
+    let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+    let mut reading = true;
+    let mut checks = tokio::time::interval(CHECK_INTERVAL);
+    tokio::pin!(stop);
+    let result = loop {
+        tokio::select! {
+            _ = &mut stop => break Ok(()),
+            line = stdin.next_line(), if reading => match line {
+                Ok(Some(line)) => forward(&mut running, &line).await,
+                _ => reading = false,
+            },
+            _ = checks.tick() => {
+                if let Some(exited) = exited(&mut running) {
+                    break Err(exited.into());
+                }
+            }
+        }
+    };
+
+    println!("Stopping {} nodes", running.len());
+    for node in &mut running {
+        node.stdin = None;
+        terminate(&node.child);
+    }
+    let stopped = tokio::time::timeout(STOP_TIMEOUT, async {
+        for node in &mut running {
+            let _ = node.child.wait().await;
+        }
+    })
+    .await;
+    if stopped.is_err() {
+        println!("Killing the nodes still running after {} s", STOP_TIMEOUT.as_secs());
+        for node in &mut running {
+            let _ = node.child.start_kill();
+        }
+        for node in &mut running {
+            let _ = node.child.wait().await;
+        }
+    }
+    result
+}
+
+// Asks a node to shut down with SIGTERM, unless it already exited and was
+// reaped, in which case its pid may belong to another process by now.
+fn terminate(child: &Child) {
+    if let Some(pid) = child.id() {
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGTERM);
+        }
+    }
+}
+
+async fn forward(running: &mut [Running], line: &str) {
+    let addressed = line
+        .split_once(':')
+        .and_then(|(index, rest)| Some((index.trim().parse::<usize>().ok()?, rest.trim())));
+    let (index, line) = addressed.unwrap_or((0, line.trim()));
+    let Some(node) = running.iter_mut().find(|node| node.index == index) else {
+        println!("There is no node {index}");
+        return;
+    };
+    if let Some(stdin) = &mut node.stdin {
+        if stdin.write_all(format!("{line}\n").as_bytes()).await.is_err() {
+            println!("Node {index} does not read its input any more");
+        }
+    }
+}
+
+// Why a node stopped, if one did.
+fn exited(running: &mut [Running]) -> Option<String> {
+    running.iter_mut().find_map(|node| match node.child.try_wait() {
+        Ok(Some(status)) => Some(format!("node {} exited with {status}, see {}", node.index, node.log.display())),
+        Ok(None) => None,
+        Err(e) => Some(format!("could not check on node {}: {e}", node.index)),
+    })
+}
@@ -0,0 +1,19 @@
+pub mod byzantine;
+pub mod chain;
+pub mod collector;
+pub mod compose;
+pub mod consensus;
+pub mod control;
+pub mod direct;
+pub mod events;
+pub mod evidence;
+pub mod keys;
+pub mod launcher;
+pub mod metrics;
+pub mod protocol;
+pub mod scoring;
+pub mod sync;
+pub mod telemetry;
+pub mod validation;
+pub mod validators;
+pub mod viewsync;
@@ -1,36 +1,139 @@
 use chrono::{NaiveDateTime, Timelike};
+use clap::Parser;
 use futures::stream::StreamExt;
 use libp2p::{
-    gossipsub, mdns, noise,
+    allow_block_list, gossipsub, identity, mdns, multiaddr::Protocol, noise, request_response,
     swarm::{NetworkBehaviour, SwarmEvent},
-    tcp, yamux,
+    tcp, yamux, PeerId,
 };
+use peer::collector;
+use peer::byzantine::Recipients;
+use peer::consensus::{Action, Core};
+use peer::control::{self, Command, Faults, Registration, Status};
+use peer::direct;
+use peer::events::{self, NodeEvent};
+use peer::evidence::Evidence;
+use peer::keys;
+use peer::metrics::{self, Metrics, NtpMetrics};
+use peer::protocol::{Decision, Message};
+use peer::scoring::{self, Offence, PeerScores};
+use peer::sync::{self, SyncResponse};
+use peer::telemetry::{self, RoundTraces};
+use peer::validators::Validators;
+use peer::viewsync::ViewSync;
+use prometheus_client::registry::Registry;
+use peer::{byzantine, validation};
 use rand::random;
-use regex::Regex;
 use std::error::Error;
+use std::fs::OpenOptions;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tokio::{io, io::AsyncBufReadExt, select, time::sleep};
-use tracing_subscriber::EnvFilter;
+use tokio::{io, io::AsyncBufReadExt, select, sync::{mpsc, watch}, time::{sleep, Instant}};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
 
 mod clocky;
+mod config;
 
-const BYZANTINE: u8 = 0; // Change this to 1 to enable Byzantine behavior
-
-// We create a custom network behaviour that combines Gossipsub and Mdns.
+// We create a custom network behaviour that combines Gossipsub, Mdns, block sync,
+// direct messages and the list of peers banned for misbehaviour.
 #[derive(NetworkBehaviour)]
 struct MyBehaviour {
     gossipsub: gossipsub::Behaviour,
     mdns: mdns::tokio::Behaviour,
+    sync: sync::Behaviour,
+    direct: direct::Behaviour,
+    blocked: allow_block_list::Behaviour<allow_block_list::BlockedPeers>,
+}
+
+// The consensus core plus the swarm-side state shared by the event handlers.
+struct Node {
+    core: Core,
+    scores: PeerScores,
+    ban_threshold: f64,
+    metrics: Metrics,
+    traces: RoundTraces,
+    // Faults peer-server injected.
+    faults: Faults,
+    // What the next heartbeat to peer-server reports.
+    status: watch::Sender<Status>,
+    // Delayed broadcasts and round timers are fed back into the event loop here.
+    outbox: mpsc::UnboundedSender<(Message, Recipients)>,
+    timers: mpsc::UnboundedSender<u64>,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let _ = tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .try_init();
+    let config = config::Config::parse();
+    let server = match &config.server {
+        Some(url) => Some(control::Server::new(url, config.server_token.as_deref(), config.server_ca.as_deref())?),
+        None => None,
+    };
+    init_tracing(&config, server.as_ref())?;
+
+    let strategy = byzantine::from_name(&config.byzantine, random())?;
+    println!("Running with {} behaviour", strategy.name());
+    let validators = match &config.validators {
+        Some(path) => Validators::load(path, config.faults)?,
+        None => {
+            println!("No --validators given: votes and view announcements of any key count");
+            Validators::open(config.faults.unwrap_or(1))
+        }
+    };
+    let (outbox, mut delayed) = mpsc::unbounded_channel();
+    let (timers, mut expired) = mpsc::unbounded_channel();
+    let (commands, mut commanded) = mpsc::unbounded_channel();
+    let (status, status_updates) = watch::channel(Status {
+        role: config.byzantine.clone(),
+        ..Status::default()
+    });
+
+    // Keep the identity key: it also signs proposals and votes.
+    let keypair = match &config.key {
+        Some(path) => keys::load_or_generate(path)?,
+        None => identity::Keypair::generate_ed25519(),
+    };
+    events::set_node(keypair.public().to_peer_id().to_string());
+    events::record(&NodeEvent::Started {
+        strategy: strategy.name().to_string(),
+    });
+    let _tracer_provider = telemetry::init(
+        &keypair.public().to_peer_id().to_string(),
+        config.otlp_endpoint.as_deref(),
+        config.trace_file.as_deref(),
+    )?;
+    let mut registry = Registry::with_prefix("fever");
+    let mut node = Node {
+        core: Core::new(
+            keypair.clone(),
+            strategy,
+            ViewSync::new(config.view_length, validators.faults() + 1),
+            validators,
+        ),
+        scores: PeerScores::new(config.spam_limit),
+        ban_threshold: config.ban_threshold,
+        metrics: Metrics::new(&mut registry),
+        traces: RoundTraces::default(),
+        faults: Faults::default(),
+        status,
+        outbox,
+        timers,
+    };
+    if let Some(server) = server {
+        let registration = Registration {
+            peer_id: node.core.peer_id().to_string(),
+            role: config.byzantine.clone(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            addresses: Vec::new(),
+        };
+        tokio::spawn(control::run(server, registration, status_updates, commands));
+    }
+
+    // Create a Gossipsub topic
+    let topic = gossipsub::IdentTopic::new("test-net");
 
-    let mut swarm = libp2p::SwarmBuilder::with_new_identity()
+    let mut swarm = libp2p::SwarmBuilder::with_existing_identity(keypair)
         .with_tokio()
         .with_tcp(
             tcp::Config::default(),
@@ -38,65 +141,140 @@ async fn main() -> Result<(), Box<dyn Error>> {
             yamux::Config::default,
         )?
         .with_quic()
+        // Resolves /dns4 and /dns6 addresses, e.g. of docker-compose services.
+        .with_dns()?
         .with_behaviour(|key| {
             // Set a custom gossipsub configuration
             let gossipsub_config = gossipsub::ConfigBuilder::default()
                 .heartbeat_interval(Duration::from_secs(10))
                 .validation_mode(gossipsub::ValidationMode::Strict)
+                // Only forward consensus messages once `screen` has accepted them.
+                .validate_messages()
                 .build()
                 .map_err(|msg| io::Error::new(io::ErrorKind::Other, msg))?; // Temporary hack because `build` does not return a proper `std::error::Error`.
 
             // Build a gossipsub network behaviour
-            let gossipsub = gossipsub::Behaviour::new(
+            let mut gossipsub = gossipsub::Behaviour::new_with_metrics(
                 gossipsub::MessageAuthenticity::Signed(key.clone()),
                 gossipsub_config,
+                registry.sub_registry_with_prefix("gossipsub"),
+                Default::default(),
+            )?;
+            gossipsub.with_peer_score(
+                scoring::score_params(topic.hash()),
+                config.score_thresholds(),
             )?;
 
             let mdns =
                 mdns::tokio::Behaviour::new(mdns::Config::default(), key.public().to_peer_id())?;
-            Ok(MyBehaviour { gossipsub, mdns })
+            Ok(MyBehaviour {
+                gossipsub,
+                mdns,
+                sync: sync::behaviour(),
+                direct: direct::behaviour(),
+                blocked: allow_block_list::Behaviour::default(),
+            })
         })?
         .with_swarm_config(|c| c.with_idle_connection_timeout(Duration::from_secs(60)))
         .build();
 
-    // Create a Gossipsub topic
-    let topic = gossipsub::IdentTopic::new("test-net");
     // Subscribe to our topic
     swarm.behaviour_mut().gossipsub.subscribe(&topic)?;
 
     // Read full lines from stdin
     let mut stdin = io::BufReader::new(io::stdin()).lines();
 
-    // Listen on all interfaces and whatever port the OS assigns
-    swarm.listen_on("/ip4/0.0.0.0/udp/0/quic-v1".parse()?)?;
-    swarm.listen_on("/ip4/0.0.0.0/tcp/0".parse()?)?;
-    
+    // Listen on all interfaces and whatever port the OS assigns, unless told where
+    if config.listen.is_empty() {
+        swarm.listen_on("/ip4/0.0.0.0/udp/0/quic-v1".parse()?)?;
+        swarm.listen_on("/ip4/0.0.0.0/tcp/0".parse()?)?;
+    }
+    for address in &config.listen {
+        swarm.listen_on(address.clone())?;
+    }
+
+    // Gossipsub keeps dialing explicit peers while they are unreachable, e.g.
+    // because they have not started yet.
+    for address in &config.bootstrap {
+        let Some(Protocol::P2p(peer_id)) = address.iter().last() else {
+            return Err(format!("bootstrap address {address} does not end with /p2p/<peer id>").into());
+        };
+        swarm.add_peer_address(peer_id, address.clone());
+        swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
+        if let Err(e) = swarm.dial(address.clone()) {
+            println!("Could not dial {address}: {e}");
+        }
+    }
+
+    if let Some(addr) = config.metrics_addr {
+        println!("Serving metrics on http://{addr}/metrics");
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(addr, registry).await {
+                println!("Metrics server error: {e}");
+            }
+        });
+    }
+
+    clocky::set_ntp_server(config.ntp_server.clone());
     tokio::spawn(async move {
         clocky::start_logical_clock().await;
     });
 
+    let ntp = node.metrics.ntp.clone();
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(Duration::from_secs(3600));
         loop {
             interval.tick().await;
-            clocky::synchronize_logical_clock();
+            synchronize_clock(&ntp);
         }
     });
 
 
-    println!("Enter messages via STDIN and they will be sent to connected peers using Gossipsub");
+    // Let penalties fade and ban peers whose score sank too low.
+    let mut scoring_interval = tokio::time::interval(Duration::from_secs(10));
+    // Drives the view clock; views start on multiples of the view length.
+    let started = Instant::now();
+    let mut clock_interval = tokio::time::interval(Duration::from_millis(100));
+
+    println!("Enter START ATTACK or START RETREAT to lead a round, EVIDENCE to list equivocation evidence or KLOCK to read the clock");
 
     // Kick it off
     loop {
         select! {
             Ok(Some(line)) = stdin.next_line() => {
-                if let Err(e) = swarm
-                    .behaviour_mut().gossipsub
-                    .publish(topic.clone(), line.as_bytes()) {
-                    println!("Publish error: {e:?}");
+                if let Some(value) = Decision::from_command(&line) {
+                    start_round(&mut swarm, &mut node, value).await;
+                } else if line.trim() == "EVIDENCE" {
+                    print_evidence(&node);
+                } else if line.trim() == "KLOCK" {
+                    synchronize_clock(&node.metrics.ntp);
+                    println!("Logical clock is at {}", clocky::current_logical_clock_time());
+                } else if !line.trim().is_empty() {
+                    // Only consensus messages go out; peers reject anything else.
+                    println!("Unknown command '{}', expected START ATTACK, START RETREAT, EVIDENCE or KLOCK", line.trim());
                 }
             }
-            event = swarm.select_next_some() => handle_event(event, &mut swarm).await,
+            Some(command) = commanded.recv() => handle_command(&mut swarm, &mut node, command).await,
+            Some((message, to)) = delayed.recv() => publish(&mut swarm, &node.traces.outgoing(message), to).await,
+            Some(view) = expired.recv() => {
+                events::record(&NodeEvent::Timeout { view });
+                node.metrics.on_timeout();
+                let actions = node.core.close_round(view);
+                perform(&mut swarm, &mut node, actions).await;
+            }
+            _ = scoring_interval.tick() => update_scores(&mut swarm, &mut node),
+            _ = clock_interval.tick() => {
+                let actions = node.core.tick(started.elapsed());
+                perform(&mut swarm, &mut node, actions).await;
+                let (view, height) = (node.core.view(), node.core.chain().height());
+                node.status.send_if_modified(|status| {
+                    let changed = (status.view, status.height) != (view, height);
+                    status.view = view;
+                    status.height = height;
+                    changed
+                });
+            }
+            event = swarm.select_next_some() => handle_event(event, &mut swarm, &mut node).await,
         }
     }
 }
@@ -104,6 +282,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 async fn handle_event(
     event: SwarmEvent<MyBehaviourEvent>,
     swarm: &mut libp2p::Swarm<MyBehaviour>,
+    node: &mut Node,
 ) {
     match event {
         SwarmEvent::Behaviour(MyBehaviourEvent::Mdns(mdns::Event::Discovered(list))) => {
@@ -118,86 +297,405 @@ async fn handle_event(
                 swarm.behaviour_mut().gossipsub.remove_explicit_peer(&peer_id);
             }
         }
+        SwarmEvent::ConnectionEstablished { peer_id, num_established, .. } => {
+            node.metrics.set_connected_peers(swarm.connected_peers().count());
+            if num_established.get() == 1 {
+                let actions = node.core.on_peer_connected(peer_id);
+                perform(swarm, node, actions).await;
+            }
+        }
+        SwarmEvent::ConnectionClosed { .. } => {
+            node.metrics.set_connected_peers(swarm.connected_peers().count());
+        }
         SwarmEvent::Behaviour(MyBehaviourEvent::Gossipsub(gossipsub::Event::Message {
             propagation_source: peer_id,
             message_id: id,
             message,
         })) => {
-        	clocky::synchronize_logical_clock();
+        	synchronize_clock(&node.metrics.ntp);
                let current_time = clocky::current_logical_clock_time();
-            let message_str = String::from_utf8_lossy(&message.data).trim().to_string();
-            println!("Got message: '{}' at '{}' with id: {} from peer: {}", message_str, current_time, id, peer_id);
-
-            if message_str == "START ATTACK" || message_str == "START RETREAT" {
-                if BYZANTINE == 0 {
-                    if message_str == "START ATTACK" {
-                        send_message(swarm, "ATTACK").await;
-                    } else if message_str == "START RETREAT" {
-                        send_message(swarm, "RETREAT").await;
-                    }
-                } else if BYZANTINE == 1 {
-                    let response = if random::<bool>() { "ATTACK" } else { "RETREAT" };
-                    send_message(swarm, response).await;
-                }
-
-                let (attack_count, retreat_count) = count_messages(swarm, 5000).await;
-                print_consensus(attack_count, retreat_count);
+            let Some(decoded) = screen(swarm, node, &id, &message, peer_id) else {
+                let message_str = String::from_utf8_lossy(&message.data).trim().to_string();
+                println!("Got message: '{}' at '{}' with id: {} from peer: {}", message_str, current_time, id, peer_id);
+                return;
+            };
+            if node.faults.crashed(Instant::now()) {
+                return;
+            }
+            match &decoded {
+                Message::Proposal(proposal) => println!(
+                    "Got proposal: '{}' for height {} in view {} at '{}' with id: {} from peer: {}",
+                    proposal.body.value, proposal.body.height, proposal.body.view, current_time, id, peer_id
+                ),
+                // Votes are recorded as `vote_received` events.
+                Message::Vote(_) => {}
+                Message::Evidence(_) => println!(
+                    "Got evidence at '{}' with id: {} from peer: {}",
+                    current_time, id, peer_id
+                ),
+                Message::View(view) => println!(
+                    "Got view {} announcement at '{}' with id: {} from peer: {}",
+                    view.body.view, current_time, id, peer_id
+                ),
+                Message::ViewCertificate(certificate) => println!(
+                    "Got view {} certificate at '{}' with id: {} from peer: {}",
+                    certificate.view, current_time, id, peer_id
+                ),
             }
+            deliver(swarm, node, peer_id, decoded).await;
         }
+        SwarmEvent::Behaviour(MyBehaviourEvent::Sync(event)) => handle_sync_event(event, swarm, node).await,
+        SwarmEvent::Behaviour(MyBehaviourEvent::Direct(event)) => handle_direct_event(event, swarm, node).await,
         SwarmEvent::NewListenAddr { address, .. } => {
             println!("Local node is listening on {address}");
+            node.status.send_modify(|status| status.addresses.push(address.to_string()));
+        }
+        SwarmEvent::ExpiredListenAddr { address, .. } => {
+            let address = address.to_string();
+            node.status.send_modify(|status| status.addresses.retain(|known| *known != address));
+        }
+        _ => {}
+    }
+}
+
+// Human-readable tracing on stderr, filtered by RUST_LOG, and the node's events
+// as JSON lines in the `--events` file and pushed to the `--server`.
+fn init_tracing(config: &config::Config, server: Option<&control::Server>) -> Result<(), Box<dyn Error>> {
+    let console = match config.log_format {
+        config::LogFormat::Text => tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .boxed(),
+        config::LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_writer(std::io::stderr)
+            .boxed(),
+    };
+    let event_log = match &config.events {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            Some(events::layer(Mutex::new(file)))
+        }
+        None => None,
+    };
+    let event_push = server.map(|server| {
+        let (writer, lines) = collector::Lines::new();
+        tokio::spawn(collector::push(server.clone(), lines));
+        events::layer(writer)
+    });
+    let _ = tracing_subscriber::registry()
+        .with(console.with_filter(EnvFilter::from_default_env()))
+        .with(event_log)
+        .with(event_push)
+        .try_init();
+    Ok(())
+}
+
+// Leads a round proposing `value`, asked for on stdin or by peer-server.
+async fn start_round(swarm: &mut libp2p::Swarm<MyBehaviour>, node: &mut Node, value: Decision) {
+    if node.core.in_round() {
+        println!("Still deciding the previous round");
+    } else if node.core.proposed_in_view() {
+        println!("Already proposed in view {}, wait for the next view", node.core.view());
+    } else {
+        let actions = node.core.propose(value);
+        perform(swarm, node, actions).await;
+    }
+}
+
+async fn handle_command(swarm: &mut libp2p::Swarm<MyBehaviour>, node: &mut Node, command: Command) {
+    println!("peer-server asked to {:?}", command);
+    match command {
+        Command::StartRound { value } => start_round(swarm, node, value).await,
+        Command::StopRound => match node.core.round_view() {
+            Some(view) => {
+                let actions = node.core.close_round(view);
+                perform(swarm, node, actions).await;
+            }
+            None => println!("No round in progress"),
+        },
+        Command::SetRole { role } => match byzantine::from_name(&role, random()) {
+            Ok(strategy) => {
+                events::record(&NodeEvent::RoleChanged {
+                    from: node.core.strategy_name().to_string(),
+                    to: strategy.name().to_string(),
+                });
+                node.core.set_strategy(strategy);
+                node.status.send_modify(|status| status.role = role);
+            }
+            Err(e) => println!("Keeping the {} behaviour: {}", node.core.strategy_name(), e),
+        },
+        Command::InjectFault { fault } => node.faults.inject(fault, Instant::now()),
+    }
+}
+
+// Carries out what the consensus core asked for.
+async fn perform(swarm: &mut libp2p::Swarm<MyBehaviour>, node: &mut Node, actions: Vec<Action>) {
+    for action in actions {
+        if let Some(event) = NodeEvent::from_action(&action) {
+            events::record(&event);
+        }
+        node.metrics.on_action(&action, &node.core);
+        match action {
+            Action::Broadcast { message, delay, to } => match node.faults.outgoing(Instant::now(), random()) {
+                None => println!("Dropped outgoing {} (injected fault)", message.kind()),
+                Some(held) if (delay + held).is_zero() => {
+                    publish(swarm, &node.traces.outgoing(message), to).await;
+                }
+                Some(held) => {
+                    let outbox = node.outbox.clone();
+                    tokio::spawn(async move {
+                        sleep(delay + held).await;
+                        let _ = outbox.send((message, to));
+                    });
+                }
+            },
+            Action::CloseRoundAfter { view, after } => {
+                let timers = node.timers.clone();
+                tokio::spawn(async move {
+                    sleep(after).await;
+                    let _ = timers.send(view);
+                });
+            }
+            Action::Sync { peer, request } => {
+                swarm.behaviour_mut().sync.send_request(&peer, request);
+            }
+            Action::Decided {
+                view,
+                height,
+                attack,
+                retreat,
+                value,
+            } => {
+                node.traces.decided(view, height, value, attack, retreat);
+                print_consensus(&node.metrics.ntp, attack, retreat);
+            }
+            // Recorded as events above.
+            Action::ViewEntered { .. } | Action::ClockBumped { .. } | Action::Applied { .. } | Action::Restored(_) => {}
+            Action::Rejected { height, error } => {
+                println!("Could not apply block at height {height}: {error}");
+            }
+            Action::CaughtUp { peer, height } => {
+                println!("Caught up with peer {peer} at height {height}");
+            }
+            Action::Equivocation(evidence) => {
+                println!("Equivocation detected: {}", evidence);
+                penalise_offender(swarm, node, &evidence);
+            }
+        }
+    }
+}
+
+async fn handle_sync_event(event: sync::Event, swarm: &mut libp2p::Swarm<MyBehaviour>, node: &mut Node) {
+    match event {
+        request_response::Event::Message {
+            peer,
+            message: request_response::Message::Request { request, channel, .. },
+        } => {
+            let Some(response) = node.core.on_sync_request(request) else {
+                return;
+            };
+            if swarm.behaviour_mut().sync.send_response(channel, response).is_err() {
+                println!("Sync response to peer {peer} was dropped");
+            }
+        }
+        request_response::Event::Message {
+            peer,
+            message: request_response::Message::Response { response, .. },
+        } => {
+            if let SyncResponse::Status { height, .. } = &response {
+                if *height > node.core.chain().height() {
+                    println!(
+                        "Peer {peer} is at height {height}, we are at {}",
+                        node.core.chain().height()
+                    );
+                }
+            }
+            let actions = node.core.on_sync_response(peer, response);
+            perform(swarm, node, actions).await;
+        }
+        request_response::Event::OutboundFailure { peer, error, .. } => {
+            println!("Sync request to peer {peer} failed: {error}");
+            node.metrics.on_sync_failure();
+            node.core.on_sync_failure(peer);
+        }
+        request_response::Event::InboundFailure { peer, error, .. } => {
+            println!("Sync request from peer {peer} failed: {error}");
+        }
+        request_response::Event::ResponseSent { .. } => {}
+    }
+}
+
+// Consensus messages a strategy sends to some peers only. They are checked like
+// gossip, without being forwarded.
+async fn handle_direct_event(event: direct::Event, swarm: &mut libp2p::Swarm<MyBehaviour>, node: &mut Node) {
+    match event {
+        request_response::Event::Message {
+            peer,
+            message: request_response::Message::Request { request, channel, .. },
+        } => {
+            let _ = swarm.behaviour_mut().direct.send_response(channel, ());
+            if node.scores.is_spamming(peer) {
+                penalise(swarm, node, peer, Offence::Spam);
+                return;
+            }
+            match validation::check(request, node.core.view()) {
+                Ok(message) if !node.faults.crashed(Instant::now()) => {
+                    println!("Got {} sent directly by peer: {}", message.kind(), peer);
+                    deliver(swarm, node, peer, message).await;
+                }
+                Ok(_) => {}
+                Err(rejection) => {
+                    println!("Dropping direct message with {} from peer: {}", rejection, peer);
+                    if let Some(offence) = rejection.offence() {
+                        penalise(swarm, node, peer, offence);
+                    }
+                }
+            }
+        }
+        request_response::Event::OutboundFailure { peer, error, .. } => {
+            println!("Direct message to peer {peer} failed: {error}");
         }
         _ => {}
     }
 }
 
+// Hands a checked consensus message from `from` to the consensus core.
+async fn deliver(swarm: &mut libp2p::Swarm<MyBehaviour>, node: &mut Node, from: PeerId, message: Message) {
+    if let Message::Vote(vote) = &message {
+        events::record(&NodeEvent::VoteReceived {
+            view: vote.body.view,
+            height: vote.body.height,
+            value: vote.body.value,
+            from: vote.signer().unwrap_or(from).to_string(),
+        });
+    }
+    node.metrics.on_message(&message, from);
+    node.traces.incoming(&message, from);
+    let actions = node.core.on_message(from, message);
+    perform(swarm, node, actions).await;
+}
+
+// Gossips `message`, or sends it straight to the connected peers among `to`.
+async fn publish(swarm: &mut libp2p::Swarm<MyBehaviour>, message: &Message, to: Recipients) {
+    if to == Recipients::All {
+        send_message(swarm, message).await;
+        return;
+    }
+    for peer in to.select(swarm.connected_peers().copied().collect()) {
+        swarm.behaviour_mut().direct.send_request(&peer, message.clone());
+    }
+}
 
 async fn send_message(
     swarm: &mut libp2p::Swarm<MyBehaviour>,
-    message: &str,
+    message: &Message,
 ) {
     let topic = gossipsub::IdentTopic::new("test-net");
     if let Err(e) = swarm
         .behaviour_mut()
         .gossipsub
-        .publish(topic, message.as_bytes())
+        .publish(topic, message.encode())
     {
         println!("Publish error: {e:?}");
     }
 }
 
-async fn count_messages(
+fn penalise_offender(swarm: &mut libp2p::Swarm<MyBehaviour>, node: &mut Node, evidence: &Evidence) {
+    if let Some(offender) = evidence.offender() {
+        penalise(swarm, node, offender, Offence::Equivocation);
+    }
+}
+
+// Validates a gossip message and tells gossipsub whether to forward it. Spam,
+// undecodable payloads and forged signatures are dropped and count against the
+// message's author.
+fn screen(
     swarm: &mut libp2p::Swarm<MyBehaviour>,
-    duration_ms: u64,
-) -> (u32, u32) {
-    let mut attack_count = 0;
-    let mut retreat_count = 0;
-    let attack_re = Regex::new(r"ATTACK").unwrap();
-    let retreat_re = Regex::new(r"RETREAT").unwrap();
-    let end_time = tokio::time::Instant::now() + Duration::from_millis(duration_ms);
-
-    while tokio::time::Instant::now() < end_time {
-        select! {
-            event = swarm.select_next_some() => match event {
-                SwarmEvent::Behaviour(MyBehaviourEvent::Gossipsub(gossipsub::Event::Message { message, .. })) => {
-                    let msg_str = String::from_utf8_lossy(&message.data).trim().to_string();
-                    if attack_re.is_match(&msg_str) {
-                        attack_count += 1;
-                    } else if retreat_re.is_match(&msg_str) {
-                        retreat_count += 1;
-                    }
-                },
-                _ => {}
-            },
-            _ = sleep(Duration::from_millis(duration_ms)) => break,
+    node: &mut Node,
+    message_id: &gossipsub::MessageId,
+    message: &gossipsub::Message,
+    propagation_source: PeerId,
+) -> Option<Message> {
+    let author = message.source.unwrap_or(propagation_source);
+    let (acceptance, decoded) = if node.scores.is_spamming(author) {
+        penalise(swarm, node, author, Offence::Spam);
+        (gossipsub::MessageAcceptance::Ignore, None)
+    } else {
+        match validation::validate(&message.data, node.core.view()) {
+            Ok(decoded) => (gossipsub::MessageAcceptance::Accept, Some(decoded)),
+            Err(rejection) => {
+                if rejection != validation::Rejection::Malformed {
+                    println!("Dropping message with {} from peer: {}", rejection, author);
+                }
+                if let Some(offence) = rejection.offence() {
+                    penalise(swarm, node, author, offence);
+                }
+                (rejection.acceptance(), None)
+            }
         }
+    };
+    if let Err(e) = swarm.behaviour_mut().gossipsub.report_message_validation_result(
+        message_id,
+        &propagation_source,
+        acceptance,
+    ) {
+        println!("Forwarding error: {e:?}");
+    }
+    decoded
+}
+
+fn penalise(swarm: &mut libp2p::Swarm<MyBehaviour>, node: &mut Node, peer: PeerId, offence: Offence) {
+    let score = node.scores.penalise(peer, offence);
+    println!("Penalised peer {} for {}: application score {:.1}", peer, offence, score);
+    swarm.behaviour_mut().gossipsub.set_application_score(&peer, score);
+    ban_if_below_threshold(swarm, node, peer);
+}
+
+fn ban_if_below_threshold(swarm: &mut libp2p::Swarm<MyBehaviour>, node: &Node, peer: PeerId) {
+    let Some(score) = swarm.behaviour().gossipsub.peer_score(&peer) else {
+        return;
+    };
+    if score <= node.ban_threshold {
+        println!("Banning peer {} with score {:.1}", peer, score);
+        swarm.behaviour_mut().gossipsub.remove_explicit_peer(&peer);
+        swarm.behaviour_mut().blocked.block_peer(peer);
+    }
+}
+
+fn update_scores(swarm: &mut libp2p::Swarm<MyBehaviour>, node: &mut Node) {
+    for (peer, score) in node.scores.decay() {
+        swarm.behaviour_mut().gossipsub.set_application_score(&peer, score);
     }
+    let peers: Vec<PeerId> = swarm
+        .behaviour()
+        .gossipsub
+        .all_peers()
+        .map(|(peer, _)| *peer)
+        .collect();
+    for peer in peers {
+        ban_if_below_threshold(swarm, node, peer);
+    }
+}
+
+fn print_evidence(node: &Node) {
+    let evidence = node.core.evidence().evidence();
+    if evidence.is_empty() {
+        println!("No equivocation evidence collected");
+    }
+    for evidence in evidence {
+        println!("Evidence: {}", evidence);
+    }
+}
 
-    (attack_count, retreat_count)
+// Synchronises the logical clock over NTP and records the offset and delay.
+fn synchronize_clock(ntp: &NtpMetrics) {
+    if let Some((offset_us, delay_us)) = clocky::synchronize_logical_clock() {
+        ntp.observe(offset_us, delay_us);
+    }
 }
 
-fn print_consensus(attack_count: u32, retreat_count: u32) {
-    clocky::synchronize_logical_clock();
+fn print_consensus(ntp: &NtpMetrics, attack_count: u32, retreat_count: u32) {
+    synchronize_clock(ntp);
                let current_time = clocky::current_logical_clock_time();
     let consensus = if attack_count > retreat_count {
         "ATTACK"
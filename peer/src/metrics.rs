@@ -0,0 +1,199 @@
+use crate::consensus::{Action, Core};
+use crate::protocol::Message;
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use libp2p::PeerId;
+use prometheus_client::encoding::text::encode;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::{exponential_buckets, Histogram};
+use prometheus_client::registry::{Registry, Unit};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use std::time::Instant;
+
+type Labels = Vec<(String, String)>;
+
+// NTP offset and delay of the last clock synchronisation. Cloned into the task
+// that synchronises the clock.
+#[derive(Clone, Default)]
+pub struct NtpMetrics {
+    offset: Gauge<f64, AtomicU64>,
+    delay: Gauge<f64, AtomicU64>,
+}
+
+impl NtpMetrics {
+    pub fn observe(&self, offset_us: i64, delay_us: u64) {
+        self.offset.set(offset_us as f64 / 1e6);
+        self.delay.set(delay_us as f64 / 1e6);
+    }
+}
+
+// What a node exposes on `/metrics`, fed from its consensus actions and the
+// messages it receives.
+pub struct Metrics {
+    view: Gauge,
+    height: Gauge,
+    // 1 for the peer that proposed in the latest round this node saw.
+    leader: Family<Labels, Gauge>,
+    connected_peers: Gauge,
+    messages_received: Family<Labels, Counter>,
+    messages_published: Family<Labels, Counter>,
+    // Time from a round's proposal to each vote for it.
+    vote_latency: Histogram,
+    round_timeouts: Counter,
+    clock_bumps: Counter,
+    sync_failures: Counter,
+    pub ntp: NtpMetrics,
+    // View of the round in progress and when its proposal was sent or received.
+    round_started: Option<(u64, Instant)>,
+}
+
+impl Metrics {
+    pub fn new(registry: &mut Registry) -> Self {
+        let metrics = Metrics {
+            view: Gauge::default(),
+            height: Gauge::default(),
+            leader: Family::default(),
+            connected_peers: Gauge::default(),
+            messages_received: Family::default(),
+            messages_published: Family::default(),
+            vote_latency: Histogram::new(exponential_buckets(0.005, 2.0, 12)),
+            round_timeouts: Counter::default(),
+            clock_bumps: Counter::default(),
+            sync_failures: Counter::default(),
+            ntp: NtpMetrics::default(),
+            round_started: None,
+        };
+        registry.register("view", "Current view", metrics.view.clone());
+        registry.register("height", "Height of the chain tip", metrics.height.clone());
+        registry.register("leader", "Proposer of the latest round", metrics.leader.clone());
+        registry.register("connected_peers", "Peers with an open connection", metrics.connected_peers.clone());
+        registry.register(
+            "messages_received",
+            "Valid consensus messages received, by kind",
+            metrics.messages_received.clone(),
+        );
+        registry.register(
+            "messages_published",
+            "Consensus messages published, by kind",
+            metrics.messages_published.clone(),
+        );
+        registry.register_with_unit(
+            "vote_latency",
+            "Time from a round's proposal to each vote received for it",
+            Unit::Seconds,
+            metrics.vote_latency.clone(),
+        );
+        registry.register("round_timeouts", "Vote windows that closed", metrics.round_timeouts.clone());
+        registry.register("clock_bumps", "View certificates that moved the view clock", metrics.clock_bumps.clone());
+        registry.register("sync_failures", "Block sync requests that failed", metrics.sync_failures.clone());
+        registry.register_with_unit(
+            "ntp_offset",
+            "Offset of the local clock at the last NTP sync",
+            Unit::Seconds,
+            metrics.ntp.offset.clone(),
+        );
+        registry.register_with_unit(
+            "ntp_delay",
+            "Round trip of the last NTP sync",
+            Unit::Seconds,
+            metrics.ntp.delay.clone(),
+        );
+        metrics
+    }
+
+    pub fn on_action(&mut self, action: &Action, core: &Core) {
+        match action {
+            Action::ViewEntered { view } => {
+                self.view.set(*view as i64);
+            }
+            Action::ClockBumped { .. } => {
+                self.clock_bumps.inc();
+            }
+            Action::Broadcast { message, .. } => {
+                self.messages_published.get_or_create(&kind(message)).inc();
+                if let Message::Proposal(proposal) = message {
+                    self.round_started(proposal.body.view, core.peer_id());
+                }
+            }
+            Action::Applied { .. } | Action::Restored(_) => {
+                self.height.set(core.chain().height() as i64);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn on_message(&mut self, message: &Message, from: PeerId) {
+        self.messages_received.get_or_create(&kind(message)).inc();
+        match message {
+            Message::Proposal(proposal) => {
+                self.round_started(proposal.body.view, proposal.signer().unwrap_or(from));
+            }
+            Message::Vote(vote) => {
+                if let Some((view, started)) = self.round_started {
+                    if view == vote.body.view {
+                        self.vote_latency.observe(started.elapsed().as_secs_f64());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn on_timeout(&self) {
+        self.round_timeouts.inc();
+    }
+
+    pub fn on_sync_failure(&self) {
+        self.sync_failures.inc();
+    }
+
+    pub fn set_connected_peers(&self, peers: usize) {
+        self.connected_peers.set(peers as i64);
+    }
+
+    fn round_started(&mut self, view: u64, leader: PeerId) {
+        self.round_started = Some((view, Instant::now()));
+        self.leader.clear();
+        self.leader
+            .get_or_create(&vec![("peer".to_string(), leader.to_string())])
+            .set(1);
+    }
+}
+
+fn kind(message: &Message) -> Labels {
+    vec![("kind".to_string(), message.kind().to_string())]
+}
+
+// Serves the registry as OpenMetrics text on `GET /metrics` until the listener fails.
+pub async fn serve(addr: SocketAddr, registry: Registry) -> io::Result<()> {
+    let app = Router::new()
+        .route("/metrics", get(render))
+        .with_state(Arc::new(registry));
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, app).await
+}
+
+async fn render(State(registry): State<Arc<Registry>>) -> impl IntoResponse {
+    let mut body = String::new();
+    match encode(&mut body, &registry) {
+        Ok(()) => (
+            StatusCode::OK,
+            [(CONTENT_TYPE, "application/openmetrics-text; version=1.0.0; charset=utf-8")],
+            body,
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            [(CONTENT_TYPE, "text/plain; charset=utf-8")],
+            "could not encode metrics".to_string(),
+        ),
+    }
+}
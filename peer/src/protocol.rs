@@ -0,0 +1,217 @@
+use crate::evidence::Evidence;
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::PeerId;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+// The two instructions a leader can give and peers can vote for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Decision {
+    Attack,
+    Retreat,
+}
+
+impl Decision {
+    // Parses the "START ATTACK" / "START RETREAT" commands typed by the leader.
+    pub fn from_command(line: &str) -> Option<Self> {
+        match line.trim() {
+            "START ATTACK" => Some(Decision::Attack),
+            "START RETREAT" => Some(Decision::Retreat),
+            _ => None,
+        }
+    }
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Decision::Attack => Decision::Retreat,
+            Decision::Retreat => Decision::Attack,
+        }
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decision::Attack => write!(f, "ATTACK"),
+            Decision::Retreat => write!(f, "RETREAT"),
+        }
+    }
+}
+
+pub type BlockHash = [u8; 32];
+
+// Short hex form of a block hash for log lines.
+pub fn short_hash(hash: &BlockHash) -> String {
+    hash[..4].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// The leader's instruction for the block at `height` on top of `parent`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proposal {
+    pub view: u64,
+    pub height: u64,
+    pub parent: BlockHash,
+    pub value: Decision,
+}
+
+// A peer's answer to a proposal. Votes are collected into quorum certificates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vote {
+    pub view: u64,
+    pub height: u64,
+    pub parent: BlockHash,
+    pub value: Decision,
+}
+
+// Types that are signed, each under its own domain tag so that a signature on
+// one never verifies as another, e.g. a proposal as a vote with the same fields.
+pub trait Signable: Serialize + DeserializeOwned {
+    const DOMAIN: &'static [u8];
+}
+
+impl Signable for Proposal {
+    const DOMAIN: &'static [u8] = b"fever/proposal/v1";
+}
+
+impl Signable for Vote {
+    const DOMAIN: &'static [u8] = b"fever/vote/v1";
+}
+
+impl Signable for ViewMessage {
+    const DOMAIN: &'static [u8] = b"fever/view/v1";
+}
+
+// A node's announcement that it entered `view`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViewMessage {
+    pub view: u64,
+}
+
+// Announcements of one view by distinct nodes. With f+1 of them at least one
+// comes from an honest node, so the view really started somewhere.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ViewCertificate {
+    pub view: u64,
+    pub messages: Vec<Signed<ViewMessage>>,
+}
+
+impl ViewCertificate {
+    // Number of distinct valid signers, or None if any announcement is invalid,
+    // repeated or for another view.
+    pub fn signers(&self) -> Option<usize> {
+        let mut signers = HashSet::new();
+        for message in &self.messages {
+            if message.body.view != self.view
+                || !message.verify()
+                || !signers.insert(message.public_key.clone())
+            {
+                return None;
+            }
+        }
+        Some(signers.len())
+    }
+}
+
+// A payload signed with the sender's libp2p identity key. Gossipsub already signs
+// whole messages, but those signatures are not exposed to the application, so votes
+// carry their own to stay verifiable when forwarded inside certificates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signed<T> {
+    pub body: T,
+    pub public_key: Vec<u8>,
+    pub signature: Vec<u8>,
+    // W3C trace context of the round this message belongs to. Not signed: it
+    // only correlates spans across nodes and has no say in consensus.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
+}
+
+impl<T: Signable> Signed<T> {
+    pub fn new(body: T, keypair: &Keypair) -> Self {
+        let signature = keypair
+            .sign(&signing_bytes(&body))
+            .expect("ed25519 signing does not fail");
+        Signed {
+            body,
+            public_key: keypair.public().encode_protobuf(),
+            signature,
+            traceparent: None,
+        }
+    }
+
+    // The peer that produced the signature, if the embedded key decodes.
+    pub fn signer(&self) -> Option<PeerId> {
+        PublicKey::try_decode_protobuf(&self.public_key)
+            .ok()
+            .map(|key| key.to_peer_id())
+    }
+
+    pub fn verify(&self) -> bool {
+        match PublicKey::try_decode_protobuf(&self.public_key) {
+            Ok(key) => key.verify(&signing_bytes(&self.body), &self.signature),
+            Err(_) => false,
+        }
+    }
+}
+
+fn signing_bytes<T: Signable>(body: &T) -> Vec<u8> {
+    let mut bytes = T::DOMAIN.to_vec();
+    bytes.push(0);
+    serde_json::to_writer(&mut bytes, body).expect("protocol types always serialize");
+    bytes
+}
+
+// Everything published on the gossipsub topic.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "payload")]
+pub enum Message {
+    Proposal(Signed<Proposal>),
+    Vote(Signed<Vote>),
+    Evidence(Evidence),
+    View(Signed<ViewMessage>),
+    ViewCertificate(ViewCertificate),
+}
+
+impl Message {
+    pub fn view(&self) -> u64 {
+        match self {
+            Message::Proposal(proposal) => proposal.body.view,
+            Message::Vote(vote) => vote.body.view,
+            Message::Evidence(evidence) => evidence.view(),
+            Message::View(view) => view.body.view,
+            Message::ViewCertificate(certificate) => certificate.view,
+        }
+    }
+
+    // A short name for logs and metric labels.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Message::Proposal(_) => "proposal",
+            Message::Vote(_) => "vote",
+            Message::Evidence(_) => "evidence",
+            Message::View(_) => "view",
+            Message::ViewCertificate(_) => "view_certificate",
+        }
+    }
+
+    // Checks the signatures of whatever the message carries.
+    pub fn verify(&self) -> bool {
+        match self {
+            Message::Proposal(proposal) => proposal.verify(),
+            Message::Vote(vote) => vote.verify(),
+            Message::Evidence(evidence) => evidence.verify(),
+            Message::View(view) => view.verify(),
+            Message::ViewCertificate(certificate) => certificate.signers().is_some_and(|n| n > 0),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("protocol types always serialize")
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(data)
+    }
+}
@@ -0,0 +1,122 @@
+use libp2p::gossipsub::{PeerScoreParams, TopicHash, TopicScoreParams};
+use libp2p::PeerId;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// Window in which `spam_limit` messages per author are tolerated.
+const SPAM_WINDOW: Duration = Duration::from_secs(10);
+
+// Fraction of a penalty that is left after each decay tick, so peers recover
+// from occasional mistakes but not from sustained misbehaviour.
+const PENALTY_DECAY: f64 = 0.9;
+
+// Protocol-level misbehaviour that lowers a peer's gossipsub score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Offence {
+    InvalidSignature,
+    Equivocation,
+    Malformed,
+    Spam,
+}
+
+impl Offence {
+    fn penalty(self) -> f64 {
+        match self {
+            Offence::InvalidSignature => 20.0,
+            // Equivocation is proven misbehaviour: enough to cross any ban threshold.
+            Offence::Equivocation => 1000.0,
+            Offence::Malformed => 5.0,
+            Offence::Spam => 10.0,
+        }
+    }
+}
+
+impl fmt::Display for Offence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Offence::InvalidSignature => write!(f, "invalid signature"),
+            Offence::Equivocation => write!(f, "equivocation"),
+            Offence::Malformed => write!(f, "malformed message"),
+            Offence::Spam => write!(f, "spam"),
+        }
+    }
+}
+
+// Gossipsub score parameters for the consensus topic. Only protocol signals count:
+// our own penalties through the application-specific score (P5) and messages we
+// reject during validation (P4). Consensus traffic is quiet between rounds, so
+// mesh delivery rates (P1-P3) say nothing about a peer and are disabled.
+pub fn score_params(topic: TopicHash) -> PeerScoreParams {
+    let mut params = PeerScoreParams {
+        app_specific_weight: 1.0,
+        // Docker and local clusters run many peers behind one address.
+        ip_colocation_factor_weight: 0.0,
+        ..Default::default()
+    };
+    params.topics.insert(
+        topic,
+        TopicScoreParams {
+            topic_weight: 1.0,
+            time_in_mesh_weight: 0.0,
+            first_message_deliveries_weight: 0.0,
+            mesh_message_deliveries_weight: 0.0,
+            mesh_failure_penalty_weight: 0.0,
+            invalid_message_deliveries_weight: -10.0,
+            invalid_message_deliveries_decay: 0.9,
+            ..Default::default()
+        },
+    );
+    params
+}
+
+// Application-specific scores fed into gossipsub, plus the per-author message
+// rates used to detect spam.
+pub struct PeerScores {
+    penalties: HashMap<PeerId, f64>,
+    rates: HashMap<PeerId, (Instant, u32)>,
+    spam_limit: u32,
+}
+
+impl PeerScores {
+    pub fn new(spam_limit: u32) -> Self {
+        PeerScores {
+            penalties: HashMap::new(),
+            rates: HashMap::new(),
+            spam_limit,
+        }
+    }
+
+    // Records an offence and returns the peer's new application score.
+    pub fn penalise(&mut self, peer: PeerId, offence: Offence) -> f64 {
+        let penalty = self.penalties.entry(peer).or_default();
+        *penalty += offence.penalty();
+        -*penalty
+    }
+
+    // Counts a message from `peer` and reports whether it exceeded the spam limit.
+    pub fn is_spamming(&mut self, peer: PeerId) -> bool {
+        let now = Instant::now();
+        let (start, count) = self.rates.entry(peer).or_insert((now, 0));
+        if now.duration_since(*start) > SPAM_WINDOW {
+            *start = now;
+            *count = 0;
+        }
+        *count += 1;
+        *count > self.spam_limit
+    }
+
+    // Lets penalties fade and returns the updated application scores.
+    pub fn decay(&mut self) -> Vec<(PeerId, f64)> {
+        self.rates
+            .retain(|_, (start, _)| start.elapsed() <= SPAM_WINDOW);
+        self.penalties.retain(|_, penalty| {
+            *penalty *= PENALTY_DECAY;
+            *penalty >= 0.1
+        });
+        self.penalties
+            .iter()
+            .map(|(peer, penalty)| (*peer, -*penalty))
+            .collect()
+    }
+}
@@ -0,0 +1,71 @@
+use crate::chain::{CertifiedBlock, Chain, Snapshot};
+use libp2p::request_response::{self, ProtocolSupport};
+use libp2p::StreamProtocol;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const PROTOCOL: StreamProtocol = StreamProtocol::new("/fever/sync/1");
+
+// Upper bound on blocks returned by a single `Blocks` request.
+pub const MAX_BLOCKS_PER_REQUEST: u64 = 64;
+
+// Gaps longer than this are closed with a snapshot instead of block replay.
+pub const SNAPSHOT_THRESHOLD: u64 = 256;
+
+// How long a peer waits for a sync response before giving up.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SyncRequest {
+    Status,
+    Blocks { from: u64, to: u64 },
+    Snapshot,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SyncResponse {
+    Status { height: u64, base_height: u64 },
+    Blocks(Vec<CertifiedBlock>),
+    Snapshot(Option<Snapshot>),
+}
+
+pub type Behaviour = request_response::json::Behaviour<SyncRequest, SyncResponse>;
+pub type Event = request_response::Event<SyncRequest, SyncResponse>;
+
+pub fn behaviour() -> Behaviour {
+    request_response::json::Behaviour::new(
+        [(PROTOCOL, ProtocolSupport::Full)],
+        request_response::Config::default().with_request_timeout(REQUEST_TIMEOUT),
+    )
+}
+
+// Answers a peer's sync request from the local chain.
+pub fn respond(chain: &Chain, request: SyncRequest) -> SyncResponse {
+    match request {
+        SyncRequest::Status => SyncResponse::Status {
+            height: chain.height(),
+            base_height: chain.base_height(),
+        },
+        SyncRequest::Blocks { from, to } => {
+            let to = to.min(from.saturating_add(MAX_BLOCKS_PER_REQUEST - 1));
+            SyncResponse::Blocks(chain.range(from, to))
+        }
+        SyncRequest::Snapshot => SyncResponse::Snapshot(chain.snapshot()),
+    }
+}
+
+// Decides how to catch up from `local` to a peer at `remote`. Replays blocks while
+// the peer still holds the next one and the gap is small, otherwise asks for a
+// snapshot of the peer's tip.
+pub fn next_request(local: u64, remote: u64, remote_base: u64) -> Option<SyncRequest> {
+    if remote <= local {
+        return None;
+    }
+    if remote - local > SNAPSHOT_THRESHOLD || local < remote_base {
+        return Some(SyncRequest::Snapshot);
+    }
+    Some(SyncRequest::Blocks {
+        from: local + 1,
+        to: remote.min(local.saturating_add(MAX_BLOCKS_PER_REQUEST)),
+    })
+}
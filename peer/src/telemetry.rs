@@ -0,0 +1,241 @@
+use crate::protocol::{Decision, Message};
+use libp2p::PeerId;
+use opentelemetry::global::{self, BoxedTracer};
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry::trace::{Span, TraceContextExt, Tracer};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{SdkTracerProvider, SpanData, SpanExporter};
+use opentelemetry_sdk::Resource;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::future::{self, Future};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Rounds older than this many views are forgotten, and their spans ended, even
+// if they never decided.
+const KEPT_VIEWS: u64 = 16;
+
+// Installs a tracer provider exporting to an OTLP/HTTP collector (e.g. Jaeger on
+// http://localhost:4318) and/or a JSON lines file. Without either, spans are
+// not recorded and messages carry no trace context. Keep the returned provider
+// alive for as long as spans should be exported.
+pub fn init(node: &str, otlp_endpoint: Option<&str>, file: Option<&Path>) -> Result<Option<SdkTracerProvider>, Box<dyn Error>> {
+    if otlp_endpoint.is_none() && file.is_none() {
+        return Ok(None);
+    }
+    let resource = Resource::builder()
+        .with_service_name("feverbft-peer")
+        .with_attribute(KeyValue::new("service.instance.id", node.to_string()))
+        .build();
+    let mut builder = SdkTracerProvider::builder().with_resource(resource);
+    if let Some(endpoint) = otlp_endpoint {
+        // The blocking HTTP client owns a runtime of its own, which cannot be
+        // created from inside the node's.
+        let endpoint = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+        let exporter = std::thread::spawn(move || {
+            opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .with_endpoint(endpoint)
+                .build()
+        })
+        .join()
+        .map_err(|_| "OTLP exporter setup panicked")??;
+        builder = builder.with_batch_exporter(exporter);
+    }
+    if let Some(path) = file {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        builder = builder.with_simple_exporter(FileExporter {
+            node: node.to_string(),
+            file: Mutex::new(BufWriter::new(file)),
+        });
+    }
+    let provider = builder.build();
+    global::set_tracer_provider(provider.clone());
+    Ok(Some(provider))
+}
+
+// Writes every finished span as one JSON object per line.
+#[derive(Debug)]
+struct FileExporter {
+    node: String,
+    file: Mutex<BufWriter<File>>,
+}
+
+impl SpanExporter for FileExporter {
+    fn export(&self, batch: Vec<SpanData>) -> impl Future<Output = OTelSdkResult> + Send {
+        future::ready(self.write(batch))
+    }
+}
+
+impl FileExporter {
+    fn write(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|e| OTelSdkError::InternalFailure(e.to_string()))?;
+        for span in batch {
+            let attributes: serde_json::Map<_, _> = span
+                .attributes
+                .iter()
+                .map(|kv| (kv.key.to_string(), json!(kv.value.to_string())))
+                .collect();
+            let line = json!({
+                "trace_id": span.span_context.trace_id().to_string(),
+                "span_id": span.span_context.span_id().to_string(),
+                "parent_span_id": span.parent_span_id.to_string(),
+                "name": span.name,
+                "node": self.node,
+                "start_us": micros(span.start_time),
+                "end_us": micros(span.end_time),
+                "attributes": attributes,
+            });
+            writeln!(file, "{}", line).map_err(|e| OTelSdkError::InternalFailure(e.to_string()))?;
+        }
+        file.flush().map_err(|e| OTelSdkError::InternalFailure(e.to_string()))
+    }
+}
+
+fn micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_micros() as u64)
+}
+
+// One trace per round. The leader opens a `round` span when it proposes and
+// puts its W3C trace context on the proposal. Followers continue that trace:
+// they record receiving the proposal, put a `vote` span's context on their
+// vote, and record each vote they receive under the voter's span. Every node
+// records its own `decide` span under the round.
+pub struct RoundTraces {
+    tracer: BoxedTracer,
+    propagator: TraceContextPropagator,
+    // Trace context of each round by view. For rounds this node leads, the
+    // context holds the open `round` span.
+    rounds: BTreeMap<u64, Context>,
+}
+
+impl Default for RoundTraces {
+    fn default() -> Self {
+        RoundTraces {
+            tracer: global::tracer("feverbft"),
+            propagator: TraceContextPropagator::new(),
+            rounds: BTreeMap::new(),
+        }
+    }
+}
+
+impl RoundTraces {
+    // Attaches the round's trace context to a proposal or vote about to be
+    // published.
+    pub fn outgoing(&mut self, mut message: Message) -> Message {
+        match &mut message {
+            Message::Proposal(proposal) => {
+                let view = proposal.body.view;
+                let span = self
+                    .tracer
+                    .span_builder("round")
+                    .with_attributes(slot(view, proposal.body.height, proposal.body.value))
+                    .start(&self.tracer);
+                let round = Context::new().with_span(span);
+                proposal.traceparent = self.inject(&round);
+                self.remember(view, round);
+            }
+            Message::Vote(vote) => {
+                let round = self.rounds.get(&vote.body.view).cloned().unwrap_or_default();
+                let span = self
+                    .tracer
+                    .span_builder("vote")
+                    .with_attributes(slot(vote.body.view, vote.body.height, vote.body.value))
+                    .start_with_context(&self.tracer, &round);
+                let context = round.with_span(span);
+                vote.traceparent = self.inject(&context);
+                context.span().end();
+            }
+            _ => {}
+        }
+        message
+    }
+
+    // Continues the trace a received proposal or vote carries.
+    pub fn incoming(&mut self, message: &Message, from: PeerId) {
+        let (name, traceparent, view, height, value) = match message {
+            Message::Proposal(proposal) => (
+                "proposal_received",
+                &proposal.traceparent,
+                proposal.body.view,
+                proposal.body.height,
+                proposal.body.value,
+            ),
+            Message::Vote(vote) => (
+                "vote_received",
+                &vote.traceparent,
+                vote.body.view,
+                vote.body.height,
+                vote.body.value,
+            ),
+            _ => return,
+        };
+        let Some(parent) = traceparent.as_deref().map(|traceparent| self.extract(traceparent)) else {
+            return;
+        };
+        if matches!(message, Message::Proposal(_)) && !self.rounds.contains_key(&view) {
+            self.remember(view, parent.clone());
+        }
+        let mut attributes = slot(view, height, value);
+        attributes.push(KeyValue::new("from", from.to_string()));
+        self.tracer
+            .span_builder(name)
+            .with_attributes(attributes)
+            .start_with_context(&self.tracer, &parent)
+            .end();
+    }
+
+    // Records the decision of a round and closes its trace on this node.
+    pub fn decided(&mut self, view: u64, height: u64, value: Decision, attack: u32, retreat: u32) {
+        let Some(round) = self.rounds.remove(&view) else {
+            return;
+        };
+        let mut attributes = slot(view, height, value);
+        attributes.push(KeyValue::new("attack", attack as i64));
+        attributes.push(KeyValue::new("retreat", retreat as i64));
+        self.tracer
+            .span_builder("decide")
+            .with_attributes(attributes)
+            .start_with_context(&self.tracer, &round)
+            .end();
+        round.span().end();
+    }
+
+    fn remember(&mut self, view: u64, round: Context) {
+        self.rounds.insert(view, round);
+        let kept = self.rounds.split_off(&view.saturating_sub(KEPT_VIEWS));
+        for stale in std::mem::replace(&mut self.rounds, kept).into_values() {
+            stale.span().end();
+        }
+    }
+
+    fn inject(&self, context: &Context) -> Option<String> {
+        let mut carrier = HashMap::new();
+        self.propagator.inject_context(context, &mut carrier);
+        carrier.remove("traceparent")
+    }
+
+    fn extract(&self, traceparent: &str) -> Context {
+        let carrier = HashMap::from([("traceparent".to_string(), traceparent.to_string())]);
+        self.propagator.extract(&carrier)
+    }
+}
+
+fn slot(view: u64, height: u64, value: Decision) -> Vec<KeyValue> {
+    vec![
+        KeyValue::new("view", view as i64),
+        KeyValue::new("height", height as i64),
+        KeyValue::new("value", value.to_string()),
+    ]
+}
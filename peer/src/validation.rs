@@ -0,0 +1,76 @@
+use crate::protocol::Message;
+use crate::scoring::Offence;
+use libp2p::gossipsub::MessageAcceptance;
+use std::fmt;
+
+// Messages from views this far behind ours are no longer useful to anyone.
+pub const STALE_VIEWS: u64 = 64;
+
+// Messages from views this far ahead of ours are not forwarded until we catch up.
+pub const MAX_VIEWS_AHEAD: u64 = 1024;
+
+// Why a gossip message was not accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    Malformed,
+    InvalidSignature,
+    Stale { view: u64 },
+    TooFarAhead { view: u64 },
+}
+
+impl Rejection {
+    // Provably bad messages are rejected, which also costs the forwarding peer
+    // gossipsub score. Messages that are merely out of range for us are ignored.
+    pub fn acceptance(self) -> MessageAcceptance {
+        match self {
+            Rejection::Malformed | Rejection::InvalidSignature => MessageAcceptance::Reject,
+            Rejection::Stale { .. } | Rejection::TooFarAhead { .. } => MessageAcceptance::Ignore,
+        }
+    }
+
+    // The offence charged to the message's author, if any.
+    pub fn offence(self) -> Option<Offence> {
+        match self {
+            Rejection::Malformed => Some(Offence::Malformed),
+            Rejection::InvalidSignature => Some(Offence::InvalidSignature),
+            Rejection::Stale { .. } | Rejection::TooFarAhead { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Malformed => write!(f, "malformed message"),
+            Rejection::InvalidSignature => write!(f, "invalid signature"),
+            Rejection::Stale { view } => write!(f, "stale view {}", view),
+            Rejection::TooFarAhead { view } => write!(f, "view {} too far ahead", view),
+        }
+    }
+}
+
+// Decodes a gossip payload and checks its signatures and view against the
+// node's current view. Evidence stays valid forever and skips the view check.
+pub fn validate(data: &[u8], current_view: u64) -> Result<Message, Rejection> {
+    let message = Message::decode(data).map_err(|_| Rejection::Malformed)?;
+    check(message, current_view)
+}
+
+// The checks of `validate` for a message that arrived already decoded, e.g.
+// sent directly instead of gossiped.
+pub fn check(message: Message, current_view: u64) -> Result<Message, Rejection> {
+    if !message.verify() {
+        return Err(Rejection::InvalidSignature);
+    }
+    if let Message::Evidence(_) = message {
+        return Ok(message);
+    }
+    let view = message.view();
+    if view.saturating_add(STALE_VIEWS) < current_view {
+        return Err(Rejection::Stale { view });
+    }
+    if view > current_view.saturating_add(MAX_VIEWS_AHEAD) {
+        return Err(Rejection::TooFarAhead { view });
+    }
+    Ok(message)
+}
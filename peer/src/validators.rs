@@ -0,0 +1,109 @@
+use libp2p::identity::PublicKey;
+use libp2p::PeerId;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+// The nodes whose signatures count towards quorum and view certificates, and
+// how many of them may be byzantine. Without a fixed set any key counts, so
+// byzantine peers can make up as many signers as they need.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Validators {
+    members: Option<BTreeSet<PeerId>>,
+    faults: usize,
+}
+
+// The parts of a cluster.json, as written by `feverbft`, that name the members.
+#[derive(Deserialize)]
+struct Membership {
+    nodes: Vec<Member>,
+}
+
+#[derive(Deserialize)]
+struct Member {
+    peer_id: String,
+}
+
+// The most byzantine nodes a cluster of `nodes` tolerates, n = 3f + 1.
+pub fn max_faults(nodes: usize) -> usize {
+    nodes.saturating_sub(1) / 3
+}
+
+impl Validators {
+    // A fixed set tolerating `faults` byzantine members, at most a third of them.
+    pub fn new(members: impl IntoIterator<Item = PeerId>, faults: usize) -> Result<Self, String> {
+        let members: BTreeSet<PeerId> = members.into_iter().collect();
+        if members.is_empty() {
+            return Err("the validator set is empty".to_string());
+        }
+        if faults > max_faults(members.len()) {
+            return Err(format!(
+                "{} validators tolerate at most {} faults, not {}",
+                members.len(),
+                max_faults(members.len()),
+                faults
+            ));
+        }
+        Ok(Validators {
+            members: Some(members),
+            faults,
+        })
+    }
+
+    // Counts every signer, for clusters whose members are not known up front.
+    pub fn open(faults: usize) -> Self {
+        Validators { members: None, faults }
+    }
+
+    // Reads the members from the `nodes[].peer_id` of a cluster.json. `faults`
+    // defaults to the most the set tolerates.
+    pub fn load(path: &Path, faults: Option<usize>) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path).map_err(|e| format!("could not read {}: {e}", path.display()))?;
+        let membership: Membership =
+            serde_json::from_str(&text).map_err(|e| format!("invalid validator set {}: {e}", path.display()))?;
+        let mut members = Vec::with_capacity(membership.nodes.len());
+        for member in membership.nodes {
+            let peer_id = member
+                .peer_id
+                .parse()
+                .map_err(|_| format!("invalid peer id '{}' in {}", member.peer_id, path.display()))?;
+            members.push(peer_id);
+        }
+        let faults = faults.unwrap_or(max_faults(members.len()));
+        Ok(Validators::new(members, faults)?)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.members.is_none()
+    }
+
+    // Number of members, if the set is fixed.
+    pub fn size(&self) -> Option<usize> {
+        self.members.as_ref().map(BTreeSet::len)
+    }
+
+    pub fn faults(&self) -> usize {
+        self.faults
+    }
+
+    // Distinct members whose votes decide a block: 2f + 1, so that any two
+    // quorums share an honest member.
+    pub fn quorum(&self) -> usize {
+        2 * self.faults + 1
+    }
+
+    pub fn contains(&self, peer: &PeerId) -> bool {
+        self.members.as_ref().is_none_or(|members| members.contains(peer))
+    }
+
+    // Whether a signature by this protobuf-encoded public key counts.
+    pub fn is_member(&self, public_key: &[u8]) -> bool {
+        match &self.members {
+            None => true,
+            Some(members) => PublicKey::try_decode_protobuf(public_key)
+                .is_ok_and(|key| members.contains(&key.to_peer_id())),
+        }
+    }
+}
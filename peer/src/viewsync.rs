@@ -0,0 +1,139 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+// Default length of a view on the local clock, in milliseconds. One round's
+// vote window plus a margin for the proposal to arrive.
+pub const VIEW_LENGTH_MS: u64 = 6000;
+
+// Announcements of views further ahead than this are dropped, so a signer cannot
+// make a node hold announcements for ever more views. An honest clock that far
+// ahead still moves the others through a certificate.
+pub const MAX_VIEWS_AHEAD: u64 = 64;
+
+// What the view synchroniser asks its owner to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SyncAction {
+    Entered(u64),
+    // Tell everyone we are in this view.
+    SendView(u64),
+    // Forward the f+1 view messages we hold for this view as a certificate.
+    SendCertificate(u64),
+    // The local view clock jumped forward.
+    Bumped { from: u64, to: u64 },
+}
+
+// Fever view synchronisation, without any I/O, signatures or real time, so the
+// node, the simulator and the model checker all run the same code.
+//
+// View `v` starts when the view clock reaches `v * view_length`. The view clock
+// is the local clock plus a forward offset. On entering a view the node
+// announces it. Once it holds `threshold` (f+1) announcements of a higher view,
+// at least one of them honest, it relays them as a certificate and bumps its
+// clock to the start of that view. Clocks only ever move forward, and never past
+// the clock of the fastest honest node.
+//
+// `Id` names the signer of an announcement. Callers check signatures before
+// passing announcements or certificates in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ViewSync<Id: Ord> {
+    view_length: u64,
+    threshold: usize,
+    offset: u64,
+    view: u64,
+    received: BTreeMap<u64, BTreeSet<Id>>,
+}
+
+impl<Id: Ord> ViewSync<Id> {
+    pub fn new(view_length: u64, threshold: usize) -> Self {
+        ViewSync {
+            view_length: view_length.max(1),
+            threshold: threshold.max(1),
+            offset: 0,
+            view: 0,
+            received: BTreeMap::new(),
+        }
+    }
+
+    // Starts with the view clock `offset` ahead of the local clock.
+    pub fn with_offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn view(&self) -> u64 {
+        self.view
+    }
+
+    pub fn view_length(&self) -> u64 {
+        self.view_length
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    // The view clock at local time `now`.
+    pub fn clock(&self, now: u64) -> u64 {
+        now.saturating_add(self.offset)
+    }
+
+    // When the next view starts, in local time.
+    pub fn next_view_at(&self) -> u64 {
+        (self.view + 1)
+            .saturating_mul(self.view_length)
+            .saturating_sub(self.offset)
+    }
+
+    // Enters the view the clock has reached, if it is past ours.
+    pub fn on_time(&mut self, now: u64) -> Vec<SyncAction> {
+        let reached = self.clock(now) / self.view_length;
+        if reached <= self.view {
+            return Vec::new();
+        }
+        self.enter(reached)
+    }
+
+    // Whether an announcement of `view` is worth holding: above ours, and at
+    // most `MAX_VIEWS_AHEAD` further.
+    pub fn is_pending(&self, view: u64) -> bool {
+        view > self.view && view - self.view <= MAX_VIEWS_AHEAD
+    }
+
+    // An announcement of `view` by `from`.
+    pub fn on_view_message(&mut self, from: Id, view: u64, now: u64) -> Vec<SyncAction> {
+        if !self.is_pending(view) {
+            return Vec::new();
+        }
+        let signers = self.received.entry(view).or_default();
+        signers.insert(from);
+        if signers.len() < self.threshold {
+            return Vec::new();
+        }
+        self.certified(view, now)
+    }
+
+    // A certificate of `threshold` distinct announcements of `view`.
+    pub fn on_certificate(&mut self, view: u64, now: u64) -> Vec<SyncAction> {
+        if view <= self.view {
+            return Vec::new();
+        }
+        self.certified(view, now)
+    }
+
+    fn certified(&mut self, view: u64, now: u64) -> Vec<SyncAction> {
+        let mut actions = vec![SyncAction::SendCertificate(view)];
+        let start = view.saturating_mul(self.view_length);
+        let clock = self.clock(now);
+        if clock < start {
+            self.offset += start - clock;
+            actions.push(SyncAction::Bumped { from: clock, to: start });
+        }
+        actions.extend(self.on_time(now));
+        actions
+    }
+
+    fn enter(&mut self, view: u64) -> Vec<SyncAction> {
+        self.view = view;
+        self.received = self.received.split_off(&(view + 1));
+        vec![SyncAction::Entered(view), SyncAction::SendView(view)]
+    }
+}
@@ -0,0 +1,58 @@
+// `feverbft cluster` starts a cluster of peer processes that share cluster.json
+// as their validator set, and stops them after `--duration`.
+
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("feverbft-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn feverbft(dir: &PathBuf, base_port: u16, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_feverbft"))
+        .arg("cluster")
+        .arg("--dir")
+        .arg(dir)
+        .args(["--base-port", &base_port.to_string(), "--peer", env!("CARGO_BIN_EXE_peer")])
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn a_cluster_runs_for_its_duration() {
+    let dir = dir("cluster");
+    let started = Instant::now();
+    let output = feverbft(&dir, 47_400, &["--nodes", "4", "--duration", "3"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{stdout}{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("Cluster of 4 nodes is running"), "{stdout}");
+    // The nodes exit on SIGTERM rather than being killed once the stop timeout passed.
+    assert!(started.elapsed() < Duration::from_secs(8));
+
+    let manifest: Value = serde_json::from_slice(&fs::read(dir.join("cluster.json")).unwrap()).unwrap();
+    assert_eq!(manifest["faults"], 1);
+    assert_eq!(manifest["nodes"].as_array().unwrap().len(), 4);
+    for index in 0..4 {
+        let log = fs::read_to_string(dir.join(format!("node-{index}")).join("peer.log")).unwrap();
+        assert!(log.contains("Running with honest behaviour"), "{log}");
+        assert!(!log.contains("No --validators given"), "{log}");
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn a_cluster_tolerates_at_most_a_third_of_its_nodes_failing() {
+    let dir = dir("faults");
+    let output = feverbft(&dir, 47_500, &["--nodes", "4", "--faults", "2", "--duration", "1"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("4 nodes tolerate at most 1 faults, not 2"));
+    assert!(!dir.join("cluster.json").exists());
+    let _ = fs::remove_dir_all(&dir);
+}
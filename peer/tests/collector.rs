@@ -0,0 +1,64 @@
+// The collector pushes events to peer-server in batches small enough for it to
+// accept, and keeps what it could not deliver.
+
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::Router;
+use peer::collector::{self, Lines};
+use peer::control::Server;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+// Serves `/events` with axum's default body limit, counting the lines taken.
+// The first `refuse` requests fail with 503.
+async fn collector(refuse: usize) -> (String, Arc<Mutex<Vec<usize>>>) {
+    let batches = Arc::new(Mutex::new(Vec::new()));
+    let refused = Arc::new(Mutex::new(0));
+    let app = Router::new().route(
+        "/events",
+        post({
+            let batches = batches.clone();
+            move |body: String| async move {
+                let mut refused = refused.lock().unwrap();
+                if *refused < refuse {
+                    *refused += 1;
+                    return StatusCode::SERVICE_UNAVAILABLE;
+                }
+                batches.lock().unwrap().push(body.lines().count());
+                StatusCode::OK
+            }
+        }),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (url, batches)
+}
+
+async fn delivered(batches: &Mutex<Vec<usize>>, lines: usize) {
+    for _ in 0..50 {
+        if batches.lock().unwrap().iter().sum::<usize>() >= lines {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+#[tokio::test]
+async fn a_backlog_goes_out_in_batches() {
+    let (url, batches) = collector(2).await;
+    let (mut writer, lines) = Lines::new();
+    // About 3 MB of events, more than one request may carry.
+    let line = format!("{{\"event\":\"view_entered\",\"padding\":\"{}\"}}\n", "x".repeat(300));
+    for _ in 0..10_000 {
+        writer.write_all(line.as_bytes()).unwrap();
+    }
+    tokio::spawn(collector::push(Server::new(&url, None, None).unwrap(), lines));
+
+    delivered(&batches, 10_000).await;
+    let batches = batches.lock().unwrap();
+    assert_eq!(batches.iter().sum::<usize>(), 10_000);
+    assert!(batches.len() >= 3, "{batches:?}");
+}
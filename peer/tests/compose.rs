@@ -0,0 +1,50 @@
+// `feverbft compose` turns a cluster spec into docker-compose services that
+// share cluster.json as their validator set.
+
+use peer::compose::{self, ClusterSpec};
+use serde_yaml::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("feverbft-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn spec(dir: &Path, yaml: &str) -> Result<ClusterSpec, String> {
+    let path = dir.join("cluster.yaml");
+    fs::write(&path, yaml).unwrap();
+    ClusterSpec::load(&path).map_err(|e| e.to_string())
+}
+
+#[test]
+fn every_service_reads_the_validator_set() {
+    let dir = dir("compose");
+    let spec = spec(&dir, "honest: 3\nbyzantine:\n  - { strategy: inverted, count: 1 }\n").unwrap();
+    assert_eq!(spec.faults(), 1);
+    let nodes = compose::generate(&spec, &dir).unwrap();
+    assert_eq!(nodes.len(), 4);
+
+    let generated: Value = serde_yaml::from_str(&fs::read_to_string(dir.join("docker-compose.yaml")).unwrap()).unwrap();
+    for node in &nodes {
+        let service = &generated["services"][node.service.as_str()];
+        let environment: Vec<&str> = service["environment"].as_sequence().unwrap().iter().filter_map(Value::as_str).collect();
+        assert!(environment.contains(&"FEVER_VALIDATORS=/cluster.json"), "{environment:?}");
+        assert!(environment.contains(&"FEVER_FAULTS=1"), "{environment:?}");
+        let volumes: Vec<&str> = service["volumes"].as_sequence().unwrap().iter().filter_map(Value::as_str).collect();
+        assert!(volumes.contains(&"./cluster.json:/cluster.json:ro"), "{volumes:?}");
+    }
+    assert!(dir.join("cluster.json").exists());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn specs_tolerate_at_most_a_third_of_their_nodes_failing() {
+    let dir = dir("compose-faults");
+    let error = spec(&dir, "honest: 4\nfaults: 2\n").unwrap_err();
+    assert!(error.contains("4 nodes tolerate at most 1 faults, not 2"), "{error}");
+    assert_eq!(spec(&dir, "honest: 7\nfaults: 2\n").unwrap().faults(), 2);
+    let _ = fs::remove_dir_all(&dir);
+}
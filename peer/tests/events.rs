@@ -0,0 +1,94 @@
+// Every node event the event layer writes reads back as the `LogEntry` that the
+// simulator's checker consumes.
+
+use peer::events::{self, LogEntry, NodeEvent};
+use peer::protocol::Decision;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::layer::SubscriberExt;
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn every_event() -> Vec<NodeEvent> {
+    vec![
+        NodeEvent::Started {
+            strategy: "honest".to_string(),
+        },
+        NodeEvent::RoleChanged {
+            from: "honest".to_string(),
+            to: "inverted".to_string(),
+        },
+        NodeEvent::ViewEntered { view: 3 },
+        NodeEvent::ClockBumped { from: 17000, to: 18000 },
+        NodeEvent::ClockSync {
+            offset_us: -1500,
+            delay_us: 800,
+        },
+        NodeEvent::Proposed {
+            view: 3,
+            height: 2,
+            value: Decision::Attack,
+        },
+        NodeEvent::VoteSent {
+            view: 3,
+            height: 2,
+            value: Decision::Retreat,
+        },
+        NodeEvent::VoteReceived {
+            view: 3,
+            height: 2,
+            value: Decision::Attack,
+            from: "12D3KooWvoter".to_string(),
+        },
+        NodeEvent::Timeout { view: 3 },
+        NodeEvent::QcFormed {
+            view: 3,
+            height: 2,
+            value: Decision::Attack,
+            attack: 3,
+            retreat: 1,
+        },
+        NodeEvent::Committed {
+            view: 3,
+            height: 2,
+            hash: "00ff00ff00ff00ff".to_string(),
+            value: Decision::Attack,
+            synced: true,
+        },
+    ]
+}
+
+#[test]
+fn the_event_layer_writes_log_entries() {
+    let buffer = Buffer::default();
+    let writer = buffer.clone();
+    let subscriber = tracing_subscriber::registry().with(events::layer(move || writer.clone()));
+    events::set_node("node-0".to_string());
+    tracing::subscriber::with_default(subscriber, || {
+        // Other targets stay out of the event log.
+        tracing::info!("not an event");
+        for event in every_event() {
+            events::record(&event);
+        }
+    });
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let entries: Vec<LogEntry> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("{line}: {e}")))
+        .collect();
+    assert_eq!(entries.iter().map(|entry| entry.event.clone()).collect::<Vec<_>>(), every_event());
+    assert!(entries.iter().all(|entry| entry.node == "node-0" && entry.at_ms > 0));
+}
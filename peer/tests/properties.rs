@@ -0,0 +1,467 @@
+// Property tests for the message decoder, vote aggregation, block sync and the
+// view progression of the consensus core. The fuzz targets in `fuzz/` cover the same
+// code with unstructured input.
+
+use libp2p::identity::Keypair;
+use libp2p::PeerId;
+use peer::byzantine::Honest;
+use peer::chain::{Block, CertifiedBlock, Chain, ChainError, QuorumCertificate, Snapshot, GENESIS_HASH};
+use peer::consensus::{Action, Core};
+use peer::evidence::Evidence;
+use peer::protocol::{BlockHash, Decision, Message, Proposal, Signed, ViewCertificate, ViewMessage, Vote};
+use peer::sync::{self, SyncRequest, SyncResponse, MAX_BLOCKS_PER_REQUEST, SNAPSHOT_THRESHOLD};
+use peer::validation::{self, Rejection};
+use peer::validators::Validators;
+use peer::viewsync::{ViewSync, MAX_VIEWS_AHEAD};
+use proptest::prelude::*;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const VALIDATORS: usize = 4;
+
+fn keypair(index: usize) -> Keypair {
+    Keypair::ed25519_from_bytes([index as u8 + 1; 32]).expect("32 bytes are a valid ed25519 secret")
+}
+
+// Every signer `keypair` hands out below VALIDATORS, tolerating one fault.
+fn validators() -> Validators {
+    Validators::new((0..VALIDATORS).map(|index| keypair(index).public().to_peer_id()), 1).unwrap()
+}
+
+fn decision() -> impl Strategy<Value = Decision> {
+    prop_oneof![Just(Decision::Attack), Just(Decision::Retreat)]
+}
+
+fn parent() -> impl Strategy<Value = BlockHash> {
+    prop_oneof![Just(GENESIS_HASH), any::<[u8; 32]>()]
+}
+
+prop_compose! {
+    fn vote()(signer in 0..VALIDATORS, view in 1..6u64, height in 1..4u64, parent in parent(), value in decision())
+        -> Signed<Vote> {
+        Signed::new(Vote { view, height, parent, value }, &keypair(signer))
+    }
+}
+
+prop_compose! {
+    fn proposal()(signer in 0..VALIDATORS, view in 1..6u64, height in 1..4u64, parent in parent(), value in decision(),
+                  traceparent in proptest::option::of("00-[0-9a-f]{32}-[0-9a-f]{16}-01"))
+        -> Signed<Proposal> {
+        Signed { traceparent, ..Signed::new(Proposal { view, height, parent, value }, &keypair(signer)) }
+    }
+}
+
+prop_compose! {
+    // Two votes by the same validator for the same slot with opposite values.
+    fn conflicting_votes()(vote in vote()) -> Evidence {
+        let signer = keypair(VALIDATORS);
+        let second = Vote { value: vote.body.value.opposite(), ..vote.body.clone() };
+        Evidence::ConflictingVotes(Signed::new(vote.body, &signer), Signed::new(second, &signer))
+    }
+}
+
+prop_compose! {
+    fn view_message()(signer in 0..VALIDATORS, view in 1..6u64) -> Signed<ViewMessage> {
+        Signed::new(ViewMessage { view }, &keypair(signer))
+    }
+}
+
+prop_compose! {
+    // Announcements of distinct validators for one view.
+    fn view_certificate()(view in 1..6u64, signers in proptest::sample::subsequence((0..VALIDATORS).collect::<Vec<_>>(), 1..=VALIDATORS))
+        -> ViewCertificate {
+        let messages = signers.into_iter().map(|signer| Signed::new(ViewMessage { view }, &keypair(signer))).collect();
+        ViewCertificate { view, messages }
+    }
+}
+
+fn message() -> impl Strategy<Value = Message> {
+    prop_oneof![
+        proposal().prop_map(Message::Proposal),
+        vote().prop_map(Message::Vote),
+        conflicting_votes().prop_map(Message::Evidence),
+        view_message().prop_map(Message::View),
+        view_certificate().prop_map(Message::ViewCertificate),
+    ]
+}
+
+// The message without the trace context, which is carried along unsigned.
+fn untraced(message: Message) -> Message {
+    fn strip<T>(signed: Signed<T>) -> Signed<T> {
+        Signed { traceparent: None, ..signed }
+    }
+    match message {
+        Message::Proposal(proposal) => Message::Proposal(strip(proposal)),
+        Message::Vote(vote) => Message::Vote(strip(vote)),
+        Message::Evidence(Evidence::ConflictingVotes(first, second)) => {
+            Message::Evidence(Evidence::ConflictingVotes(strip(first), strip(second)))
+        }
+        Message::Evidence(Evidence::ConflictingProposals(first, second)) => {
+            Message::Evidence(Evidence::ConflictingProposals(strip(first), strip(second)))
+        }
+        Message::View(view) => Message::View(strip(view)),
+        Message::ViewCertificate(certificate) => Message::ViewCertificate(ViewCertificate {
+            view: certificate.view,
+            messages: certificate.messages.into_iter().map(strip).collect(),
+        }),
+    }
+}
+
+// Votes of distinct validators for one slot.
+fn votes_for(block: &Block, values: &[Decision]) -> Vec<Signed<Vote>> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(signer, value)| {
+            let vote = Vote {
+                view: block.view,
+                height: block.height,
+                parent: block.parent,
+                value: *value,
+            };
+            Signed::new(vote, &keypair(signer))
+        })
+        .collect()
+}
+
+// Blocks deciding `values` one after the other, each certified by a quorum.
+fn certified_blocks(values: &[Decision]) -> Vec<CertifiedBlock> {
+    let mut parent = GENESIS_HASH;
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let block = Block { view: index as u64 + 1, height: index as u64 + 1, parent, value: *value };
+            parent = block.hash();
+            let qc = QuorumCertificate { votes: votes_for(&block, &vec![*value; validators().quorum()]) };
+            CertifiedBlock { block, qc }
+        })
+        .collect()
+}
+
+fn chain(blocks: &[CertifiedBlock]) -> Chain {
+    let mut chain = Chain::new(validators());
+    for block in blocks {
+        chain.apply(block.clone()).unwrap();
+    }
+    chain
+}
+
+// Long enough to take more than one block request to sync.
+const SYNCED_BLOCKS: usize = MAX_BLOCKS_PER_REQUEST as usize + 10;
+
+// A chain of SYNCED_BLOCKS blocks to sync from, built once as signing is slow.
+fn synced() -> &'static (Vec<CertifiedBlock>, Chain) {
+    static SYNCED: OnceLock<(Vec<CertifiedBlock>, Chain)> = OnceLock::new();
+    SYNCED.get_or_init(|| {
+        let values: Vec<Decision> = (0..SYNCED_BLOCKS).map(|index| if index % 3 == 0 { Decision::Retreat } else { Decision::Attack }).collect();
+        let blocks = certified_blocks(&values);
+        let chain = chain(&blocks);
+        (blocks, chain)
+    })
+}
+
+// Catches `local` up with `remote` the way a peer does, asking for whatever
+// `next_request` says until it says nothing. Returns the requests sent.
+fn catch_up(local: &mut Chain, remote: &Chain) -> Vec<SyncRequest> {
+    let mut requests = Vec::new();
+    while let Some(request) = sync::next_request(local.height(), remote.height(), remote.base_height()) {
+        requests.push(request.clone());
+        match sync::respond(remote, request) {
+            SyncResponse::Blocks(blocks) => {
+                assert!(!blocks.is_empty() && blocks.len() as u64 <= MAX_BLOCKS_PER_REQUEST);
+                for block in blocks {
+                    local.apply(block).unwrap();
+                }
+            }
+            SyncResponse::Snapshot(Some(snapshot)) => local.restore(snapshot).unwrap(),
+            response => panic!("unexpected {response:?}"),
+        }
+    }
+    requests
+}
+
+// Any u64, with the values next to the ends of the range drawn often.
+fn edge_u64() -> impl Strategy<Value = u64> {
+    prop_oneof![any::<u64>(), 0..=100u64, (u64::MAX - 100)..=u64::MAX]
+}
+
+proptest! {
+    // Every case signs messages, so keep the default run short.
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn messages_round_trip(message in message()) {
+        prop_assert!(message.verify());
+        prop_assert_eq!(Message::decode(&message.encode()).unwrap(), message);
+    }
+
+    #[test]
+    fn decoder_never_panics(data in proptest::collection::vec(any::<u8>(), 0..512), view in any::<u64>()) {
+        let _ = validation::validate(&data, view);
+    }
+
+    // Flipping any byte of a valid message either breaks decoding or the
+    // signature, or only touches what is not signed: the name of an optional
+    // field or the trace context.
+    #[test]
+    fn corrupted_messages_are_caught(message in message(), index in any::<prop::sample::Index>(), flip in 1..=255u8) {
+        let mut data = message.encode();
+        let at = index.index(data.len());
+        data[at] ^= flip;
+        match validation::validate(&data, message.view()) {
+            Ok(decoded) => prop_assert_eq!(untraced(decoded), untraced(message)),
+            Err(Rejection::Malformed) | Err(Rejection::InvalidSignature) => {}
+            Err(rejection) => prop_assert!(false, "corruption passed as {}", rejection),
+        }
+    }
+
+    #[test]
+    fn views_are_checked_without_overflow(vote in vote(), view in edge_u64(), current in edge_u64()) {
+        let vote = Vote { view, ..vote.body };
+        let message = Message::Vote(Signed::new(vote, &keypair(0)));
+        match validation::validate(&message.encode(), current) {
+            Ok(_) | Err(Rejection::Stale { .. }) | Err(Rejection::TooFarAhead { .. }) => {}
+            Err(rejection) => prop_assert!(false, "valid message rejected as {}", rejection),
+        }
+    }
+
+    #[test]
+    fn tally_counts_every_vote(votes in proptest::collection::vec(vote(), 0..16)) {
+        let qc = QuorumCertificate { votes };
+        let (attack, retreat) = qc.tally();
+        prop_assert_eq!((attack + retreat) as usize, qc.votes.len());
+        let expected = if attack > retreat { Decision::Attack } else { Decision::Retreat };
+        prop_assert_eq!(qc.decision(), expected);
+    }
+
+    #[test]
+    fn certificates_verify_their_own_decision(
+        view in 1..100u64,
+        height in 1..100u64,
+        parent in parent(),
+        value in decision(),
+        dissent in any::<bool>(),
+    ) {
+        // 2f + 1 validators vote for `value`, the last one against it or not at all.
+        let validators = validators();
+        let quorum = validators.quorum();
+        let mut values = vec![value; quorum];
+        if dissent {
+            values.push(value.opposite());
+        }
+        let block = Block { view, height, parent, value };
+        let qc = QuorumCertificate { votes: votes_for(&block, &values) };
+        prop_assert_eq!(qc.verify(&block, &validators), Ok(()));
+
+        let wrong = Block { value: value.opposite(), ..block.clone() };
+        prop_assert_eq!(qc.verify(&wrong, &validators), Err(ChainError::NoQuorum { height, votes: dissent as usize, quorum }));
+
+        let mut doubled = qc.clone();
+        doubled.votes.push(qc.votes[0].clone());
+        prop_assert!(doubled.verify(&block, &validators).is_err());
+
+        // One vote short of 2f + 1.
+        let mut short = qc.clone();
+        short.votes.truncate(quorum - 1);
+        prop_assert_eq!(short.verify(&block, &validators), Err(ChainError::NoQuorum { height, votes: quorum - 1, quorum }));
+
+        // A quorum made up with a key outside the validator set.
+        let mut forged = short.clone();
+        let vote = Vote { value, ..forged.votes[0].body.clone() };
+        forged.votes.push(Signed::new(vote, &keypair(VALIDATORS)));
+        prop_assert_eq!(forged.verify(&block, &validators), Err(ChainError::UnknownVoter(height)));
+
+        // Validators split evenly decide nothing, whichever value is claimed.
+        let split: Vec<Decision> = (0..VALIDATORS).map(|index| if index % 2 == 0 { value } else { value.opposite() }).collect();
+        let split = QuorumCertificate { votes: votes_for(&block, &split) };
+        prop_assert!(split.verify(&block, &validators).is_err());
+        prop_assert!(split.verify(&wrong, &validators).is_err());
+    }
+
+    // A signed proposal never passes as a vote with the same fields.
+    #[test]
+    fn signatures_are_bound_to_their_message_type(proposal in proposal()) {
+        let body = &proposal.body;
+        let vote = Vote { view: body.view, height: body.height, parent: body.parent, value: body.value };
+        let forged = Signed { body: vote, public_key: proposal.public_key.clone(), signature: proposal.signature.clone(), traceparent: None };
+        prop_assert!(proposal.verify());
+        prop_assert!(!forged.verify());
+    }
+
+    // Whatever the core is fed, its view never goes back, every view it reports
+    // entering is higher than the last, and its chain only grows one block at a
+    // time.
+    #[test]
+    fn core_views_only_move_forward(ops in proptest::collection::vec(op(), 0..40)) {
+        let mut core = Core::new(keypair(0), Box::new(Honest), ViewSync::new(1000, 2), validators());
+        let mut now = 0;
+        let mut view = core.view();
+        let mut height = core.chain().height();
+        for op in ops {
+            let actions = match op {
+                Op::Propose(value) => {
+                    if core.in_round() {
+                        continue;
+                    }
+                    core.propose(value)
+                }
+                Op::Receive(from, message) => match validation::validate(&message.encode(), core.view()) {
+                    Ok(message) => core.on_message(from, message),
+                    Err(_) => continue,
+                },
+                Op::Close(view) => core.close_round(view),
+                Op::Tick(elapsed) => {
+                    now += elapsed;
+                    core.tick(Duration::from_millis(now))
+                }
+            };
+            for action in &actions {
+                match action {
+                    Action::ViewEntered { view: entered } => {
+                        prop_assert!(*entered > view);
+                        view = *entered;
+                    }
+                    Action::Applied { block, .. } => {
+                        prop_assert_eq!(block.height, height + 1);
+                        height = block.height;
+                    }
+                    _ => {}
+                }
+            }
+            prop_assert_eq!(core.view(), view);
+            prop_assert_eq!(core.chain().height(), height);
+        }
+    }
+
+    // Announcements move the view once f + 1 validators sent them, and only for
+    // views within reach; certificates count their validators' announcements
+    // alone. Signers from VALIDATORS up are outside the set.
+    #[test]
+    fn only_validators_move_the_view(
+        view in 1..2 * MAX_VIEWS_AHEAD,
+        signers in proptest::sample::subsequence((0..VALIDATORS + 2).collect::<Vec<_>>(), 0..=VALIDATORS + 2),
+    ) {
+        let core = || Core::new(keypair(0), Box::new(Honest), ViewSync::new(1000, 2), validators());
+        let messages: Vec<_> = signers.iter().map(|&signer| Signed::new(ViewMessage { view }, &keypair(signer))).collect();
+        let certified = signers.iter().filter(|&&signer| signer < VALIDATORS).count() >= 2;
+
+        let mut announced = core();
+        for (signer, message) in signers.iter().zip(&messages) {
+            announced.on_message(keypair(*signer).public().to_peer_id(), Message::View(message.clone()));
+        }
+        let expected = if certified && view <= MAX_VIEWS_AHEAD { view } else { 0 };
+        prop_assert_eq!(announced.view(), expected);
+
+        let mut relayed = core();
+        relayed.on_message(keypair(1).public().to_peer_id(), Message::ViewCertificate(ViewCertificate { view, messages }));
+        prop_assert_eq!(relayed.view(), if certified { view } else { 0 });
+    }
+}
+
+proptest! {
+    // Every block synced verifies 2f + 1 signatures, which is slow in debug builds.
+    #![proptest_config(ProptestConfig::with_cases(4))]
+
+    // Small gaps are closed block by block, at most MAX_BLOCKS_PER_REQUEST at a
+    // time, large ones and those the peer no longer holds blocks for with a
+    // snapshot.
+    #[test]
+    fn sync_replays_small_gaps_and_snapshots_large_ones(local in edge_u64(), gap in 0..2 * SNAPSHOT_THRESHOLD, base in 0..2 * SNAPSHOT_THRESHOLD) {
+        let remote = local.saturating_add(gap);
+        let base = base.min(remote);
+        match sync::next_request(local, remote, base) {
+            None => prop_assert_eq!(remote, local),
+            Some(SyncRequest::Snapshot) => prop_assert!(remote - local > SNAPSHOT_THRESHOLD || local < base),
+            Some(SyncRequest::Blocks { from, to }) => {
+                prop_assert!(remote - local <= SNAPSHOT_THRESHOLD && local >= base);
+                prop_assert_eq!(from, local + 1);
+                prop_assert!(from <= to && to <= remote && to - from < MAX_BLOCKS_PER_REQUEST);
+            }
+            Some(SyncRequest::Status) => prop_assert!(false, "next_request never asks for the status"),
+        }
+    }
+
+    #[test]
+    fn a_lagging_chain_catches_up_block_by_block(local in 0..=SYNCED_BLOCKS) {
+        let (blocks, remote) = synced();
+        let mut lagging = chain(&blocks[..local]);
+        let requests = catch_up(&mut lagging, remote);
+        prop_assert!(requests.iter().all(|request| matches!(request, SyncRequest::Blocks { .. })), "{:?}", requests);
+        prop_assert_eq!(lagging.height(), remote.height());
+        prop_assert_eq!(lagging.tip_hash(), remote.tip_hash());
+
+        // However many blocks are asked for, one answer carries at most MAX_BLOCKS_PER_REQUEST.
+        let SyncResponse::Blocks(answer) = sync::respond(remote, SyncRequest::Blocks { from: 1, to: u64::MAX }) else {
+            panic!("blocks are answered with blocks");
+        };
+        prop_assert_eq!(answer.len() as u64, remote.height().min(MAX_BLOCKS_PER_REQUEST));
+    }
+
+    // A peer restored from a snapshot far ahead only holds its tip, so a
+    // lagging chain jumps there instead of asking for blocks.
+    #[test]
+    fn a_far_behind_chain_restores_a_snapshot(
+        height in SNAPSHOT_THRESHOLD..10 * SNAPSHOT_THRESHOLD,
+        parent in any::<[u8; 32]>(),
+        value in decision(),
+        local in 0..4usize,
+    ) {
+        let block = Block { view: height, height, parent, value };
+        let qc = QuorumCertificate { votes: votes_for(&block, &vec![value; validators().quorum()]) };
+        let tip = CertifiedBlock { block, qc };
+        let mut remote = Chain::new(validators());
+        remote.restore(Snapshot { tip: tip.clone() }).unwrap();
+
+        let mut lagging = chain(&certified_blocks(&vec![value; local]));
+        prop_assert!(matches!(catch_up(&mut lagging, &remote).as_slice(), [SyncRequest::Snapshot]));
+        prop_assert_eq!(lagging.height(), height);
+        prop_assert_eq!(lagging.base_height(), height - 1);
+        prop_assert_eq!(lagging.tip(), Some(&tip));
+    }
+
+    // Neither a synced block nor a snapshot is taken without 2f + 1 validators
+    // voting for it.
+    #[test]
+    fn sync_rejects_blocks_without_a_validator_quorum(
+        values in proptest::collection::vec(decision(), 0..4),
+        value in decision(),
+        outsiders in any::<bool>(),
+    ) {
+        let mut local = chain(&certified_blocks(&values));
+        let height = local.height() + 1;
+        let block = Block { view: height, height, parent: local.tip_hash(), value };
+        let quorum = validators().quorum();
+        let (votes, expected) = if outsiders {
+            // As many votes as a quorum, signed by keys outside the validator set.
+            let votes = (VALIDATORS..VALIDATORS + quorum)
+                .map(|signer| Signed::new(Vote { view: height, height, parent: block.parent, value }, &keypair(signer)))
+                .collect();
+            (votes, ChainError::UnknownVoter(height))
+        } else {
+            (votes_for(&block, &vec![value; quorum - 1]), ChainError::NoQuorum { height, votes: quorum - 1, quorum })
+        };
+        let forged = CertifiedBlock { block, qc: QuorumCertificate { votes } };
+
+        prop_assert_eq!(local.apply(forged.clone()), Err(expected.clone()));
+        prop_assert_eq!(local.restore(Snapshot { tip: forged }), Err(expected));
+        prop_assert_eq!(local.height(), values.len() as u64);
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Op {
+    Propose(Decision),
+    Receive(PeerId, Box<Message>),
+    Close(u64),
+    Tick(u64),
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        decision().prop_map(Op::Propose),
+        (0..VALIDATORS, message()).prop_map(|(from, message)| Op::Receive(keypair(from).public().to_peer_id(), Box::new(message))),
+        (0..8u64).prop_map(Op::Close),
+        (0..1500u64).prop_map(Op::Tick),
+    ]
+}
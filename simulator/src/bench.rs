@@ -0,0 +1,135 @@
+use crate::simulation::Report;
+use peer::consensus::Action;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::time::Duration;
+
+// How long after the first honest node entered a view the last one did.
+#[derive(Debug, Clone, Serialize)]
+pub struct ViewLatency {
+    pub view: u64,
+    pub latency_ms: f64,
+}
+
+// What one run cost and achieved, counted over honest nodes.
+#[derive(Debug, Clone, Serialize)]
+pub struct Measurement {
+    pub nodes: usize,
+    pub byzantine: usize,
+    pub seed: u64,
+    pub duration_ms: u64,
+    // Highest view an honest node reached.
+    pub views: u64,
+    // Views every honest node entered; skipped views have no latency.
+    pub synced_views: usize,
+    pub mean_sync_ms: f64,
+    pub max_sync_ms: f64,
+    pub messages: u64,
+    pub view_messages: u64,
+    // Encoded message payloads only, without gossipsub, noise or TCP framing,
+    // so real traffic is higher.
+    pub payload_bytes: u64,
+    pub messages_per_view: f64,
+    pub payload_bytes_per_view: f64,
+    // Blocks every honest node committed.
+    pub committed: u64,
+    pub blocks_per_second: f64,
+    pub latencies: Vec<ViewLatency>,
+}
+
+impl Measurement {
+    pub fn from_report(report: &Report) -> Self {
+        let honest: HashSet<usize> = report
+            .nodes
+            .iter()
+            .filter(|node| node.strategy == "honest")
+            .map(|node| node.index)
+            .collect();
+
+        // First and last time an honest node entered each view, and how many did.
+        let mut entered: BTreeMap<u64, (Duration, Duration, usize)> = BTreeMap::new();
+        for entry in report.trace.iter().filter(|entry| honest.contains(&entry.node)) {
+            if let Action::ViewEntered { view } = entry.action {
+                let times = entered.entry(view).or_insert((entry.at, entry.at, 0));
+                times.1 = entry.at;
+                times.2 += 1;
+            }
+        }
+        let latencies: Vec<ViewLatency> = entered
+            .iter()
+            .filter(|(_, (_, _, count))| *count == honest.len())
+            .map(|(view, (first, last, _))| ViewLatency {
+                view: *view,
+                latency_ms: (*last - *first).as_secs_f64() * 1000.0,
+            })
+            .collect();
+        let sync_ms = latencies.iter().map(|latency| latency.latency_ms);
+        let mean_sync_ms = if latencies.is_empty() {
+            0.0
+        } else {
+            sync_ms.clone().sum::<f64>() / latencies.len() as f64
+        };
+
+        let views = entered.keys().last().copied().unwrap_or(0);
+        let committed = report
+            .nodes
+            .iter()
+            .filter(|node| honest.contains(&node.index))
+            .map(|node| node.height)
+            .min()
+            .unwrap_or(0);
+        let stats = &report.stats;
+        let per_view = |total: u64| total as f64 / views.max(1) as f64;
+        Measurement {
+            nodes: report.nodes.len(),
+            byzantine: report.nodes.len() - honest.len(),
+            seed: report.seed,
+            duration_ms: report.duration.as_millis() as u64,
+            views,
+            synced_views: latencies.len(),
+            mean_sync_ms,
+            max_sync_ms: sync_ms.fold(0.0, f64::max),
+            messages: stats.sent,
+            view_messages: stats.view_messages,
+            payload_bytes: stats.bytes,
+            messages_per_view: per_view(stats.sent),
+            payload_bytes_per_view: per_view(stats.bytes),
+            committed,
+            blocks_per_second: committed as f64 / report.duration.as_secs_f64().max(f64::MIN_POSITIVE),
+            latencies,
+        }
+    }
+}
+
+const CSV_HEADER: &str = "nodes,byzantine,seed,duration_ms,views,synced_views,mean_sync_ms,max_sync_ms,\
+messages,view_messages,payload_bytes,messages_per_view,payload_bytes_per_view,committed,blocks_per_second";
+
+// One row per run. Per-view latencies are only in the JSON output.
+pub fn to_csv(measurements: &[Measurement]) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+    for m in measurements {
+        // Writing to a String cannot fail.
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{:.3},{:.3},{},{},{},{:.3},{:.3},{},{:.6}",
+            m.nodes,
+            m.byzantine,
+            m.seed,
+            m.duration_ms,
+            m.views,
+            m.synced_views,
+            m.mean_sync_ms,
+            m.max_sync_ms,
+            m.messages,
+            m.view_messages,
+            m.payload_bytes,
+            m.messages_per_view,
+            m.payload_bytes_per_view,
+            m.committed,
+            m.blocks_per_second
+        );
+    }
+    csv
+}
@@ -0,0 +1,116 @@
+use clap::{Parser, ValueEnum};
+use simulator::bench::{self, Measurement};
+use simulator::network::{Latency, NetworkConfig};
+use simulator::simulation::{max_faults, SimConfig, Simulation};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Csv,
+    Json,
+}
+
+// Runs simulated clusters of every requested size and number of byzantine
+// nodes, and reports view synchronisation latency, message complexity and
+// throughput for plotting.
+#[derive(Parser, Debug)]
+#[command(name = "bench", about = "Measure view synchronisation and throughput of simulated feverbft clusters")]
+struct Args {
+    /// Cluster sizes, e.g. 4,7,10
+    #[arg(long, value_delimiter = ',', default_value = "4,7,10")]
+    nodes: Vec<usize>,
+
+    /// Numbers of byzantine nodes. Counts above the f a cluster tolerates are
+    /// skipped for that cluster
+    #[arg(long, value_delimiter = ',', default_value = "0,1")]
+    byzantine: Vec<usize>,
+
+    /// Strategy of the byzantine nodes, which are the last nodes of the cluster
+    #[arg(long, default_value = "silence")]
+    strategy: String,
+
+    /// Runs per configuration, with seeds counting up from --seed
+    #[arg(long, default_value_t = 3)]
+    runs: u64,
+
+    /// Seed of the first run of every configuration
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Number of rounds; leaders take turns in node order
+    #[arg(long, default_value_t = 20)]
+    rounds: u64,
+
+    /// Milliseconds between two rounds
+    #[arg(long, default_value_t = 6000)]
+    round_interval: u64,
+
+    /// Link latency: constant:<ms>, uniform:<min>-<max>, normal:<mean>,<std_dev>
+    /// or exponential:<mean>
+    #[arg(long, default_value = "constant:50")]
+    latency: Latency,
+
+    /// Maximum milliseconds a node's view clock starts ahead of the others
+    #[arg(long, default_value_t = 1000)]
+    clock_skew: u64,
+
+    /// Milliseconds per view
+    #[arg(long, default_value_t = peer::viewsync::VIEW_LENGTH_MS)]
+    view_length: u64,
+
+    /// Output format
+    #[arg(long, value_enum, default_value = "csv")]
+    format: Format,
+
+    /// Write the results here instead of to stdout
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let mut measurements = Vec::new();
+    for &nodes in &args.nodes {
+        for &byzantine in &args.byzantine {
+            if byzantine > 0 && byzantine > nodes.saturating_sub(1) / 3 {
+                eprintln!("Skipping {} byzantine nodes out of {}", byzantine, nodes);
+                continue;
+            }
+            for run in 0..args.runs {
+                let config = SimConfig {
+                    nodes,
+                    seed: args.seed + run,
+                    rounds: args.rounds,
+                    round_interval: Duration::from_millis(args.round_interval),
+                    network: NetworkConfig {
+                        latency: args.latency.clone(),
+                        ..NetworkConfig::default()
+                    },
+                    drift: 0.0,
+                    clock_skew: Duration::from_millis(args.clock_skew),
+                    view_length: Duration::from_millis(args.view_length),
+                    faults: max_faults(nodes),
+                    byzantine: (nodes - byzantine..nodes)
+                        .map(|index| (index, args.strategy.clone()))
+                        .collect(),
+                };
+                eprintln!("Running {} nodes, {} byzantine, seed {}", nodes, byzantine, config.seed);
+                let report = Simulation::new(config)?.run();
+                measurements.push(Measurement::from_report(&report));
+            }
+        }
+    }
+
+    let output = match args.format {
+        Format::Csv => bench::to_csv(&measurements),
+        Format::Json => serde_json::to_string_pretty(&measurements)? + "\n",
+    };
+    match &args.output {
+        Some(path) => fs::write(path, output)?,
+        None => print!("{}", output),
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use clap::Parser;
+use peer::events::LogEntry;
+use simulator::checker::{self, CheckConfig};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+// Checks the event logs of a run, from the simulator or from real peers.
+#[derive(Parser, Debug)]
+#[command(name = "check", about = "Check agreement, validity and view sync over node event logs")]
+struct Args {
+    /// JSON lines event logs; one merged file or one per node
+    #[arg(required = true)]
+    logs: Vec<PathBuf>,
+
+    /// Milliseconds after the first logged event at which the network
+    /// stabilised. View sync is not checked without it
+    #[arg(long)]
+    gst: Option<u64>,
+
+    /// Milliseconds after GST within which all honest nodes must reach a view
+    /// once one of them entered it
+    #[arg(long, default_value_t = 10000)]
+    view_sync_bound: u64,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let mut entries = Vec::new();
+    for path in &args.logs {
+        for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: LogEntry = serde_json::from_str(line)
+                .map_err(|e| format!("{}:{}: {}", path.display(), number + 1, e))?;
+            entries.push(entry);
+        }
+    }
+    // Stable, so events logged at the same millisecond keep their order.
+    entries.sort_by_key(|entry| entry.at_ms);
+
+    let config = CheckConfig {
+        gst: args.gst.map(Duration::from_millis),
+        view_sync_bound: Duration::from_millis(args.view_sync_bound),
+    };
+    let violations = checker::check(&entries, &config);
+    if violations.is_empty() {
+        println!("All invariants hold over {} events", entries.len());
+        return Ok(());
+    }
+    for violation in &violations {
+        println!("{}", violation);
+    }
+    println!("Counter-example for the first violation:");
+    for entry in checker::counter_example(&entries, &violations[0]) {
+        println!("  {}", serde_json::to_string(&entry)?);
+    }
+    process::exit(1);
+}
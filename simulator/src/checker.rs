@@ -0,0 +1,274 @@
+use peer::events::{LogEntry, NodeEvent};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::time::Duration;
+
+// What the checker assumes about the run it is given.
+#[derive(Debug, Clone)]
+pub struct CheckConfig {
+    // View sync is only required after GST, counted from the first entry of the
+    // log, since peers log wall-clock milliseconds.
+    pub gst: Option<Duration>,
+    // How long after an honest node enters a view every other honest node must
+    // have entered it too.
+    pub view_sync_bound: Duration,
+}
+
+impl Default for CheckConfig {
+    fn default() -> Self {
+        CheckConfig {
+            gst: None,
+            view_sync_bound: Duration::from_millis(10000),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    // Two honest nodes committed different blocks at the same height.
+    Disagreement {
+        height: u64,
+        first: LogEntry,
+        second: LogEntry,
+    },
+    // An honest node committed a value no leader proposed for that view and height.
+    Invalid { commit: LogEntry },
+    // After GST, an honest node had not entered `view` within the bound after
+    // another honest node did.
+    SlowViewSync {
+        view: u64,
+        entered: LogEntry,
+        lagging: String,
+        caught_up_ms: Option<u64>,
+    },
+}
+
+impl Violation {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Violation::Disagreement { .. } => "agreement",
+            Violation::Invalid { .. } => "validity",
+            Violation::SlowViewSync { .. } => "view sync",
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Disagreement { height, first, second } => write!(
+                f,
+                "agreement violated at height {}: {} committed {} at {} ms, {} committed {} at {} ms",
+                height,
+                first.node,
+                short(committed_hash(first)),
+                first.at_ms,
+                second.node,
+                short(committed_hash(second)),
+                second.at_ms
+            ),
+            Violation::Invalid { commit } => write!(
+                f,
+                "validity violated: {} committed a block at {} ms that no leader proposed ({:?})",
+                commit.node, commit.at_ms, commit.event
+            ),
+            Violation::SlowViewSync {
+                view,
+                entered,
+                lagging,
+                caught_up_ms,
+            } => match caught_up_ms {
+                Some(at) => write!(
+                    f,
+                    "view sync violated: {} entered view {} at {} ms, {} only at {} ms",
+                    entered.node, view, entered.at_ms, lagging, at
+                ),
+                None => write!(
+                    f,
+                    "view sync violated: {} entered view {} at {} ms, {} never did",
+                    entered.node, view, entered.at_ms, lagging
+                ),
+            },
+        }
+    }
+}
+
+fn committed_hash(entry: &LogEntry) -> &str {
+    match &entry.event {
+        NodeEvent::Committed { hash, .. } => hash,
+        _ => "",
+    }
+}
+
+fn short(hash: &str) -> &str {
+    &hash[..8.min(hash.len())]
+}
+
+// Nodes that announced a byzantine strategy, at startup or by switching to one
+// later. Nodes that never announced one are taken to be honest.
+fn byzantine_nodes(entries: &[LogEntry]) -> HashSet<&str> {
+    entries
+        .iter()
+        .filter_map(|entry| match &entry.event {
+            NodeEvent::Started { strategy } if strategy != "honest" => Some(entry.node.as_str()),
+            NodeEvent::RoleChanged { to, .. } if to != "honest" => Some(entry.node.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+// Checks agreement, validity and view sync over the merged logs of every node,
+// ordered by time. Returns at most one violation per height or view.
+pub fn check(entries: &[LogEntry], config: &CheckConfig) -> Vec<Violation> {
+    let byzantine = byzantine_nodes(entries);
+    let honest = |entry: &&LogEntry| !byzantine.contains(entry.node.as_str());
+    let mut violations = Vec::new();
+
+    let proposed: HashSet<_> = entries
+        .iter()
+        .filter_map(|entry| match &entry.event {
+            NodeEvent::Proposed { view, height, value } => Some((*view, *height, *value)),
+            _ => None,
+        })
+        .collect();
+    let mut first_commit: BTreeMap<u64, &LogEntry> = BTreeMap::new();
+    let mut disagreeing = HashSet::new();
+    let mut invalid = HashSet::new();
+    for entry in entries.iter().filter(honest) {
+        let NodeEvent::Committed {
+            view,
+            height,
+            hash,
+            value,
+            ..
+        } = &entry.event
+        else {
+            continue;
+        };
+        if !proposed.contains(&(*view, *height, *value)) && invalid.insert(hash.clone()) {
+            violations.push(Violation::Invalid { commit: entry.clone() });
+        }
+        match first_commit.get(height) {
+            None => {
+                first_commit.insert(*height, entry);
+            }
+            Some(first) => {
+                if committed_hash(first) != committed_hash(entry) && disagreeing.insert(*height) {
+                    violations.push(Violation::Disagreement {
+                        height: *height,
+                        first: (*first).clone(),
+                        second: entry.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    if let Some(gst) = config.gst {
+        violations.extend(check_view_sync(entries, &byzantine, gst, config.view_sync_bound));
+    }
+    violations
+}
+
+fn check_view_sync(
+    entries: &[LogEntry],
+    byzantine: &HashSet<&str>,
+    gst: Duration,
+    bound: Duration,
+) -> Vec<Violation> {
+    let start = entries.iter().map(|entry| entry.at_ms).min().unwrap_or(0);
+    let gst = start + gst.as_millis() as u64;
+    let bound = bound.as_millis() as u64;
+    let end = entries.iter().map(|entry| entry.at_ms).max().unwrap_or(0);
+
+    // When each honest node entered each of its views, in order.
+    let mut views: BTreeMap<&str, Vec<(u64, u64)>> = BTreeMap::new();
+    for entry in entries {
+        if byzantine.contains(entry.node.as_str()) {
+            continue;
+        }
+        let node_views = views.entry(entry.node.as_str()).or_default();
+        if let NodeEvent::ViewEntered { view } = entry.event {
+            node_views.push((entry.at_ms, view));
+        }
+    }
+    let mut first_entered: HashMap<u64, &LogEntry> = HashMap::new();
+    for entry in entries {
+        if let NodeEvent::ViewEntered { view } = entry.event {
+            if !byzantine.contains(entry.node.as_str()) {
+                first_entered.entry(view).or_insert(entry);
+            }
+        }
+    }
+
+    let mut entered: Vec<_> = first_entered.into_iter().collect();
+    entered.sort_by_key(|(view, _)| *view);
+    let mut violations = Vec::new();
+    for (view, entry) in entered {
+        // Views entered before GST only have to be reached `bound` after it.
+        let deadline = entry.at_ms.max(gst) + bound;
+        if deadline > end {
+            // The log ends before we could tell.
+            continue;
+        }
+        for (node, node_views) in &views {
+            let caught_up = node_views
+                .iter()
+                .find(|(_, entered)| *entered >= view)
+                .map(|(at, _)| *at);
+            if caught_up.is_none_or(|at| at > deadline) {
+                violations.push(Violation::SlowViewSync {
+                    view,
+                    entered: entry.clone(),
+                    lagging: node.to_string(),
+                    caught_up_ms: caught_up,
+                });
+                break;
+            }
+        }
+    }
+    violations
+}
+
+// The part of the log that explains a violation: node announcements and the
+// events of the heights, views and nodes involved.
+pub fn counter_example(entries: &[LogEntry], violation: &Violation) -> Vec<LogEntry> {
+    let relevant = |entry: &LogEntry| -> bool {
+        if matches!(entry.event, NodeEvent::Started { .. }) {
+            return true;
+        }
+        match violation {
+            Violation::Disagreement { height, .. } => match entry.event {
+                NodeEvent::Proposed { height: h, .. } | NodeEvent::Committed { height: h, .. } => h == *height,
+                _ => false,
+            },
+            Violation::Invalid { commit } => {
+                let NodeEvent::Committed { view, height, .. } = commit.event else {
+                    return false;
+                };
+                match entry.event {
+                    NodeEvent::Proposed { height: h, .. } | NodeEvent::Committed { height: h, .. } => h == height,
+                    NodeEvent::ViewEntered { view: v } => v == view,
+                    _ => false,
+                }
+            }
+            Violation::SlowViewSync {
+                view,
+                entered,
+                lagging,
+                caught_up_ms,
+            } => {
+                let involved = entry.node == entered.node || entry.node == *lagging;
+                let until = caught_up_ms.unwrap_or(u64::MAX);
+                let in_window = entry.at_ms <= until;
+                match entry.event {
+                    NodeEvent::ViewEntered { view: v } | NodeEvent::Proposed { view: v, .. } => {
+                        involved && in_window && v + 1 >= *view
+                    }
+                    _ => false,
+                }
+            }
+        }
+    };
+    entries.iter().filter(|entry| relevant(entry)).cloned().collect()
+}
@@ -0,0 +1,5 @@
+pub mod bench;
+pub mod checker;
+pub mod network;
+pub mod scenario;
+pub mod simulation;
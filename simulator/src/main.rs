@@ -0,0 +1,239 @@
+use clap::Parser;
+use peer::protocol::short_hash;
+use simulator::checker::{self, CheckConfig, Violation};
+use simulator::network::{Latency, NetworkConfig, Partition};
+use simulator::scenario::Scenario;
+use simulator::simulation::{describe, max_faults, SimConfig, Simulation};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// Runs a cluster of peers in one process over a simulated network.
+#[derive(Parser, Debug)]
+#[command(name = "simulator", about = "Deterministic in-process simulation of a feverbft cluster")]
+struct Args {
+    /// Scenario file; replaces the options from --nodes to --byzantine, while --seed, --trace,
+    /// --events, --check and --view-sync-bound still apply
+    #[arg(long)]
+    scenario: Option<PathBuf>,
+
+    /// Number of nodes
+    #[arg(long, default_value_t = 4)]
+    nodes: usize,
+
+    /// Seed for every random choice in the run; a random one is picked and
+    /// printed when omitted
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Number of rounds; leaders take turns in node order
+    #[arg(long, default_value_t = 10)]
+    rounds: u64,
+
+    /// Milliseconds between two rounds
+    #[arg(long, default_value_t = 6000)]
+    round_interval: u64,
+
+    /// Link latency: constant:<ms>, uniform:<min>-<max>, normal:<mean>,<std_dev>
+    /// or exponential:<mean>
+    #[arg(long, default_value = "constant:50")]
+    latency: Latency,
+
+    /// Probability that a message is lost
+    #[arg(long, default_value_t = 0.0)]
+    loss: f64,
+
+    /// Let messages on the same link overtake each other
+    #[arg(long)]
+    reorder: bool,
+
+    /// Maximum relative clock drift per node, e.g. 0.01 for ±1%
+    #[arg(long, default_value_t = 0.0)]
+    drift: f64,
+
+    /// Maximum milliseconds a node's view clock starts ahead of the others
+    #[arg(long, default_value_t = 0)]
+    clock_skew: u64,
+
+    /// Milliseconds per view
+    #[arg(long, default_value_t = peer::viewsync::VIEW_LENGTH_MS)]
+    view_length: u64,
+
+    /// Byzantine nodes tolerated; defaults to the most the cluster size allows
+    #[arg(long)]
+    faults: Option<usize>,
+
+    /// Partition as <from>-<until>:<nodes>|<nodes> in milliseconds, e.g.
+    /// 10000-30000:0,1|2,3. May be repeated
+    #[arg(long)]
+    partition: Vec<Partition>,
+
+    /// Milliseconds after which partitions and loss stop; view sync is measured
+    /// from here
+    #[arg(long)]
+    gst: Option<u64>,
+
+    /// Byzantine node as <index>=<strategy>, e.g. 3=inverted. May be repeated
+    #[arg(long, value_parser = parse_byzantine)]
+    byzantine: Vec<(usize, String)>,
+
+    /// Print every action of every node
+    #[arg(long)]
+    trace: bool,
+
+    /// Write the nodes' event logs to this file as JSON lines
+    #[arg(long)]
+    events: Option<PathBuf>,
+
+    /// Check agreement, validity and view sync, and shrink any failing run to
+    /// the fewest rounds that still fail
+    #[arg(long)]
+    check: bool,
+
+    /// Milliseconds after GST within which all honest nodes must reach a view
+    /// once one of them entered it
+    #[arg(long, default_value_t = 10000)]
+    view_sync_bound: u64,
+}
+
+fn parse_byzantine(spec: &str) -> Result<(usize, String), String> {
+    let (index, strategy) = spec
+        .split_once('=')
+        .ok_or_else(|| format!("invalid byzantine node '{}', expected <index>=<strategy>", spec))?;
+    let index = index
+        .parse()
+        .map_err(|_| format!("invalid node index '{}'", index))?;
+    Ok((index, strategy.to_string()))
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let config = match &args.scenario {
+        Some(path) => {
+            let scenario = Scenario::load(path)?;
+            println!("Running scenario {}", scenario.name);
+            scenario.config(args.seed)
+        }
+        None => SimConfig {
+            nodes: args.nodes,
+            seed: args.seed.unwrap_or_else(rand::random),
+            rounds: args.rounds,
+            round_interval: Duration::from_millis(args.round_interval),
+            network: NetworkConfig {
+                latency: args.latency,
+                loss: args.loss,
+                reorder: args.reorder,
+                partitions: args.partition,
+                spikes: Vec::new(),
+                gst: args.gst.map(Duration::from_millis),
+            },
+            drift: args.drift,
+            clock_skew: Duration::from_millis(args.clock_skew),
+            view_length: Duration::from_millis(args.view_length),
+            faults: args.faults.unwrap_or(max_faults(args.nodes)),
+            byzantine: args.byzantine.into_iter().collect::<BTreeMap<_, _>>(),
+        },
+    };
+
+    let report = Simulation::new(config.clone())?.run();
+    if args.trace {
+        for entry in &report.trace {
+            println!(
+                "[{:>9.3}s] node {}: {}",
+                entry.at.as_secs_f64(),
+                entry.node,
+                describe(&entry.action)
+            );
+        }
+    }
+
+    println!("Simulated {:.1}s with seed {}", report.duration.as_secs_f64(), report.seed);
+    for node in &report.nodes {
+        println!(
+            "node {} ({}, {}, clock x{:.4}): height {}, tip {}",
+            node.index,
+            node.peer_id,
+            node.strategy,
+            node.clock_rate,
+            node.height,
+            short_hash(&node.tip)
+        );
+    }
+    let stats = &report.stats;
+    println!(
+        "Messages: {} sent ({} payload bytes, {} for view sync), {} delivered, {} lost, {} partitioned, {} rejected",
+        stats.sent, stats.bytes, stats.view_messages, stats.delivered, stats.lost, stats.partitioned, stats.rejected
+    );
+    if let Some(gst) = report.gst {
+        println!(
+            "GST at {:.1}s: honest views synchronised {}, honest chains agreed {}",
+            gst.as_secs_f64(),
+            after_gst(report.view_sync),
+            after_gst(report.agreement)
+        );
+    }
+    if let Some(path) = &args.events {
+        write_events(path, &report.log())?;
+    }
+    if args.check {
+        let check_config = CheckConfig {
+            gst: report.gst,
+            view_sync_bound: Duration::from_millis(args.view_sync_bound),
+        };
+        let violations = checker::check(&report.log(), &check_config);
+        if violations.is_empty() {
+            println!("All invariants hold");
+        }
+        for violation in &violations {
+            println!("{}", violation);
+        }
+        if let Some(violation) = violations.first() {
+            shrink(&config, &check_config, violation)?;
+        }
+    }
+    println!("Replay this run with --seed {} and the same options", report.seed);
+    Ok(())
+}
+
+// Re-runs the same seed with fewer rounds until the first kind of violation
+// shows up, and prints the events that explain it.
+fn shrink(config: &SimConfig, check_config: &CheckConfig, violation: &Violation) -> Result<(), Box<dyn Error>> {
+    for rounds in 1..=config.rounds {
+        let shorter = SimConfig {
+            rounds,
+            ..config.clone()
+        };
+        let log = Simulation::new(shorter)?.run().log();
+        let found = checker::check(&log, check_config)
+            .into_iter()
+            .find(|found| found.kind() == violation.kind());
+        if let Some(found) = found {
+            println!("Smallest failing run: --rounds {} ({})", rounds, found);
+            for entry in checker::counter_example(&log, &found) {
+                println!("  {}", serde_json::to_string(&entry)?);
+            }
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn write_events(path: &Path, log: &[peer::events::LogEntry]) -> Result<(), Box<dyn Error>> {
+    let mut file = BufWriter::new(File::create(path)?);
+    for entry in log {
+        serde_json::to_writer(&mut file, entry)?;
+        writeln!(file)?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+fn after_gst(latency: Option<Duration>) -> String {
+    match latency {
+        Some(latency) => format!("{:.3}s after GST", latency.as_secs_f64()),
+        None => "never".to_string(),
+    }
+}
@@ -0,0 +1,241 @@
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde::{de, Deserialize, Deserializer};
+use std::str::FromStr;
+use std::time::Duration;
+
+// One-way delay of a simulated link, drawn per message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Latency {
+    Constant(Duration),
+    Uniform { min: Duration, max: Duration },
+    // Normally distributed, cut off at zero.
+    Normal { mean: Duration, std_dev: Duration },
+    Exponential { mean: Duration },
+}
+
+impl Latency {
+    pub fn sample(&self, rng: &mut StdRng) -> Duration {
+        match self {
+            Latency::Constant(delay) => *delay,
+            Latency::Uniform { min, max } => {
+                if min >= max {
+                    *min
+                } else {
+                    rng.gen_range(*min..=*max)
+                }
+            }
+            Latency::Normal { mean, std_dev } => {
+                // Box-Muller transform, so we only need uniform samples.
+                let u1: f64 = 1.0 - rng.gen::<f64>();
+                let u2: f64 = rng.gen();
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+                Duration::from_secs_f64((mean.as_secs_f64() + z * std_dev.as_secs_f64()).max(0.0))
+            }
+            Latency::Exponential { mean } => {
+                let u: f64 = 1.0 - rng.gen::<f64>();
+                Duration::from_secs_f64(-u.ln() * mean.as_secs_f64())
+            }
+        }
+    }
+}
+
+// Parses "constant:50", "uniform:10-200", "normal:80,20" and "exponential:60",
+// all in milliseconds.
+impl FromStr for Latency {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (kind, args) = spec
+            .split_once(':')
+            .ok_or_else(|| format!("invalid latency '{}', expected <distribution>:<ms>", spec))?;
+        match kind {
+            "constant" => Ok(Latency::Constant(millis(args)?)),
+            "uniform" => {
+                let (min, max) = args
+                    .split_once('-')
+                    .ok_or_else(|| format!("invalid uniform latency '{}', expected min-max", args))?;
+                Ok(Latency::Uniform {
+                    min: millis(min)?,
+                    max: millis(max)?,
+                })
+            }
+            "normal" => {
+                let (mean, std_dev) = args
+                    .split_once(',')
+                    .ok_or_else(|| format!("invalid normal latency '{}', expected mean,std_dev", args))?;
+                Ok(Latency::Normal {
+                    mean: millis(mean)?,
+                    std_dev: millis(std_dev)?,
+                })
+            }
+            "exponential" => Ok(Latency::Exponential { mean: millis(args)? }),
+            _ => Err(format!(
+                "unknown latency distribution '{}', expected constant, uniform, normal or exponential",
+                kind
+            )),
+        }
+    }
+}
+
+pub(crate) fn millis(value: &str) -> Result<Duration, String> {
+    value
+        .trim()
+        .parse()
+        .map(Duration::from_millis)
+        .map_err(|_| format!("invalid duration '{}', expected milliseconds", value))
+}
+
+// Scenario files give times as plain milliseconds.
+pub(crate) fn de_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    u64::deserialize(deserializer).map(Duration::from_millis)
+}
+
+// Scenario files write latencies in the same form as the command line.
+pub(crate) fn de_parsed<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = String>,
+{
+    String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+}
+
+// Splits the nodes into groups that cannot reach each other between `from` and
+// `until`. Nodes not listed in any group are cut off from everyone.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Partition {
+    #[serde(deserialize_with = "de_millis")]
+    pub from: Duration,
+    #[serde(deserialize_with = "de_millis")]
+    pub until: Duration,
+    pub groups: Vec<Vec<usize>>,
+}
+
+impl Partition {
+    pub fn separates(&self, a: usize, b: usize, at: Duration) -> bool {
+        if at < self.from || at >= self.until {
+            return false;
+        }
+        let group = |node| self.groups.iter().position(|group| group.contains(&node));
+        match (group(a), group(b)) {
+            (Some(x), Some(y)) => x != y,
+            _ => a != b,
+        }
+    }
+}
+
+// Parses "<from>-<until>:<group>|<group>", times in milliseconds and groups as
+// comma separated node indices, e.g. "10000-30000:0,1|2,3".
+impl FromStr for Partition {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid partition '{}', expected <from>-<until>:<nodes>|<nodes>", spec);
+        let (window, groups) = spec.split_once(':').ok_or_else(invalid)?;
+        let (from, until) = window.split_once('-').ok_or_else(invalid)?;
+        let groups = groups
+            .split('|')
+            .map(|group| {
+                group
+                    .split(',')
+                    .map(|node| node.trim().parse().map_err(|_| invalid()))
+                    .collect()
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Partition {
+            from: millis(from)?,
+            until: millis(until)?,
+            groups,
+        })
+    }
+}
+
+// Replaces the latency of every link touching `nodes` (all links when empty)
+// between `from` and `until`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LatencySpike {
+    #[serde(deserialize_with = "de_millis")]
+    pub from: Duration,
+    #[serde(deserialize_with = "de_millis")]
+    pub until: Duration,
+    #[serde(default)]
+    pub nodes: Vec<usize>,
+    #[serde(deserialize_with = "de_parsed")]
+    pub latency: Latency,
+}
+
+impl LatencySpike {
+    fn applies(&self, a: usize, b: usize, at: Duration) -> bool {
+        at >= self.from
+            && at < self.until
+            && (self.nodes.is_empty() || self.nodes.contains(&a) || self.nodes.contains(&b))
+    }
+}
+
+// How the simulated network treats every message.
+#[derive(Debug, Clone)]
+pub struct NetworkConfig {
+    pub latency: Latency,
+    // Probability that a message is dropped.
+    pub loss: f64,
+    // Whether messages on the same link may overtake each other. Without it
+    // every link delivers in send order, like a TCP stream.
+    pub reorder: bool,
+    pub partitions: Vec<Partition>,
+    pub spikes: Vec<LatencySpike>,
+    // Global stabilisation time. From here on partitions, spikes and loss stop
+    // and only the base latency applies.
+    pub gst: Option<Duration>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            latency: Latency::Constant(Duration::from_millis(50)),
+            loss: 0.0,
+            reorder: false,
+            partitions: Vec::new(),
+            spikes: Vec::new(),
+            gst: None,
+        }
+    }
+}
+
+impl NetworkConfig {
+    pub fn stable(&self, at: Duration) -> bool {
+        self.gst.is_some_and(|gst| at >= gst)
+    }
+
+    pub fn partitioned(&self, a: usize, b: usize, at: Duration) -> bool {
+        !self.stable(at) && self.partitions.iter().any(|partition| partition.separates(a, b, at))
+    }
+
+    pub fn loss(&self, at: Duration) -> f64 {
+        if self.stable(at) {
+            0.0
+        } else {
+            self.loss
+        }
+    }
+
+    // The latency of the link between `a` and `b` at `at`; the latest spike wins.
+    pub fn latency(&self, a: usize, b: usize, at: Duration) -> &Latency {
+        if self.stable(at) {
+            return &self.latency;
+        }
+        self.spikes
+            .iter()
+            .rev()
+            .find(|spike| spike.applies(a, b, at))
+            .map_or(&self.latency, |spike| &spike.latency)
+    }
+
+    // The end of the last scheduled fault, used as GST when none is given.
+    pub fn last_fault(&self) -> Option<Duration> {
+        let partitions = self.partitions.iter().map(|partition| partition.until);
+        let spikes = self.spikes.iter().map(|spike| spike.until);
+        partitions.chain(spikes).max()
+    }
+}
@@ -0,0 +1,155 @@
+use crate::network::{de_millis, de_parsed, Latency, LatencySpike, NetworkConfig, Partition};
+use crate::simulation::{max_faults, SimConfig};
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+// A named run: cluster size, byzantine nodes and the faults the network goes
+// through before it stabilises. Written as YAML, e.g.
+//
+//     name: partition-heal
+//     nodes: 7
+//     byzantine: { 6: equivocation }
+//     partitions:
+//       - { from: 10000, until: 40000, groups: [[0, 1, 2], [3, 4, 5, 6]] }
+//
+// or built in Rust with `Scenario::new("partition-heal", 7).partition(...)`.
+// Times are milliseconds since the start of the run.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    pub nodes: usize,
+    pub seed: Option<u64>,
+    pub rounds: u64,
+    #[serde(deserialize_with = "de_millis")]
+    pub round_interval: Duration,
+    #[serde(deserialize_with = "de_parsed")]
+    pub latency: Latency,
+    pub loss: f64,
+    pub reorder: bool,
+    pub drift: f64,
+    #[serde(deserialize_with = "de_millis")]
+    pub clock_skew: Duration,
+    #[serde(deserialize_with = "de_millis")]
+    pub view_length: Duration,
+    // Defaults to the most an n = 3f + 1 cluster of this size tolerates.
+    pub faults: Option<usize>,
+    pub byzantine: BTreeMap<usize, String>,
+    pub partitions: Vec<Partition>,
+    pub spikes: Vec<LatencySpike>,
+    // Defaults to the end of the last partition or spike.
+    #[serde(deserialize_with = "de_optional_millis")]
+    pub gst: Option<Duration>,
+}
+
+fn de_optional_millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    Option::<u64>::deserialize(deserializer).map(|ms| ms.map(Duration::from_millis))
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        let config = SimConfig::default();
+        Scenario {
+            name: "unnamed".to_string(),
+            nodes: config.nodes,
+            seed: None,
+            rounds: config.rounds,
+            round_interval: config.round_interval,
+            latency: config.network.latency,
+            loss: 0.0,
+            reorder: false,
+            drift: 0.0,
+            clock_skew: config.clock_skew,
+            view_length: config.view_length,
+            faults: None,
+            byzantine: BTreeMap::new(),
+            partitions: Vec::new(),
+            spikes: Vec::new(),
+            gst: None,
+        }
+    }
+}
+
+impl Scenario {
+    pub fn new(name: &str, nodes: usize) -> Self {
+        Scenario {
+            name: name.to_string(),
+            nodes,
+            ..Scenario::default()
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        Ok(serde_yaml::from_str(&text)?)
+    }
+
+    pub fn rounds(mut self, rounds: u64) -> Self {
+        self.rounds = rounds;
+        self
+    }
+
+    pub fn latency(mut self, latency: Latency) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    pub fn byzantine(mut self, node: usize, strategy: &str) -> Self {
+        self.byzantine.insert(node, strategy.to_string());
+        self
+    }
+
+    pub fn partition(mut self, from_ms: u64, until_ms: u64, groups: &[&[usize]]) -> Self {
+        self.partitions.push(Partition {
+            from: Duration::from_millis(from_ms),
+            until: Duration::from_millis(until_ms),
+            groups: groups.iter().map(|group| group.to_vec()).collect(),
+        });
+        self
+    }
+
+    pub fn spike(mut self, from_ms: u64, until_ms: u64, nodes: &[usize], latency: Latency) -> Self {
+        self.spikes.push(LatencySpike {
+            from: Duration::from_millis(from_ms),
+            until: Duration::from_millis(until_ms),
+            nodes: nodes.to_vec(),
+            latency,
+        });
+        self
+    }
+
+    pub fn gst(mut self, at_ms: u64) -> Self {
+        self.gst = Some(Duration::from_millis(at_ms));
+        self
+    }
+
+    // The simulation config for this scenario. `seed` overrides the scenario's
+    // own seed.
+    pub fn config(&self, seed: Option<u64>) -> SimConfig {
+        let mut network = NetworkConfig {
+            latency: self.latency.clone(),
+            loss: self.loss,
+            reorder: self.reorder,
+            partitions: self.partitions.clone(),
+            spikes: self.spikes.clone(),
+            gst: self.gst,
+        };
+        network.gst = network.gst.or(network.last_fault()).or(Some(Duration::ZERO));
+        SimConfig {
+            nodes: self.nodes,
+            seed: seed.or(self.seed).unwrap_or_else(rand::random),
+            rounds: self.rounds,
+            round_interval: self.round_interval,
+            network,
+            drift: self.drift,
+            clock_skew: self.clock_skew,
+            view_length: self.view_length,
+            faults: self.faults.unwrap_or(max_faults(self.nodes)),
+            byzantine: self.byzantine.clone(),
+        }
+    }
+}
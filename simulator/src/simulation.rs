@@ -0,0 +1,602 @@
+use crate::network::NetworkConfig;
+use libp2p::identity::Keypair;
+use libp2p::PeerId;
+use peer::byzantine::{self, Recipients};
+use peer::consensus::{Action, Core, VOTE_WINDOW};
+use peer::events::{LogEntry, NodeEvent};
+use peer::protocol::{short_hash, BlockHash, Decision, Message};
+use peer::sync::{self, SyncRequest, SyncResponse};
+use peer::validation;
+use peer::validators::Validators;
+use peer::viewsync::{ViewSync, VIEW_LENGTH_MS};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+use std::time::Duration;
+
+pub use peer::validators::max_faults;
+
+// How often each node's view clock is advanced.
+const TICK: Duration = Duration::from_millis(100);
+
+// Everything that determines a run. The same config always produces the same
+// trace, so a failing run is replayed by re-running its seed.
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    pub nodes: usize,
+    pub seed: u64,
+    pub rounds: u64,
+    // Time between two leaders starting a round.
+    pub round_interval: Duration,
+    pub network: NetworkConfig,
+    // Each node's clock runs at a rate drawn from 1 ± drift.
+    pub drift: f64,
+    // Each node's view clock starts up to this far ahead.
+    pub clock_skew: Duration,
+    pub view_length: Duration,
+    // f; f+1 view announcements move a node's view clock.
+    pub faults: usize,
+    // Byzantine strategy per node index; everyone else is honest.
+    pub byzantine: BTreeMap<usize, String>,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            nodes: 4,
+            seed: 0,
+            rounds: 10,
+            round_interval: Duration::from_millis(6000),
+            network: NetworkConfig::default(),
+            drift: 0.0,
+            clock_skew: Duration::ZERO,
+            view_length: Duration::from_millis(VIEW_LENGTH_MS),
+            faults: 1,
+            byzantine: BTreeMap::new(),
+        }
+    }
+}
+
+// What travels over a simulated link.
+#[derive(Debug, Clone)]
+enum Packet {
+    Gossip(Vec<u8>),
+    SyncRequest(SyncRequest),
+    SyncResponse(SyncResponse),
+}
+
+impl Packet {
+    // Payload bytes; sync messages use the JSON codec of `/fever/sync/1`.
+    fn size(&self) -> usize {
+        match self {
+            Packet::Gossip(data) => data.len(),
+            Packet::SyncRequest(request) => serde_json::to_vec(request).map_or(0, |data| data.len()),
+            Packet::SyncResponse(response) => serde_json::to_vec(response).map_or(0, |data| data.len()),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Event {
+    // The leader of a round is told to propose.
+    Command { node: usize, value: Decision },
+    Deliver { from: usize, to: usize, packet: Packet },
+    // A round timer set by `Action::CloseRoundAfter` fires.
+    Timer { node: usize, view: u64 },
+    // A broadcast the strategy held back is due.
+    Publish { node: usize, message: Message, to: Recipients },
+    // A sync request got no answer in time.
+    SyncTimeout { node: usize, peer: usize },
+    // The node's clock advances.
+    Tick { node: usize },
+    // The network stabilises.
+    Gst,
+}
+
+struct Scheduled {
+    at: Duration,
+    // Breaks ties between events at the same instant in scheduling order.
+    seq: u64,
+    event: Event,
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        (self.at, self.seq) == (other.at, other.seq)
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.at, self.seq).cmp(&(other.at, other.seq))
+    }
+}
+
+pub struct SimNode {
+    pub core: Core,
+    // Local seconds per simulated second.
+    pub clock_rate: f64,
+}
+
+// An action a node took, stamped with the simulated time.
+#[derive(Debug, Clone)]
+pub struct TraceEntry {
+    pub at: Duration,
+    pub node: usize,
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub sent: u64,
+    // Encoded size of the messages sent, without the framing of the transport.
+    pub bytes: u64,
+    // View announcements and certificates among the messages sent.
+    pub view_messages: u64,
+    pub delivered: u64,
+    pub lost: u64,
+    pub partitioned: u64,
+    // Gossip the receiving node's validation refused.
+    pub rejected: u64,
+}
+
+pub struct NodeSummary {
+    pub index: usize,
+    pub peer_id: PeerId,
+    pub strategy: &'static str,
+    pub clock_rate: f64,
+    pub height: u64,
+    pub tip: BlockHash,
+}
+
+pub struct Report {
+    pub round_interval: Duration,
+    pub seed: u64,
+    pub duration: Duration,
+    pub gst: Option<Duration>,
+    // Time after GST until all honest nodes were in the same view.
+    pub view_sync: Option<Duration>,
+    // Time after GST until all honest nodes had the same chain tip.
+    pub agreement: Option<Duration>,
+    pub nodes: Vec<NodeSummary>,
+    pub stats: Stats,
+    pub trace: Vec<TraceEntry>,
+}
+
+pub struct Simulation {
+    config: SimConfig,
+    rng: StdRng,
+    now: Duration,
+    seq: u64,
+    queue: BinaryHeap<Reverse<Scheduled>>,
+    nodes: Vec<SimNode>,
+    peers: HashMap<PeerId, usize>,
+    // Latest delivery time per link, to keep links FIFO.
+    last_delivery: HashMap<(usize, usize), Duration>,
+    stats: Stats,
+    trace: Vec<TraceEntry>,
+    view_sync: Option<Duration>,
+    agreement: Option<Duration>,
+}
+
+impl Simulation {
+    pub fn new(config: SimConfig) -> Result<Self, String> {
+        if config.nodes < 2 {
+            return Err("the simulation needs at least 2 nodes".to_string());
+        }
+        if let Some(index) = config.byzantine.keys().find(|&&index| index >= config.nodes) {
+            return Err(format!("byzantine node {} does not exist", index));
+        }
+        for partition in &config.network.partitions {
+            let mut grouped = HashSet::new();
+            for &node in partition.groups.iter().flatten() {
+                if node >= config.nodes {
+                    return Err(format!("partitioned node {} does not exist", node));
+                }
+                if !grouped.insert(node) {
+                    return Err(format!("node {} is in more than one group of a partition", node));
+                }
+            }
+        }
+        let spiked = config.network.spikes.iter().flat_map(|spike| &spike.nodes);
+        if let Some(node) = spiked.into_iter().find(|&&node| node >= config.nodes) {
+            return Err(format!("latency spike node {} does not exist", node));
+        }
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let keypairs: Vec<Keypair> = (0..config.nodes)
+            .map(|_| {
+                let mut secret = [0u8; 32];
+                rng.fill(&mut secret);
+                Keypair::ed25519_from_bytes(secret).expect("32 bytes are a valid ed25519 secret")
+            })
+            .collect();
+        let validators = Validators::new(keypairs.iter().map(|keypair| keypair.public().to_peer_id()), config.faults)?;
+        let mut nodes = Vec::with_capacity(config.nodes);
+        let mut peers = HashMap::new();
+        for (index, keypair) in keypairs.into_iter().enumerate() {
+            let spec = config.byzantine.get(&index).map(String::as_str).unwrap_or("honest");
+            let strategy = byzantine::from_name(spec, rng.gen())?;
+            let clock_rate = if config.drift > 0.0 {
+                1.0 + rng.gen_range(-config.drift..=config.drift)
+            } else {
+                1.0
+            };
+            let skew = rng.gen_range(0..=config.clock_skew.as_millis() as u64);
+            let views = ViewSync::new(config.view_length.as_millis() as u64, config.faults + 1).with_offset(skew);
+            peers.insert(keypair.public().to_peer_id(), index);
+            nodes.push(SimNode {
+                core: Core::new(keypair, strategy, views, validators.clone()),
+                clock_rate,
+            });
+        }
+
+        let mut simulation = Simulation {
+            config,
+            rng,
+            now: Duration::ZERO,
+            seq: 0,
+            queue: BinaryHeap::new(),
+            nodes,
+            peers,
+            last_delivery: HashMap::new(),
+            stats: Stats::default(),
+            trace: Vec::new(),
+            view_sync: None,
+            agreement: None,
+        };
+        if let Some(gst) = simulation.config.network.gst {
+            simulation.schedule(gst, Event::Gst);
+        }
+        // Leaders take turns, starting one interval in so the initial status
+        // exchange settles first. Their instructions come from a separate stream
+        // so that a run with fewer rounds replays the same prefix.
+        let mut instructions = StdRng::seed_from_u64(simulation.config.seed.wrapping_add(1));
+        for round in 0..simulation.config.rounds {
+            let value = if instructions.gen::<bool>() {
+                Decision::Attack
+            } else {
+                Decision::Retreat
+            };
+            let node = (round % simulation.config.nodes as u64) as usize;
+            let at = simulation.config.round_interval * (round as u32 + 1);
+            simulation.schedule(at, Event::Command { node, value });
+        }
+        Ok(simulation)
+    }
+
+    pub fn nodes(&self) -> &[SimNode] {
+        &self.nodes
+    }
+
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    // Runs until the last round has had time to close and any sync triggered by
+    // it has finished, or nothing is left to do.
+    pub fn run(mut self) -> Report {
+        // Every node sees every other node connect, as mDNS does on a LAN.
+        for node in 0..self.nodes.len() {
+            self.schedule(Duration::ZERO, Event::Tick { node });
+            for peer in 0..self.nodes.len() {
+                if peer != node {
+                    let peer_id = self.nodes[peer].core.peer_id();
+                    let actions = self.nodes[node].core.on_peer_connected(peer_id);
+                    self.perform(node, actions);
+                }
+            }
+        }
+
+        let end = self.config.round_interval * (self.config.rounds as u32 + 1)
+            + VOTE_WINDOW * 2
+            + sync::REQUEST_TIMEOUT;
+        while let Some(Reverse(scheduled)) = self.queue.pop() {
+            if scheduled.at > end {
+                break;
+            }
+            self.now = scheduled.at;
+            self.handle(scheduled.event);
+            self.check_sync();
+        }
+        self.now = self.now.max(end);
+        self.report()
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Command { node, value } => {
+                let core = &self.nodes[node].core;
+                if !core.in_round() && !core.proposed_in_view() {
+                    let actions = self.nodes[node].core.propose(value);
+                    self.perform(node, actions);
+                }
+            }
+            Event::Deliver { from, to, packet } => {
+                self.stats.delivered += 1;
+                self.receive(from, to, packet);
+            }
+            Event::Timer { node, view } => {
+                let actions = self.nodes[node].core.close_round(view);
+                self.perform(node, actions);
+            }
+            Event::Publish { node, message, to } => self.broadcast(node, &message, to),
+            Event::SyncTimeout { node, peer } => {
+                let peer_id = self.nodes[peer].core.peer_id();
+                self.nodes[node].core.on_sync_failure(peer_id);
+            }
+            Event::Tick { node } => {
+                let local = self.now.mul_f64(self.nodes[node].clock_rate);
+                let actions = self.nodes[node].core.tick(local);
+                self.perform(node, actions);
+                self.schedule(self.now + TICK, Event::Tick { node });
+            }
+            Event::Gst => {}
+        }
+    }
+
+    // Records when the honest nodes first share a view and a chain tip after GST.
+    fn check_sync(&mut self) {
+        let Some(gst) = self.config.network.gst.filter(|&gst| self.now >= gst) else {
+            return;
+        };
+        let honest: Vec<&Core> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| !self.config.byzantine.contains_key(index))
+            .map(|(_, node)| &node.core)
+            .collect();
+        let Some(first) = honest.first() else {
+            return;
+        };
+        if self.view_sync.is_none() && honest.iter().all(|core| core.view() == first.view()) {
+            self.view_sync = Some(self.now - gst);
+        }
+        if self.agreement.is_none()
+            && honest
+                .iter()
+                .all(|core| core.chain().tip_hash() == first.chain().tip_hash())
+        {
+            self.agreement = Some(self.now - gst);
+        }
+    }
+
+    fn receive(&mut self, from: usize, to: usize, packet: Packet) {
+        let from_id = self.nodes[from].core.peer_id();
+        match packet {
+            Packet::Gossip(data) => {
+                // The same checks gossipsub validation runs before a real node
+                // sees the message.
+                match validation::validate(&data, self.nodes[to].core.view()) {
+                    Ok(message) => {
+                        let actions = self.nodes[to].core.on_message(from_id, message);
+                        self.perform(to, actions);
+                    }
+                    Err(_) => self.stats.rejected += 1,
+                }
+            }
+            Packet::SyncRequest(request) => match self.nodes[to].core.on_sync_request(request) {
+                Some(response) => {
+                    if !self.send(to, from, Packet::SyncResponse(response)) {
+                        self.sync_timeout(from, to);
+                    }
+                }
+                None => self.sync_timeout(from, to),
+            },
+            Packet::SyncResponse(response) => {
+                let actions = self.nodes[to].core.on_sync_response(from_id, response);
+                self.perform(to, actions);
+            }
+        }
+    }
+
+    fn perform(&mut self, node: usize, actions: Vec<Action>) {
+        for action in actions {
+            match &action {
+                Action::Broadcast { message, delay, to } if delay.is_zero() => {
+                    self.broadcast(node, message, *to);
+                }
+                Action::Broadcast { message, delay, to } => {
+                    let at = self.now + self.local_to_simulated(node, *delay);
+                    let message = message.clone();
+                    self.schedule(at, Event::Publish { node, message, to: *to });
+                }
+                Action::CloseRoundAfter { view, after } => {
+                    let at = self.now + self.local_to_simulated(node, *after);
+                    self.schedule(at, Event::Timer { node, view: *view });
+                }
+                Action::Sync { peer, request } => match self.peers.get(peer).copied() {
+                    Some(peer) => {
+                        if !self.send(node, peer, Packet::SyncRequest(request.clone())) {
+                            self.sync_timeout(node, peer);
+                        }
+                    }
+                    None => continue,
+                },
+                _ => {}
+            }
+            self.trace.push(TraceEntry {
+                at: self.now,
+                node,
+                action,
+            });
+        }
+    }
+
+    // Sends `message` to every other node among `recipients`. Messages for only
+    // some of them go out directly, which the receivers treat like gossip.
+    fn broadcast(&mut self, from: usize, message: &Message, recipients: Recipients) {
+        let data = message.encode();
+        let view_sync = matches!(message, Message::View(_) | Message::ViewCertificate(_));
+        let others = (0..self.nodes.len())
+            .filter(|&to| to != from)
+            .map(|to| self.nodes[to].core.peer_id())
+            .collect();
+        for peer in recipients.select(others) {
+            if view_sync {
+                self.stats.view_messages += 1;
+            }
+            self.send(from, self.peers[&peer], Packet::Gossip(data.clone()));
+        }
+    }
+
+    // Puts a packet on the link from `from` to `to`. Returns false if the
+    // network dropped it.
+    fn send(&mut self, from: usize, to: usize, packet: Packet) -> bool {
+        self.stats.sent += 1;
+        self.stats.bytes += packet.size() as u64;
+        if self.config.network.partitioned(from, to, self.now) {
+            self.stats.partitioned += 1;
+            return false;
+        }
+        let loss = self.config.network.loss(self.now);
+        if loss > 0.0 && self.rng.gen_bool(loss.min(1.0)) {
+            self.stats.lost += 1;
+            return false;
+        }
+        let latency = self.config.network.latency(from, to, self.now);
+        let mut at = self.now + latency.sample(&mut self.rng);
+        if !self.config.network.reorder {
+            let last = self.last_delivery.entry((from, to)).or_default();
+            at = at.max(*last);
+            *last = at;
+        }
+        self.schedule(at, Event::Deliver { from, to, packet });
+        true
+    }
+
+    // The requester gives up on `peer` after the request-response timeout.
+    fn sync_timeout(&mut self, node: usize, peer: usize) {
+        let at = self.now + self.local_to_simulated(node, sync::REQUEST_TIMEOUT);
+        self.schedule(at, Event::SyncTimeout { node, peer });
+    }
+
+    // A timer of `local` on the node's own clock, in simulated time.
+    fn local_to_simulated(&self, node: usize, local: Duration) -> Duration {
+        local.div_f64(self.nodes[node].clock_rate)
+    }
+
+    fn schedule(&mut self, at: Duration, event: Event) {
+        self.seq += 1;
+        self.queue.push(Reverse(Scheduled {
+            at,
+            seq: self.seq,
+            event,
+        }));
+    }
+
+    fn report(self) -> Report {
+        let nodes = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| NodeSummary {
+                index,
+                peer_id: node.core.peer_id(),
+                strategy: node.core.strategy_name(),
+                clock_rate: node.clock_rate,
+                height: node.core.chain().height(),
+                tip: node.core.chain().tip_hash(),
+            })
+            .collect();
+        Report {
+            seed: self.config.seed,
+            round_interval: self.config.round_interval,
+            duration: self.now,
+            gst: self.config.network.gst,
+            view_sync: self.view_sync,
+            agreement: self.agreement,
+            nodes,
+            stats: self.stats,
+            trace: self.trace,
+        }
+    }
+}
+
+impl Report {
+    // The trace as the event log a node writes, with nodes named by index.
+    pub fn log(&self) -> Vec<LogEntry> {
+        let started = self.nodes.iter().map(|node| LogEntry {
+            at_ms: 0,
+            node: format!("node-{}", node.index),
+            event: NodeEvent::Started {
+                strategy: node.strategy.to_string(),
+            },
+        });
+        let events = self.trace.iter().filter_map(|entry| {
+            NodeEvent::from_action(&entry.action).map(|event| LogEntry {
+                at_ms: entry.at.as_millis() as u64,
+                node: format!("node-{}", entry.node),
+                event,
+            })
+        });
+        started.chain(events).collect()
+    }
+}
+
+// One line per traced action, in the wording the peer uses in its logs.
+pub fn describe(action: &Action) -> String {
+    match action {
+        Action::Broadcast { message, delay, to } => {
+            let kind = match message {
+                Message::Proposal(proposal) => {
+                    format!("proposal {} for height {}", proposal.body.value, proposal.body.height)
+                }
+                Message::Vote(vote) => format!("vote {} for height {}", vote.body.value, vote.body.height),
+                Message::Evidence(evidence) => format!("evidence: {}", evidence),
+                Message::View(_) => "view announcement".to_string(),
+                Message::ViewCertificate(certificate) => {
+                    format!("view certificate with {} announcements", certificate.messages.len())
+                }
+            };
+            let to = match to {
+                Recipients::All => "",
+                Recipients::Lower => " to the lower half",
+                Recipients::Upper => " to the upper half",
+            };
+            if delay.is_zero() {
+                format!("Broadcast {}{} in view {}", kind, to, message.view())
+            } else {
+                format!("Broadcast {}{} in view {} after {:?}", kind, to, message.view(), delay)
+            }
+        }
+        Action::ViewEntered { view } => format!("Entered view {}", view),
+        Action::ClockBumped { from, to } => format!("View clock bumped from {} ms to {} ms", from, to),
+        Action::CloseRoundAfter { view, after } => format!("Counting votes for view {} for {:?}", view, after),
+        Action::Sync { peer, request } => format!("Sync {:?} from peer {}", request, peer),
+        Action::Decided {
+            view,
+            height,
+            attack,
+            retreat,
+            value,
+        } => format!(
+            "Decided {} at height {} in view {} (ATTACK {}, RETREAT {})",
+            value, height, view, attack, retreat
+        ),
+        Action::Applied { block, synced } => format!(
+            "Applied {}block {} ({}) at height {}",
+            if *synced { "synced " } else { "" },
+            short_hash(&block.hash()),
+            block.value,
+            block.height
+        ),
+        Action::Restored(block) => format!(
+            "Restored snapshot at height {} ({})",
+            block.height,
+            short_hash(&block.hash())
+        ),
+        Action::Rejected { height, error } => format!("Could not apply block at height {}: {}", height, error),
+        Action::CaughtUp { peer, height } => format!("Caught up with peer {} at height {}", peer, height),
+        Action::Equivocation(evidence) => format!("Equivocation detected: {}", evidence),
+    }
+}
@@ -0,0 +1,132 @@
+// The checker passes honest runs and points at the events behind a violation,
+// for simulated logs as well as for logs with wall-clock times.
+
+use peer::events::{LogEntry, NodeEvent};
+use peer::protocol::Decision;
+use simulator::checker::{self, CheckConfig, Violation};
+use simulator::scenario::Scenario;
+use simulator::simulation::Simulation;
+use std::time::Duration;
+
+// Peers log milliseconds since the epoch.
+const EPOCH_MS: u64 = 1_718_000_000_000;
+
+fn entry(at_ms: u64, node: &str, event: NodeEvent) -> LogEntry {
+    LogEntry {
+        at_ms,
+        node: node.to_string(),
+        event,
+    }
+}
+
+fn started(node: &str) -> LogEntry {
+    entry(
+        EPOCH_MS,
+        node,
+        NodeEvent::Started {
+            strategy: "honest".to_string(),
+        },
+    )
+}
+
+fn committed(at_ms: u64, node: &str, hash: &str, value: Decision) -> LogEntry {
+    entry(
+        at_ms,
+        node,
+        NodeEvent::Committed {
+            view: 1,
+            height: 1,
+            hash: hash.to_string(),
+            value,
+            synced: false,
+        },
+    )
+}
+
+fn view_entered(at_ms: u64, node: &str, view: u64) -> LogEntry {
+    entry(at_ms, node, NodeEvent::ViewEntered { view })
+}
+
+#[test]
+fn honest_runs_pass() {
+    let report = Simulation::new(Scenario::new("honest", 4).rounds(4).config(Some(1)))
+        .unwrap()
+        .run();
+    let config = CheckConfig {
+        gst: Some(Duration::ZERO),
+        ..CheckConfig::default()
+    };
+    assert_eq!(checker::check(&report.log(), &config), Vec::new());
+}
+
+#[test]
+fn conflicting_commits_violate_agreement() {
+    let proposed = entry(
+        EPOCH_MS + 6000,
+        "a",
+        NodeEvent::Proposed {
+            view: 1,
+            height: 1,
+            value: Decision::Attack,
+        },
+    );
+    let first = committed(EPOCH_MS + 6100, "a", "aaaaaaaaaa", Decision::Attack);
+    let second = committed(EPOCH_MS + 6200, "b", "bbbbbbbbbb", Decision::Attack);
+    let log = vec![
+        started("a"),
+        started("b"),
+        view_entered(EPOCH_MS + 6000, "a", 1),
+        proposed.clone(),
+        first.clone(),
+        second.clone(),
+    ];
+
+    let violations = checker::check(&log, &CheckConfig::default());
+    assert_eq!(
+        violations,
+        vec![Violation::Disagreement {
+            height: 1,
+            first: first.clone(),
+            second: second.clone(),
+        }]
+    );
+    assert_eq!(
+        checker::counter_example(&log, &violations[0]),
+        vec![started("a"), started("b"), proposed, first, second]
+    );
+}
+
+#[test]
+fn gst_counts_from_the_first_entry() {
+    // b reaches view 2 twelve seconds after a, with GST five seconds into the run.
+    let log = vec![
+        started("a"),
+        started("b"),
+        view_entered(EPOCH_MS + 6000, "a", 1),
+        view_entered(EPOCH_MS + 6000, "b", 1),
+        view_entered(EPOCH_MS + 12000, "a", 2),
+        view_entered(EPOCH_MS + 24000, "b", 2),
+        view_entered(EPOCH_MS + 30000, "a", 3),
+        view_entered(EPOCH_MS + 30000, "b", 3),
+    ];
+    let config = CheckConfig {
+        gst: Some(Duration::from_millis(5000)),
+        view_sync_bound: Duration::from_millis(10000),
+    };
+    assert_eq!(
+        checker::check(&log, &config),
+        vec![Violation::SlowViewSync {
+            view: 2,
+            entered: log[4].clone(),
+            lagging: "b".to_string(),
+            caught_up_ms: Some(EPOCH_MS + 24000),
+        }]
+    );
+
+    // Views entered before GST only count from GST on.
+    let late = CheckConfig {
+        gst: Some(Duration::from_millis(20000)),
+        ..config
+    };
+    assert_eq!(checker::check(&log, &late), Vec::new());
+}
@@ -0,0 +1,33 @@
+// Every scenario shipped in scenarios/ loads, runs and keeps the invariants the
+// checker knows about.
+
+use simulator::checker::{self, CheckConfig};
+use simulator::scenario::Scenario;
+use simulator::simulation::Simulation;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+#[test]
+fn shipped_scenarios_hold_every_invariant() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios");
+    let mut paths: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "yaml"))
+        .collect();
+    paths.sort();
+    assert_eq!(paths.len(), 3, "expected the three scenarios in {}", dir.display());
+
+    for path in paths {
+        let scenario = Scenario::load(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        let report = Simulation::new(scenario.config(None)).unwrap().run();
+        let config = CheckConfig {
+            gst: report.gst,
+            view_sync_bound: Duration::from_secs(10),
+        };
+        let violations = checker::check(&report.log(), &config);
+        assert!(violations.is_empty(), "{}: {:?}", scenario.name, violations);
+        assert!(report.agreement.is_some(), "{}: honest chains never agreed", scenario.name);
+    }
+}
@@ -0,0 +1,73 @@
+// Runs are reproducible: the same config gives the same trace, and the event
+// log a run writes reads back into the same checks.
+
+use peer::events::LogEntry;
+use simulator::checker::{self, CheckConfig};
+use simulator::scenario::Scenario;
+use simulator::simulation::{describe, SimConfig, Simulation};
+use std::time::Duration;
+
+// A short run that draws on every source of randomness: latency, loss, drift,
+// clock skew and a random voter.
+fn config(seed: u64) -> SimConfig {
+    Scenario {
+        loss: 0.05,
+        drift: 0.01,
+        clock_skew: Duration::from_millis(500),
+        ..Scenario::new("replay", 4)
+            .rounds(4)
+            .latency("uniform:20-200".parse().unwrap())
+            .byzantine(3, "random")
+    }
+    .config(Some(seed))
+}
+
+fn trace(config: SimConfig) -> Vec<String> {
+    Simulation::new(config)
+        .unwrap()
+        .run()
+        .trace
+        .iter()
+        .map(|entry| format!("{:?} {} {}", entry.at, entry.node, describe(&entry.action)))
+        .collect()
+}
+
+#[test]
+fn the_same_seed_replays_the_same_run() {
+    let first = trace(config(7));
+    assert!(!first.is_empty());
+    assert_eq!(first, trace(config(7)));
+    assert_ne!(first, trace(config(8)));
+}
+
+#[test]
+fn event_logs_replay_through_the_checker() {
+    let report = Simulation::new(config(7)).unwrap().run();
+    let log = report.log();
+    let lines: Vec<String> = log.iter().map(|entry| serde_json::to_string(entry).unwrap()).collect();
+    let replayed: Vec<LogEntry> = lines.iter().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(replayed, log);
+
+    let check_config = CheckConfig {
+        gst: report.gst,
+        view_sync_bound: Duration::from_secs(10),
+    };
+    assert_eq!(checker::check(&replayed, &check_config), checker::check(&log, &check_config));
+}
+
+#[test]
+fn faults_must_name_existing_nodes() {
+    let error = |scenario: Scenario| Simulation::new(scenario.config(Some(0))).err().unwrap();
+    assert_eq!(
+        error(Scenario::new("partition", 4).partition(0, 1000, &[&[0, 1], &[2, 4]])),
+        "partitioned node 4 does not exist"
+    );
+    assert_eq!(
+        error(Scenario::new("overlap", 4).partition(0, 1000, &[&[0, 1], &[1, 2, 3]])),
+        "node 1 is in more than one group of a partition"
+    );
+    assert_eq!(
+        error(Scenario::new("spike", 4).spike(0, 1000, &[5], "constant:500".parse().unwrap())),
+        "latency spike node 5 does not exist"
+    );
+}